@@ -0,0 +1,207 @@
+use std::{sync::Arc, time::Duration};
+use tracing::{error, info, warn};
+
+use anyhow::{Result, anyhow};
+use tokio::process::Command;
+
+use event_bus::Event;
+use registry_ops::{RegistryEntries, ScheduleAction, StopWarnings};
+
+use crate::{AppContext, CommandError, ControllerCommand, status};
+
+/// How long sleeping waits for the idle service to give up keeping the system awake, so our
+/// own inhibitor lock doesn't block it.
+const RELEASE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a command run at the stop time may take before it is killed, so a hanging one
+/// doesn't hold up the next stop.
+const RUN_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// The action configured to run at the stop time.
+///
+/// # Errors
+///
+/// Returns an error if the setting can't be read or parsed.
+pub fn configured(context: &AppContext) -> Result<ScheduleAction, CommandError> {
+    status::parse(context.settings().get(RegistryEntries::ScheduleAction))
+}
+
+/// Minutes before the stop time to warn at.
+///
+/// # Errors
+///
+/// Returns an error if the setting can't be read or parsed.
+pub fn warnings(context: &AppContext) -> Result<StopWarnings, CommandError> {
+    status::parse(context.settings().get(RegistryEntries::StopWarnings))
+}
+
+/// Stops keeping the system awake.
+fn pause(context: &AppContext) {
+    if let Some(service) = context.idle_service() {
+        service.pause();
+    }
+}
+
+/// Stops keeping the system awake and waits until the keep-awake lock is released, so it
+/// doesn't block sleeping.
+async fn release(context: &AppContext) {
+    let Some(service) = context.idle_service() else {
+        return;
+    };
+    if service.is_paused() {
+        return;
+    }
+    let mut subscription = context.events().subscribe("stop action");
+    service.pause();
+    let released = tokio::time::timeout(RELEASE_TIMEOUT, async {
+        while let Some(event) = subscription.next().await {
+            if matches!(event, Event::AssertionReleased) {
+                return;
+            }
+        }
+    })
+    .await;
+    if released.is_err() {
+        warn!("Keep-awake lock not released after {RELEASE_TIMEOUT:?}, going on anyway");
+    }
+}
+
+/// Runs `command` through the shell and waits for it to finish, killing it after `timeout`.
+async fn run(command: &str, timeout: Duration) -> Result<()> {
+    #[cfg(windows)]
+    let mut shell = {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    };
+    #[cfg(not(windows))]
+    let mut shell = {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let mut child = shell
+        .arg(command)
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|err| anyhow!("Failed to start {command:?}, err: {err}"))?;
+    let status = tokio::time::timeout(timeout, child.wait())
+        .await
+        .map_err(|_| anyhow!("{command:?} didn't finish within {timeout:?}, killed it"))?
+        .map_err(|err| anyhow!("Failed to wait for {command:?}, err: {err}"))?;
+    if status.success() {
+        info!("{command:?} finished");
+        Ok(())
+    } else {
+        Err(anyhow!("{command:?} failed with {status}"))
+    }
+}
+
+/// Performs `action`. Exiting is left to the front-ends, so this only publishes
+/// `ExitRequested` for [`ScheduleAction::Exit`].
+///
+/// # Errors
+///
+/// Returns an error if the system refuses to lock, sleep or hibernate, or the command fails.
+pub async fn perform(context: &AppContext, action: &ScheduleAction) -> Result<()> {
+    info!("Performing stop action {action}");
+    match action {
+        ScheduleAction::Exit => context.events().publish(Event::ExitRequested),
+        ScheduleAction::Pause => pause(context),
+        ScheduleAction::Lock => {
+            pause(context);
+            idler_utils::lock_session().await?;
+        }
+        ScheduleAction::Sleep => {
+            release(context).await;
+            idler_utils::suspend().await?;
+        }
+        ScheduleAction::Hibernate => {
+            release(context).await;
+            idler_utils::hibernate().await?;
+        }
+        ScheduleAction::Run(command) => {
+            pause(context);
+            run(command, RUN_TIMEOUT).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Hands the warning lead times to the scheduler.
+fn send_warnings(context: &AppContext, warnings: StopWarnings) {
+    if warnings.minutes().is_empty() {
+        info!("Not warning before the stop time");
+    } else {
+        info!("Warning {warnings} min before the stop time");
+    }
+    if let Err(err) = context
+        .scheduler()
+        .post(ControllerCommand::SetWarnings(warnings))
+    {
+        error!("Failed to hand the stop warnings to the scheduler, err: {err}");
+    }
+}
+
+/// Hands the stored warning lead times to the scheduler and follows the event bus to pick up
+/// changed ones and perform the configured action whenever the stop time is reached.
+///
+/// # Panics
+///
+/// Panics if called outside of a tokio runtime.
+pub fn spawn(context: Arc<AppContext>) {
+    let mut subscription = context.events().subscribe("stop action");
+    match warnings(&context) {
+        Ok(warnings) => send_warnings(&context, warnings),
+        Err(err) => error!("Not warning before the stop time, err: {err}"),
+    }
+
+    tokio::spawn(async move {
+        let entry = RegistryEntries::StopWarnings.to_string();
+        while let Some(event) = subscription.next().await {
+            match event {
+                Event::ScheduleFired { .. } => {
+                    let action = configured(&context).unwrap_or_else(|err| {
+                        warn!("Exiting instead of the stop action, err: {err}");
+                        ScheduleAction::Exit
+                    });
+                    // A command may run for a while, the events keep being read meanwhile.
+                    let context = Arc::clone(&context);
+                    tokio::spawn(async move {
+                        if let Err(err) = perform(&context, &action).await {
+                            error!("Failed to perform stop action {action}, err: {err}");
+                        }
+                    });
+                }
+                Event::SettingChanged { setting, value } if setting == entry => {
+                    match value.parse() {
+                        Ok(warnings) => send_warnings(&context, warnings),
+                        Err(err) => error!("Found invalid stop warnings {value:?}, err: {err}"),
+                    }
+                }
+                _ => {}
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn runs_commands_through_the_shell() {
+        run("exit 0", Duration::from_secs(5)).await.unwrap();
+        let err = run("exit 3", Duration::from_secs(5)).await.unwrap_err();
+        assert!(err.to_string().contains('3'), "{err}");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn kills_commands_running_too_long() {
+        let err = run("sleep 5", Duration::from_millis(100))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("didn't finish"), "{err}");
+    }
+}
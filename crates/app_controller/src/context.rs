@@ -0,0 +1,263 @@
+use std::{
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+use tracing::{info, trace, warn};
+
+use event_bus::{Event, EventBus};
+use idler_utils::IdleService;
+use registry_ops::{RegistryEntries, SettingsStore, ShutdownTime};
+
+use crate::{
+    Clock, CommandError, ControllerCommand, ScheduleState, Scheduler, SystemClock, status,
+};
+
+/// Everything the app shares between its components, created once at startup and handed to
+/// them explicitly. It doesn't depend on any UI: front-ends subscribe to the events, and exit
+/// after calling [`AppContext::shutdown`] once `ExitRequested` is published.
+pub struct AppContext {
+    settings: Arc<SettingsStore>,
+    events: EventBus,
+    scheduler: Scheduler,
+    idle_service: OnceLock<IdleService>,
+}
+
+impl AppContext {
+    /// Loads the settings and starts the scheduler. The statistics and the idle service are
+    /// started separately with [`AppContext::start_services`], once every subscriber of
+    /// `events` is listening.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    #[must_use]
+    pub fn new(events: EventBus) -> AppContext {
+        AppContext::with_store(SettingsStore::load(), events, SystemClock)
+    }
+
+    /// Like [`AppContext::new`], on `settings` and a scheduler on `clock`, e.g. an in-memory
+    /// store and a mock clock, so several contexts can run side by side.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    #[must_use]
+    pub fn with_store(settings: SettingsStore, events: EventBus, clock: impl Clock) -> AppContext {
+        AppContext {
+            settings: Arc::new(settings),
+            scheduler: Scheduler::with_clock(events.clone(), clock),
+            events,
+            idle_service: OnceLock::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn settings(&self) -> &SettingsStore {
+        &self.settings
+    }
+
+    #[must_use]
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    #[must_use]
+    pub fn scheduler(&self) -> &Scheduler {
+        &self.scheduler
+    }
+
+    /// The idle service, `None` until [`AppContext::start_services`] was called.
+    #[must_use]
+    pub fn idle_service(&self) -> Option<&IdleService> {
+        self.idle_service.get()
+    }
+
+    /// The idle service, for commands that need it running.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CommandError::NotReady`] until [`AppContext::start_services`] was called.
+    pub fn running_idle_service(&self) -> Result<&IdleService, CommandError> {
+        self.idle_service()
+            .ok_or_else(|| CommandError::NotReady("Idle service was not started".to_string()))
+    }
+
+    /// Writes the already validated `value` to `entry` and announces it on the event bus.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the setting can't be locked or written.
+    pub fn store(&self, entry: RegistryEntries, value: &impl ToString) -> Result<(), CommandError> {
+        let mut setting = match self.settings.get(entry).lock() {
+            Ok(setting) => setting,
+            Err(err) => return Err(CommandError::lock(entry, &err)),
+        };
+        let value = value.to_string();
+        if let Err(err) = setting.set_registry_data(&value) {
+            return Err(CommandError::registry(entry, &err));
+        }
+        trace!("Set {entry}: {value:?}");
+        self.events.publish(Event::setting_changed(entry, value));
+        Ok(())
+    }
+
+    /// Stores the daily stop `time` and schedules it, or cancels it with
+    /// [`ShutdownTime::Stopped`]. Doesn't wait for the scheduler to apply it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the setting can't be written or the scheduler stopped.
+    pub fn schedule_stop(&self, time: ShutdownTime) -> Result<(), CommandError> {
+        self.store(RegistryEntries::ShutdownTime, &time)?;
+        self.scheduler
+            .post(time.into())
+            .map_err(|err| CommandError::NotReady(err.to_string()))
+    }
+
+    /// Schedules the stored daily stop time, if one is set. Every front-end calls it once at
+    /// startup, so the stop time survives restarts.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the setting can't be read or the scheduler stopped.
+    pub fn schedule_stored_stop(&self) -> Result<(), CommandError> {
+        let time: ShutdownTime = status::parse(self.settings.get(RegistryEntries::ShutdownTime))?;
+        if time != ShutdownTime::Stopped {
+            info!("Stopping at {time}");
+        }
+        self.scheduler
+            .post(time.into())
+            .map_err(|err| CommandError::NotReady(err.to_string()))
+    }
+
+    /// Moves the pending stop later by `duration`, once.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no stop is pending or the scheduler stopped.
+    pub async fn postpone_stop(&self, duration: Duration) -> Result<ScheduleState, CommandError> {
+        let state = self
+            .scheduler
+            .send(ControllerCommand::Postpone(duration))
+            .await
+            .map_err(|err| CommandError::NotReady(err.to_string()))?;
+        if !state.is_active() {
+            return Err(CommandError::InvalidInput(
+                "No stop is scheduled to postpone".to_string(),
+            ));
+        }
+        Ok(state)
+    }
+
+    /// Starts the statistics and the idle service. Calling it more than once is a no-op.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub fn start_services(&self) {
+        if self.idle_service.get().is_some() {
+            warn!("Services already started");
+            return;
+        }
+        self.start_statistics();
+        let service = IdleService::start(Arc::clone(&self.settings), &self.events);
+        let _ = self.idle_service.set(service);
+    }
+
+    /// Stops the idle service, if it was started, waits for it to exit and saves the
+    /// statistics.
+    ///
+    /// Blocks the calling thread, so it must not be called from a current-thread runtime.
+    pub fn shutdown(&self) {
+        if let Some(service) = self.idle_service.get() {
+            service.shutdown();
+        } else {
+            warn!("Idle service was not started");
+        }
+        idle_stats::flush();
+    }
+
+    fn start_statistics(&self) {
+        let enabled = self
+            .settings
+            .get(RegistryEntries::LogStatistics)
+            .lock()
+            .is_ok_and(|setting| setting.is_enabled());
+        let retention_days = self
+            .settings
+            .get(RegistryEntries::StatisticsRetention)
+            .lock()
+            .ok()
+            .and_then(|setting| setting.last_data.parse().ok())
+            .unwrap_or(idle_stats::DEFAULT_RETENTION_DAYS);
+        idle_stats::init(idle_stats::default_path(), enabled, retention_days);
+        idle_stats::spawn_tasks(&self.events);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use event_bus::Subscription;
+
+    use super::*;
+    use crate::testing::published;
+
+    fn context() -> (AppContext, Subscription) {
+        let events = EventBus::new();
+        let subscription = events.subscribe("test");
+        let context = AppContext::with_store(SettingsStore::in_memory(), events, SystemClock);
+        (context, subscription)
+    }
+
+    fn read(context: &AppContext, entry: RegistryEntries) -> String {
+        context
+            .settings()
+            .get(entry)
+            .lock()
+            .unwrap()
+            .last_data
+            .clone()
+    }
+
+    #[tokio::test]
+    async fn keeps_contexts_apart() {
+        let (first, mut first_events) = context();
+        let (second, mut second_events) = context();
+
+        first.store(RegistryEntries::ForceInterval, &120).unwrap();
+        first.schedule_stop("18:00".parse().unwrap()).unwrap();
+        second.schedule_stop("07:30".parse().unwrap()).unwrap();
+
+        assert_eq!(read(&first, RegistryEntries::ForceInterval), "120");
+        assert_eq!(read(&second, RegistryEntries::ForceInterval), "60");
+        assert_eq!(read(&first, RegistryEntries::ShutdownTime), "18:00");
+        assert_eq!(read(&second, RegistryEntries::ShutdownTime), "07:30");
+
+        let first_stop = first.scheduler().query().await.unwrap().deadline.unwrap();
+        let second_stop = second.scheduler().query().await.unwrap().deadline.unwrap();
+        assert_eq!(first_stop.format("%H:%M").to_string(), "18:00");
+        assert_eq!(second_stop.format("%H:%M").to_string(), "07:30");
+
+        first.scheduler().post(ControllerCommand::Cancel).unwrap();
+        assert!(!first.scheduler().query().await.unwrap().is_active());
+        assert!(second.scheduler().query().await.unwrap().is_active());
+
+        let settings = |events: Vec<Event>| -> Vec<String> {
+            events
+                .into_iter()
+                .filter_map(|event| match event {
+                    Event::SettingChanged { setting, .. } => Some(setting),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(
+            settings(published(&mut first_events).await),
+            ["ForceInterval", "ShutdownTime"]
+        );
+        assert_eq!(
+            settings(published(&mut second_events).await),
+            ["ShutdownTime"]
+        );
+    }
+}
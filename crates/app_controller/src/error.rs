@@ -0,0 +1,62 @@
+use std::{fmt, sync::PoisonError};
+
+use serde::{Deserialize, Serialize};
+
+use registry_ops::{RegistryEntries, SettingError};
+
+/// Error returned to the front-ends by the commands, serialized as
+/// `{ "kind": ..., "message": ... }`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum CommandError {
+    /// A thread panicked while holding the setting.
+    Lock(String),
+    /// The stored value of a setting can't be parsed.
+    InvalidSetting(String),
+    /// The request was rejected before anything was changed.
+    InvalidInput(String),
+    /// The registry refused the new value.
+    Registry(String),
+    /// A component was not started yet, or already stopped.
+    NotReady(String),
+    /// The caller is not allowed to make the request.
+    Denied(String),
+}
+
+impl CommandError {
+    #[must_use]
+    pub fn lock<T>(entry: RegistryEntries, err: &PoisonError<T>) -> CommandError {
+        CommandError::Lock(format!("Failed to lock {entry}, err: {err}"))
+    }
+
+    #[must_use]
+    pub fn invalid_setting(entry: RegistryEntries, value: &str) -> CommandError {
+        CommandError::InvalidSetting(format!("Found invalid {entry} value {value:?}"))
+    }
+
+    #[must_use]
+    pub fn registry(entry: RegistryEntries, err: &anyhow::Error) -> CommandError {
+        CommandError::Registry(format!("Failed to write {entry}, err: {err}"))
+    }
+}
+
+impl From<SettingError> for CommandError {
+    fn from(err: SettingError) -> Self {
+        CommandError::InvalidInput(err.to_string())
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandError::Lock(message)
+            | CommandError::InvalidSetting(message)
+            | CommandError::InvalidInput(message)
+            | CommandError::Registry(message)
+            | CommandError::NotReady(message)
+            | CommandError::Denied(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
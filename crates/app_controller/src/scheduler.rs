@@ -0,0 +1,720 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Local, NaiveTime, TimeDelta, TimeZone};
+use event_bus::{Event, EventBus};
+use registry_ops::{ShutdownTime, StopWarnings, WeeklySchedule};
+use std::{cmp::Reverse, collections::BinaryHeap, sync::Arc, time::Duration};
+use tokio::sync::{mpsc, oneshot, watch};
+use tracing::{debug, error, info};
+
+use crate::weekly::{self, Window};
+
+/// Longest single sleep. The monotonic clock stands still while the system is suspended, so
+/// waking up regularly to compare the deadlines with the wall clock makes one that passed
+/// during a suspend, or after the clock was changed, fire right after.
+const MAX_SLEEP: Duration = Duration::from_secs(10);
+/// How long the one-click postpone after a stop warning moves the stop.
+pub const POSTPONE: Duration = Duration::from_secs(30 * 60);
+
+/// Request to the scheduler, answered with the resulting [`ScheduleState`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControllerCommand {
+    /// Stop at the next occurrence of the time, today or tomorrow, and again every day after.
+    ScheduleAt(NaiveTime),
+    /// Stop once the duration elapsed.
+    ScheduleIn(Duration),
+    /// Move the pending stop later by the duration, once. A daily stop is back at its time
+    /// the day after.
+    Postpone(Duration),
+    /// Cancel the pending stop.
+    Cancel,
+    /// Publish `StopWarning` the given minutes before every stop.
+    SetWarnings(StopWarnings),
+    /// Follow the rules, announcing every window opening or closing with `WindowChanged`.
+    /// The window at the time of the command is announced right away, even if it was already,
+    /// so changed rules are applied again.
+    SetWeekly(WeeklySchedule),
+    /// Only report the state.
+    Query,
+}
+
+impl From<ShutdownTime> for ControllerCommand {
+    fn from(time: ShutdownTime) -> Self {
+        match time {
+            ShutdownTime::At(time) => ControllerCommand::ScheduleAt(time),
+            ShutdownTime::Stopped => ControllerCommand::Cancel,
+        }
+    }
+}
+
+/// State of the scheduler, as seen by its task.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScheduleState {
+    /// When the pending stop fires, `None` when nothing is scheduled.
+    pub deadline: Option<DateTime<Local>>,
+    /// Time left until the deadline when the state was taken.
+    pub remaining: Option<Duration>,
+    /// Whether a `StopWarning` was published for the deadline.
+    pub warned: bool,
+    /// The weekly schedule window, `None` without rules.
+    pub window: Option<Window>,
+}
+
+impl ScheduleState {
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        self.deadline.is_some()
+    }
+}
+
+/// Source of the wall clock time for the scheduler, replaced by a mock in tests.
+pub trait Clock: Send + Sync + 'static {
+    fn now(&self) -> DateTime<Local>;
+}
+
+/// The system clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> DateTime<Local> {
+        C::now(self)
+    }
+}
+
+/// A command and, unless it was posted, where to send the resulting state.
+type Message = (ControllerCommand, Option<oneshot::Sender<ScheduleState>>);
+
+/// Handle to the task running the scheduled jobs.
+pub struct Scheduler {
+    tx: mpsc::UnboundedSender<Message>,
+    state: watch::Receiver<ScheduleState>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Scheduler {
+    /// Spawns the scheduler task on the system clock. Once the deadline is reached,
+    /// `ScheduleFired` is published on `events` and only a daily stop is scheduled again;
+    /// acting on it is up to the subscribers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    #[must_use]
+    pub fn start(events: EventBus) -> Scheduler {
+        Scheduler::with_clock(events, SystemClock)
+    }
+
+    /// Spawns the scheduler task on `clock`. A mock clock only takes effect when the task
+    /// wakes up, at the latest after [`MAX_SLEEP`] or right after any command, e.g.
+    /// [`ControllerCommand::Query`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    #[must_use]
+    pub fn with_clock(events: EventBus, clock: impl Clock) -> Scheduler {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (state_tx, state) = watch::channel(ScheduleState::default());
+        let clock: Arc<dyn Clock> = Arc::new(clock);
+        tokio::spawn(run(rx, state_tx, events, Arc::clone(&clock)));
+        Scheduler { tx, state, clock }
+    }
+
+    /// Sends `command` and waits for the state it left the scheduler in.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the scheduler task has stopped.
+    pub async fn send(&self, command: ControllerCommand) -> Result<ScheduleState> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        if self.tx.send((command, Some(reply_tx))).is_err() {
+            return Err(anyhow!("Scheduler task is not running"));
+        }
+        reply_rx
+            .await
+            .map_err(|_| anyhow!("Scheduler task stopped before answering"))
+    }
+
+    /// Queues `command` without waiting for it to be applied, for callers that don't need the
+    /// resulting state. Commands are applied in the order they were posted or sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the scheduler task has stopped.
+    pub fn post(&self, command: ControllerCommand) -> Result<()> {
+        self.tx
+            .send((command, None))
+            .map_err(|_| anyhow!("Scheduler task is not running"))
+    }
+
+    /// The current state of the scheduler, after running the jobs that are due.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the scheduler task has stopped.
+    pub async fn query(&self) -> Result<ScheduleState> {
+        self.send(ControllerCommand::Query).await
+    }
+
+    /// The state the scheduler was left in by its last command or job, with the time
+    /// remaining until the deadline taken now. Doesn't wait for the task.
+    #[must_use]
+    pub fn state(&self) -> ScheduleState {
+        let state = *self.state.borrow();
+        let now = self.clock.now();
+        ScheduleState {
+            remaining: state
+                .deadline
+                .map(|at| (at - now).to_std().unwrap_or_default()),
+            ..state
+        }
+    }
+}
+
+impl std::fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("state", &*self.state.borrow())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The next occurrence of `time` after `now`, today or tomorrow. A time skipped by a daylight
+/// saving change moves to the next day it exists.
+#[must_use]
+pub fn next_occurrence(time: NaiveTime, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    let today = now.date_naive();
+    let next = (0..=2)
+        .filter_map(|days| today.checked_add_days(chrono::Days::new(days)))
+        .filter_map(|date| Local.from_local_datetime(&date.and_time(time)).earliest())
+        .find(|at| *at > now);
+    if next.is_none() {
+        error!("Failed to find the next occurrence of {time} after {now}");
+    }
+    next
+}
+
+/// What runs at a deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Job {
+    /// Publish `ScheduleFired`.
+    Stop,
+    /// Announce the next weekly schedule window.
+    Window,
+    /// Publish `StopWarning` the minutes before the stop.
+    Warning(u32),
+}
+
+/// Jobs ordered by deadline, the earliest first.
+#[derive(Debug, Default)]
+struct Queue {
+    entries: BinaryHeap<Reverse<(DateTime<Local>, Job)>>,
+}
+
+impl Queue {
+    /// Queues `job` at `at`, replacing its previous deadline.
+    fn schedule(&mut self, job: Job, at: DateTime<Local>) {
+        self.cancel(job);
+        self.entries.push(Reverse((at, job)));
+    }
+
+    fn cancel(&mut self, job: Job) -> bool {
+        self.cancel_where(|queued| queued == job)
+    }
+
+    fn cancel_where(&mut self, matches: impl Fn(Job) -> bool) -> bool {
+        let count = self.entries.len();
+        self.entries
+            .retain(|Reverse((_, queued))| !matches(*queued));
+        self.entries.len() != count
+    }
+
+    fn deadline(&self, job: Job) -> Option<DateTime<Local>> {
+        self.entries
+            .iter()
+            .find(|Reverse((_, queued))| *queued == job)
+            .map(|Reverse((at, _))| *at)
+    }
+
+    fn next_deadline(&self) -> Option<DateTime<Local>> {
+        self.entries.peek().map(|Reverse((at, _))| *at)
+    }
+
+    /// Removes the jobs due at `now`, in deadline order.
+    fn pop_due(&mut self, now: DateTime<Local>) -> Vec<(DateTime<Local>, Job)> {
+        let mut due = Vec::new();
+        while self
+            .entries
+            .peek()
+            .is_some_and(|Reverse((at, _))| *at <= now)
+        {
+            if let Some(Reverse(entry)) = self.entries.pop() {
+                due.push(entry);
+            }
+        }
+        due
+    }
+}
+
+/// The scheduler task's view of the jobs and the weekly schedule.
+struct Worker<'a> {
+    queue: Queue,
+    /// The daily stop time, scheduled again once it fired.
+    daily: Option<NaiveTime>,
+    warnings: StopWarnings,
+    /// Whether a warning was published for the pending stop.
+    warned: bool,
+    weekly: WeeklySchedule,
+    /// The window last announced with `WindowChanged`, `None` without rules.
+    window_open: Option<bool>,
+    events: &'a EventBus,
+}
+
+impl Worker<'_> {
+    fn apply(&mut self, command: ControllerCommand, now: DateTime<Local>) {
+        match command {
+            ControllerCommand::ScheduleAt(time) => {
+                self.daily = Some(time);
+                self.schedule_daily(now);
+            }
+            ControllerCommand::ScheduleIn(duration) => {
+                match TimeDelta::from_std(duration).map(|delta| now + delta) {
+                    Ok(at) => {
+                        self.daily = None;
+                        self.schedule_stop(at, now);
+                    }
+                    Err(err) => error!("Failed to schedule shutdown in {duration:?}, err: {err}"),
+                }
+            }
+            ControllerCommand::Postpone(duration) => {
+                let Some(deadline) = self.queue.deadline(Job::Stop) else {
+                    return;
+                };
+                match TimeDelta::from_std(duration).map(|delta| deadline + delta) {
+                    Ok(at) => {
+                        info!("Postponed scheduled shutdown to {at}");
+                        self.schedule_stop(at, now);
+                    }
+                    Err(err) => error!("Failed to postpone shutdown by {duration:?}, err: {err}"),
+                }
+            }
+            ControllerCommand::Cancel => {
+                self.daily = None;
+                self.warned = false;
+                self.queue
+                    .cancel_where(|job| matches!(job, Job::Warning(_)));
+                if self.queue.cancel(Job::Stop) {
+                    info!("Cancelled scheduled shutdown");
+                }
+            }
+            ControllerCommand::SetWarnings(warnings) => {
+                self.warnings = warnings;
+                if let Some(deadline) = self.queue.deadline(Job::Stop) {
+                    self.schedule_warnings(deadline, now);
+                }
+            }
+            ControllerCommand::SetWeekly(schedule) => {
+                self.weekly = schedule;
+                self.window_open = None;
+                self.update_window(now);
+            }
+            ControllerCommand::Query => {}
+        }
+    }
+
+    /// Queues the next occurrence of the daily stop time, if any.
+    fn schedule_daily(&mut self, now: DateTime<Local>) {
+        match self.daily.and_then(|time| next_occurrence(time, now)) {
+            Some(at) => self.schedule_stop(at, now),
+            None => {
+                self.queue.cancel(Job::Stop);
+            }
+        }
+    }
+
+    fn schedule_stop(&mut self, at: DateTime<Local>, now: DateTime<Local>) {
+        self.queue.schedule(Job::Stop, at);
+        self.warned = false;
+        self.schedule_warnings(at, now);
+        debug!("Scheduled shutdown at {at}");
+    }
+
+    /// Queues the warnings before `deadline` that are still ahead of `now`.
+    fn schedule_warnings(&mut self, deadline: DateTime<Local>, now: DateTime<Local>) {
+        self.queue
+            .cancel_where(|job| matches!(job, Job::Warning(_)));
+        for &minutes in self.warnings.minutes() {
+            let at = deadline - TimeDelta::minutes(minutes.into());
+            if at > now {
+                self.queue.schedule(Job::Warning(minutes), at);
+            }
+        }
+    }
+
+    /// Announces the window at `now` if it changed and queues the next transition.
+    fn update_window(&mut self, now: DateTime<Local>) {
+        let Some(window) = weekly::window(&self.weekly, now) else {
+            self.queue.cancel(Job::Window);
+            self.window_open = None;
+            return;
+        };
+        if self.window_open != Some(window.open) {
+            self.window_open = Some(window.open);
+            self.events
+                .publish(Event::WindowChanged { open: window.open });
+        }
+        match window.until {
+            Some(at) => self.queue.schedule(Job::Window, at),
+            None => {
+                self.queue.cancel(Job::Window);
+            }
+        }
+    }
+
+    /// Runs the jobs due at `now`. Of several warnings due at once, e.g. after a suspend, only
+    /// the last one is published.
+    fn run_due(&mut self, now: DateTime<Local>) {
+        let due = self.queue.pop_due(now);
+        let last_warning = due
+            .iter()
+            .rposition(|(_, job)| matches!(job, Job::Warning(_)));
+        for (index, (at, job)) in due.into_iter().enumerate() {
+            match job {
+                Job::Stop => {
+                    info!("Scheduled shutdown at {at} reached at {now}");
+                    self.warned = false;
+                    self.events.publish(Event::ScheduleFired {
+                        time: at.format("%H:%M").to_string(),
+                    });
+                    self.schedule_daily(now);
+                }
+                Job::Window => self.update_window(now),
+                Job::Warning(_) if Some(index) != last_warning => {}
+                Job::Warning(minutes) => {
+                    let Some(deadline) = self.queue.deadline(Job::Stop) else {
+                        continue;
+                    };
+                    self.warned = true;
+                    self.events.publish(Event::StopWarning {
+                        time: deadline.format("%H:%M").to_string(),
+                        minutes,
+                    });
+                }
+            }
+        }
+    }
+
+    fn state(&self, now: DateTime<Local>) -> ScheduleState {
+        let deadline = self.queue.deadline(Job::Stop);
+        ScheduleState {
+            deadline,
+            remaining: deadline.map(|at| (at - now).to_std().unwrap_or_default()),
+            warned: self.warned,
+            window: self.window_open.map(|open| Window {
+                open,
+                until: self.queue.deadline(Job::Window),
+            }),
+        }
+    }
+}
+
+async fn run(
+    mut rx: mpsc::UnboundedReceiver<Message>,
+    state: watch::Sender<ScheduleState>,
+    events: EventBus,
+    clock: Arc<dyn Clock>,
+) {
+    let mut worker = Worker {
+        queue: Queue::default(),
+        daily: None,
+        warnings: StopWarnings::default(),
+        warned: false,
+        weekly: WeeklySchedule::default(),
+        window_open: None,
+        events: &events,
+    };
+    loop {
+        let now = clock.now();
+        worker.run_due(now);
+        state.send_replace(worker.state(now));
+
+        let sleep = worker
+            .queue
+            .next_deadline()
+            .map(|deadline| (deadline - now).to_std().unwrap_or_default().min(MAX_SLEEP));
+        tokio::select! {
+            message = rx.recv() => {
+                let Some((command, reply)) = message else {
+                    info!("Scheduler channel closed");
+                    return;
+                };
+                debug!("Received command: {command:?}");
+                let now = clock.now();
+                worker.apply(command, now);
+                // Jobs due by now run before the caller sees the state.
+                worker.run_due(now);
+                let current = worker.state(now);
+                state.send_replace(current);
+                // The caller may have given up waiting, which is fine.
+                if let Some(reply) = reply {
+                    let _ = reply.send(current);
+                }
+            }
+            () = tokio::time::sleep(sleep.unwrap_or_default()), if sleep.is_some() => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use event_bus::Subscription;
+
+    use super::*;
+    use crate::testing::{MockClock, noon, published};
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    /// A scheduler on a mock clock at noon and a subscription to its events.
+    fn scheduler() -> (Scheduler, Arc<MockClock>, Subscription) {
+        let events = EventBus::new();
+        let subscription = events.subscribe("test");
+        let clock = Arc::new(MockClock::new(noon()));
+        (
+            Scheduler::with_clock(events, Arc::clone(&clock)),
+            clock,
+            subscription,
+        )
+    }
+
+    #[test]
+    fn pops_jobs_in_deadline_order() {
+        let mut queue = Queue::default();
+        let now = noon();
+        queue.schedule(Job::Stop, now + TimeDelta::minutes(30));
+        queue.schedule(Job::Warning(10), now + TimeDelta::minutes(20));
+        queue.schedule(Job::Window, now + TimeDelta::minutes(5));
+        queue.schedule(Job::Warning(2), now + TimeDelta::minutes(28));
+        assert_eq!(queue.next_deadline(), Some(now + TimeDelta::minutes(5)));
+
+        assert!(queue.pop_due(now).is_empty());
+        let due: Vec<Job> = queue
+            .pop_due(now + TimeDelta::minutes(28))
+            .into_iter()
+            .map(|(_, job)| job)
+            .collect();
+        assert_eq!(due, [Job::Window, Job::Warning(10), Job::Warning(2)]);
+        assert_eq!(queue.next_deadline(), Some(now + TimeDelta::minutes(30)));
+    }
+
+    #[test]
+    fn replaces_and_cancels_jobs() {
+        let mut queue = Queue::default();
+        let now = noon();
+        queue.schedule(Job::Stop, now + TimeDelta::minutes(30));
+        queue.schedule(Job::Stop, now + TimeDelta::minutes(60));
+        assert_eq!(
+            queue.deadline(Job::Stop),
+            Some(now + TimeDelta::minutes(60))
+        );
+        assert_eq!(queue.entries.len(), 1);
+
+        queue.schedule(Job::Warning(10), now + TimeDelta::minutes(50));
+        queue.schedule(Job::Warning(5), now + TimeDelta::minutes(55));
+        assert!(queue.cancel_where(|job| matches!(job, Job::Warning(_))));
+        assert!(queue.cancel(Job::Stop));
+        assert!(!queue.cancel(Job::Stop));
+        assert_eq!(queue.next_deadline(), None);
+    }
+
+    #[test]
+    fn finds_the_next_occurrence() {
+        let now = noon();
+        assert_eq!(
+            next_occurrence(time(12, 30), now),
+            Some(now + TimeDelta::minutes(30))
+        );
+        assert_eq!(
+            next_occurrence(time(12, 0), now),
+            Some(now + TimeDelta::days(1))
+        );
+        assert_eq!(
+            next_occurrence(time(8, 0), now),
+            Some(now + TimeDelta::hours(20))
+        );
+    }
+
+    #[tokio::test]
+    async fn rearms_the_daily_stop_once_it_fired() {
+        let (scheduler, clock, mut subscription) = scheduler();
+        let state = scheduler
+            .send(ControllerCommand::ScheduleAt(time(12, 30)))
+            .await
+            .unwrap();
+        assert_eq!(state.deadline, Some(noon() + TimeDelta::minutes(30)));
+        assert_eq!(state.remaining, Some(Duration::from_secs(30 * 60)));
+
+        clock.advance(TimeDelta::minutes(31));
+        let state = scheduler.query().await.unwrap();
+        assert_eq!(
+            published(&mut subscription).await,
+            [Event::ScheduleFired {
+                time: "12:30".to_string()
+            }]
+        );
+        assert_eq!(
+            state.deadline,
+            Some(noon() + TimeDelta::days(1) + TimeDelta::minutes(30))
+        );
+        assert_eq!(scheduler.state().deadline, state.deadline);
+    }
+
+    #[tokio::test]
+    async fn fires_a_one_off_stop_once() {
+        let (scheduler, clock, mut subscription) = scheduler();
+        let state = scheduler
+            .send(ControllerCommand::ScheduleIn(Duration::from_secs(90)))
+            .await
+            .unwrap();
+        assert_eq!(state.deadline, Some(noon() + TimeDelta::seconds(90)));
+
+        clock.advance(TimeDelta::seconds(90));
+        let state = scheduler.query().await.unwrap();
+        assert_eq!(published(&mut subscription).await.len(), 1);
+        assert!(!state.is_active());
+    }
+
+    #[tokio::test]
+    async fn reschedules_postpones_and_cancels() {
+        let (scheduler, clock, mut subscription) = scheduler();
+        scheduler
+            .send(ControllerCommand::ScheduleAt(time(12, 30)))
+            .await
+            .unwrap();
+        let state = scheduler
+            .send(ControllerCommand::ScheduleAt(time(13, 0)))
+            .await
+            .unwrap();
+        assert_eq!(state.deadline, Some(noon() + TimeDelta::hours(1)));
+
+        let state = scheduler
+            .send(ControllerCommand::Postpone(POSTPONE))
+            .await
+            .unwrap();
+        assert_eq!(state.deadline, Some(noon() + TimeDelta::minutes(90)));
+
+        let state = scheduler.send(ControllerCommand::Cancel).await.unwrap();
+        assert!(!state.is_active());
+        let state = scheduler
+            .send(ControllerCommand::Postpone(POSTPONE))
+            .await
+            .unwrap();
+        assert!(!state.is_active());
+
+        clock.advance(TimeDelta::hours(2));
+        scheduler.query().await.unwrap();
+        assert!(published(&mut subscription).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn warns_before_the_stop() {
+        let (scheduler, clock, mut subscription) = scheduler();
+        scheduler
+            .send(ControllerCommand::SetWarnings("10,5,2".parse().unwrap()))
+            .await
+            .unwrap();
+        scheduler
+            .send(ControllerCommand::ScheduleAt(time(12, 30)))
+            .await
+            .unwrap();
+
+        clock.advance(TimeDelta::minutes(20));
+        let state = scheduler.query().await.unwrap();
+        assert!(state.warned);
+        assert_eq!(
+            published(&mut subscription).await,
+            [Event::StopWarning {
+                time: "12:30".to_string(),
+                minutes: 10
+            }]
+        );
+
+        // Of the warnings due at once, only the last one is published.
+        clock.advance(TimeDelta::minutes(9));
+        scheduler.query().await.unwrap();
+        assert_eq!(
+            published(&mut subscription).await,
+            [Event::StopWarning {
+                time: "12:30".to_string(),
+                minutes: 2
+            }]
+        );
+
+        // Postponing takes the warnings along and clears the warned flag.
+        let state = scheduler
+            .send(ControllerCommand::Postpone(POSTPONE))
+            .await
+            .unwrap();
+        assert!(!state.warned);
+        clock.advance(TimeDelta::minutes(21));
+        scheduler.query().await.unwrap();
+        assert_eq!(
+            published(&mut subscription).await,
+            [Event::StopWarning {
+                time: "13:00".to_string(),
+                minutes: 10
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn announces_weekly_windows() {
+        let (scheduler, clock, mut subscription) = scheduler();
+        let weekly: WeeklySchedule = "wed 09:00-17:00".parse().unwrap();
+        let state = scheduler
+            .send(ControllerCommand::SetWeekly(weekly.clone()))
+            .await
+            .unwrap();
+        assert_eq!(
+            state.window,
+            Some(Window {
+                open: true,
+                until: Some(noon() + TimeDelta::hours(5)),
+            })
+        );
+        let opened = [Event::WindowChanged { open: true }];
+        assert_eq!(published(&mut subscription).await, opened);
+
+        // The same rules again are applied again.
+        scheduler
+            .send(ControllerCommand::SetWeekly(weekly))
+            .await
+            .unwrap();
+        assert_eq!(published(&mut subscription).await, opened);
+
+        clock.advance(TimeDelta::hours(5));
+        let state = scheduler.query().await.unwrap();
+        assert_eq!(
+            published(&mut subscription).await,
+            [Event::WindowChanged { open: false }]
+        );
+        assert_eq!(
+            state.window.and_then(|window| window.until),
+            Some(noon() + TimeDelta::days(7) - TimeDelta::hours(3))
+        );
+
+        let state = scheduler
+            .send(ControllerCommand::SetWeekly(WeeklySchedule::default()))
+            .await
+            .unwrap();
+        assert_eq!(state.window, None);
+        assert!(published(&mut subscription).await.is_empty());
+    }
+}
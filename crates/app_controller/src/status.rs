@@ -0,0 +1,155 @@
+use std::{str::FromStr, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use event_bus::IdlerMode;
+use idler_utils::Timer;
+use registry_ops::{RegistryEntries, RegistrySetting};
+
+use crate::{AppContext, CommandError, weekly::Window};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimerStatus {
+    pub mode: Option<String>,
+    pub remaining_seconds: u64,
+    pub deadline: String,
+}
+
+impl From<Option<Timer>> for TimerStatus {
+    fn from(timer: Option<Timer>) -> Self {
+        let Some(timer) = timer else {
+            return TimerStatus {
+                mode: None,
+                remaining_seconds: 0,
+                deadline: String::new(),
+            };
+        };
+        TimerStatus {
+            mode: Some(timer.mode.to_string()),
+            remaining_seconds: timer.remaining().as_secs(),
+            deadline: timer.deadline.format("%H:%M").to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowStatus {
+    pub open: bool,
+    /// Next opening or closing as `%a %H:%M`, `None` when there is none.
+    pub until: Option<String>,
+}
+
+impl From<Window> for WindowStatus {
+    fn from(window: Window) -> Self {
+        WindowStatus {
+            open: window.open,
+            until: window
+                .until
+                .map(|until| until.format("%a %H:%M").to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleStatus {
+    pub active: bool,
+    /// Shutdown time as `%H:%M`.
+    pub time: String,
+    pub remaining_seconds: Option<u64>,
+    /// Whether the warning before the shutdown time was given.
+    pub warned: bool,
+    /// What happens at the shutdown time, e.g. `sleep`.
+    pub action: String,
+    /// The weekly schedule window, `None` without rules.
+    pub weekly: Option<WindowStatus>,
+}
+
+/// Everything the front-ends show about the running app. The switches mirror independent
+/// settings, so they stay plain bools.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Status {
+    pub idle_seconds: Option<u64>,
+    pub force_interval: u64,
+    /// Seconds before input is injected if the user stays idle, `None` while paused.
+    pub next_injection_seconds: Option<u64>,
+    pub last_injection: String,
+    pub mode: IdlerMode,
+    pub timer: TimerStatus,
+    pub schedule: ScheduleStatus,
+    pub logging: bool,
+    pub metrics_enabled: bool,
+    pub metrics_port: u16,
+    pub api_enabled: bool,
+    pub api_port: u16,
+    pub mqtt_enabled: bool,
+    /// `host:port` of the MQTT broker.
+    pub mqtt_broker: String,
+    pub mqtt_topic: String,
+}
+
+fn read(setting: &Mutex<RegistrySetting>) -> Result<RegistrySetting, CommandError> {
+    match setting.lock() {
+        Ok(setting) => Ok(setting.clone()),
+        Err(err) => Err(CommandError::lock(err.get_ref().registry_entry, &err)),
+    }
+}
+
+pub(crate) fn parse<T: FromStr>(setting: &Mutex<RegistrySetting>) -> Result<T, CommandError> {
+    let setting = read(setting)?;
+    setting
+        .last_data
+        .parse()
+        .map_err(|_| CommandError::invalid_setting(setting.registry_entry, &setting.last_data))
+}
+
+/// State of the scheduled shutdown, as last reported by the scheduler.
+///
+/// # Errors
+///
+/// Returns an error if the shutdown time or the stop action can't be read.
+pub fn schedule(context: &AppContext) -> Result<ScheduleStatus, CommandError> {
+    let state = context.scheduler().state();
+    let time = match state.deadline {
+        Some(deadline) => deadline.format("%H:%M").to_string(),
+        None => read(context.settings().get(RegistryEntries::ShutdownTime))?.last_data,
+    };
+    Ok(ScheduleStatus {
+        active: state.is_active(),
+        time,
+        remaining_seconds: state.remaining.map(|remaining| remaining.as_secs()),
+        warned: state.warned,
+        action: read(context.settings().get(RegistryEntries::ScheduleAction))?.last_data,
+        weekly: state.window.map(WindowStatus::from),
+    })
+}
+
+/// Snapshot of the idle service, the settings and the schedule.
+///
+/// # Errors
+///
+/// Returns an error if the idle service was not started yet or a setting can't be read.
+pub fn current(context: &AppContext) -> Result<Status, CommandError> {
+    let service = context.running_idle_service()?;
+    let settings = context.settings();
+
+    Ok(Status {
+        idle_seconds: idler_utils::get_last_input(),
+        force_interval: parse(settings.get(RegistryEntries::ForceInterval))?,
+        next_injection_seconds: service
+            .until_injection()
+            .map(|remaining| remaining.as_secs()),
+        last_injection: read(settings.get(RegistryEntries::LastRobotInput))?.last_data,
+        mode: service.mode(),
+        timer: service.timer().into(),
+        schedule: schedule(context)?,
+        logging: read(settings.get(RegistryEntries::LogStatistics))?.is_enabled(),
+        metrics_enabled: read(settings.get(RegistryEntries::MetricsEndpoint))?.is_enabled(),
+        metrics_port: parse(settings.get(RegistryEntries::MetricsPort))?,
+        api_enabled: read(settings.get(RegistryEntries::ApiEndpoint))?.is_enabled(),
+        api_port: parse(settings.get(RegistryEntries::ApiPort))?,
+        mqtt_enabled: read(settings.get(RegistryEntries::MqttEndpoint))?.is_enabled(),
+        mqtt_broker: read(settings.get(RegistryEntries::MqttBroker))?.last_data,
+        mqtt_topic: read(settings.get(RegistryEntries::MqttTopic))?.last_data,
+    })
+}
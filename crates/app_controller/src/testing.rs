@@ -0,0 +1,42 @@
+//! Helpers shared by the unit tests.
+
+use std::{sync::Mutex, time::Duration};
+
+use chrono::{DateTime, Local, TimeDelta, TimeZone};
+
+use event_bus::{Event, Subscription};
+
+use crate::Clock;
+
+/// A clock that only moves when told to.
+pub(crate) struct MockClock(Mutex<DateTime<Local>>);
+
+impl MockClock {
+    pub(crate) fn new(now: DateTime<Local>) -> MockClock {
+        MockClock(Mutex::new(now))
+    }
+
+    pub(crate) fn advance(&self, delta: TimeDelta) {
+        *self.0.lock().unwrap() += delta;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Local> {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// Noon on Wednesday 2026-06-10, local time.
+pub(crate) fn noon() -> DateTime<Local> {
+    Local.with_ymd_and_hms(2026, 6, 10, 12, 0, 0).unwrap()
+}
+
+/// The events already published.
+pub(crate) async fn published(subscription: &mut Subscription) -> Vec<Event> {
+    let mut events = Vec::new();
+    while let Ok(Some(event)) = tokio::time::timeout(Duration::ZERO, subscription.next()).await {
+        events.push(event);
+    }
+    events
+}
@@ -0,0 +1,464 @@
+use std::sync::{Arc, Mutex};
+use tracing::{error, info, warn};
+
+use chrono::{DateTime, Datelike, Days, Local, NaiveDateTime, TimeDelta, TimeZone};
+
+use event_bus::{Event, IdlerMode};
+use idler_utils::IdleService;
+use registry_ops::{RegistryEntries, RegistrySetting, WeeklyRule, WeeklySchedule};
+
+use crate::{AppContext, CommandError, ControllerCommand};
+
+/// Where the weekly schedule stands at some point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    pub open: bool,
+    /// When the window closes, or the next one opens.
+    pub until: Option<DateTime<Local>>,
+}
+
+/// Whether a window of `schedule` is open at the local time `at`.
+#[must_use]
+pub fn is_open(schedule: &WeeklySchedule, at: NaiveDateTime) -> bool {
+    schedule.rules().iter().any(|rule| {
+        rule.days.contains(at.weekday()) && rule.open <= at.time() && at.time() < rule.close
+    })
+}
+
+/// The first time after `now` a window opens or closes, `None` without rules. Touching or
+/// overlapping windows are one window.
+#[must_use]
+pub fn next_transition(schedule: &WeeklySchedule, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    let local_now = now.naive_local();
+    let open = is_open(schedule, local_now);
+    let today = local_now.date();
+    // A week and a day covers every rule, including today's once they passed.
+    let mut edges: Vec<NaiveDateTime> = (0..=7)
+        .filter_map(|days| today.checked_add_days(Days::new(days)))
+        .flat_map(|date| {
+            schedule
+                .rules()
+                .iter()
+                .filter(move |rule| rule.days.contains(date.weekday()))
+                .flat_map(move |rule| [date.and_time(rule.open), date.and_time(rule.close)])
+        })
+        .filter(|edge| *edge > local_now)
+        .collect();
+    edges.sort_unstable();
+    let edge = edges
+        .into_iter()
+        .find(|edge| is_open(schedule, *edge) != open)?;
+    // A time skipped by a daylight saving change happens an hour later.
+    Local.from_local_datetime(&edge).earliest().or_else(|| {
+        Local
+            .from_local_datetime(&(edge + TimeDelta::hours(1)))
+            .earliest()
+    })
+}
+
+/// The window at `now`, `None` without rules.
+#[must_use]
+pub fn window(schedule: &WeeklySchedule, now: DateTime<Local>) -> Option<Window> {
+    if schedule.is_empty() {
+        return None;
+    }
+    Some(Window {
+        open: is_open(schedule, now.naive_local()),
+        until: next_transition(schedule, now),
+    })
+}
+
+fn parse(setting: &Mutex<RegistrySetting>) -> Result<WeeklySchedule, CommandError> {
+    let entry = RegistryEntries::WeeklySchedule;
+    let value = match setting.lock() {
+        Ok(setting) => setting.last_data.clone(),
+        Err(err) => return Err(CommandError::lock(entry, &err)),
+    };
+    value
+        .parse()
+        .map_err(|_| CommandError::invalid_setting(entry, &value))
+}
+
+/// The stored rules.
+///
+/// # Errors
+///
+/// Returns an error if the setting can't be read or parsed.
+pub fn rules(context: &AppContext) -> Result<WeeklySchedule, CommandError> {
+    parse(context.settings().get(RegistryEntries::WeeklySchedule))
+}
+
+fn update(
+    context: &AppContext,
+    change: impl FnOnce(&mut WeeklySchedule) -> Result<(), CommandError>,
+) -> Result<WeeklySchedule, CommandError> {
+    let mut schedule = rules(context)?;
+    change(&mut schedule)?;
+    context.store(RegistryEntries::WeeklySchedule, &schedule)?;
+    Ok(schedule)
+}
+
+/// Adds `rule`, e.g. `mon-thu 08:30-17:30`, and returns the new rules.
+///
+/// # Errors
+///
+/// Returns an error if `rule` is invalid or the setting can't be written.
+pub fn add_rule(context: &AppContext, rule: &str) -> Result<WeeklySchedule, CommandError> {
+    let rule: WeeklyRule = rule.parse()?;
+    update(context, |schedule| {
+        schedule.add(rule);
+        Ok(())
+    })
+}
+
+/// Replaces the rule numbered `number`, counting from 1, and returns the new rules.
+///
+/// # Errors
+///
+/// Returns an error if there is no such rule, `rule` is invalid or the setting can't be
+/// written.
+pub fn replace_rule(
+    context: &AppContext,
+    number: usize,
+    rule: &str,
+) -> Result<WeeklySchedule, CommandError> {
+    let rule: WeeklyRule = rule.parse()?;
+    update(context, |schedule| Ok(schedule.replace(number, rule)?))
+}
+
+/// Removes the rule numbered `number`, counting from 1, and returns the remaining rules.
+///
+/// # Errors
+///
+/// Returns an error if there is no such rule or the setting can't be written.
+pub fn remove_rule(context: &AppContext, number: usize) -> Result<WeeklySchedule, CommandError> {
+    update(context, |schedule| {
+        schedule.remove(number).map(drop).map_err(Into::into)
+    })
+}
+
+fn send(context: &AppContext, schedule: WeeklySchedule) {
+    if schedule.is_empty() {
+        info!("No weekly schedule to follow");
+    } else {
+        info!("Following weekly schedule {schedule}");
+    }
+    if let Err(err) = context
+        .scheduler()
+        .post(ControllerCommand::SetWeekly(schedule))
+    {
+        error!("Failed to hand the weekly schedule to the scheduler, err: {err}");
+    }
+}
+
+/// What the weekly schedule did to the idle service, so it pauses outside of the windows
+/// without undoing a manual change made since.
+#[derive(Debug, Default)]
+struct Follower {
+    /// The mode the schedule last set, to tell its own mode changes from anyone else's.
+    expected: Option<IdlerMode>,
+    /// Someone else changed the mode since the schedule last did.
+    overridden: bool,
+    /// There are rules, windows announced for older ones are ignored once they are gone.
+    following: bool,
+}
+
+impl Follower {
+    /// The idle service, `None` while it isn't started or a timer decides its mode.
+    fn service(context: &AppContext) -> Option<&IdleService> {
+        match context.running_idle_service() {
+            Ok(service) if service.timer().is_some() => {
+                info!("Leaving the weekly schedule window to the running timer");
+                None
+            }
+            Ok(service) => Some(service),
+            Err(err) => {
+                warn!("Ignoring weekly schedule window, err: {err}");
+                None
+            }
+        }
+    }
+
+    /// Keeps the system awake, a fresh start for the manual changes. A running pause timer
+    /// ending in the window counts as the schedule's.
+    fn open(&mut self, context: &AppContext) {
+        if !self.following {
+            return;
+        }
+        self.overridden = false;
+        self.expected = Some(IdlerMode::Active);
+        if let Some(service) = Follower::service(context) {
+            info!("Weekly schedule window opened, keeping awake");
+            service.resume();
+        }
+    }
+
+    /// Pauses, unless the mode was changed by hand since the window opened.
+    fn close(&mut self, context: &AppContext) {
+        if !self.following {
+            return;
+        }
+        if self.overridden {
+            info!("Weekly schedule window closed, leaving the manually set mode");
+            return;
+        }
+        self.expected = Some(IdlerMode::Paused);
+        if let Some(service) = Follower::service(context) {
+            info!("Weekly schedule window closed, pausing");
+            service.pause();
+        }
+    }
+
+    fn mode_changed(&mut self, mode: IdlerMode) {
+        if self.expected != Some(mode) {
+            self.overridden = true;
+            self.expected = None;
+        }
+    }
+
+    /// Applies changed rules afresh once the scheduler announces their window. Without rules,
+    /// keeps the system awake again if the schedule paused it.
+    fn rules_changed(&mut self, context: &AppContext, schedule: WeeklySchedule) {
+        let paused = self.expected == Some(IdlerMode::Paused) && !self.overridden;
+        if schedule.is_empty() && self.following && paused {
+            if let Some(service) = Follower::service(context) {
+                info!("Weekly schedule cleared, keeping awake again");
+                service.resume();
+            }
+        }
+        self.following = !schedule.is_empty();
+        self.overridden = false;
+        send(context, schedule);
+    }
+}
+
+/// Hands the stored rules to the scheduler and follows the event bus to apply the windows
+/// to the idle service and pick up changed rules. The system is paused outside of the
+/// windows, unless its mode was changed by hand since the last opening or closing, and a
+/// running timer is left alone. The current window is applied right away, so this is
+/// called after [`AppContext::start_services`].
+///
+/// # Panics
+///
+/// Panics if called outside of a tokio runtime.
+pub fn spawn(context: Arc<AppContext>) {
+    let mut subscription = context.events().subscribe("weekly schedule");
+    let schedule = rules(&context).unwrap_or_else(|err| {
+        error!("Not following the weekly schedule, err: {err}");
+        WeeklySchedule::default()
+    });
+    let mut follower = Follower {
+        following: !schedule.is_empty(),
+        ..Follower::default()
+    };
+    send(&context, schedule);
+
+    tokio::spawn(async move {
+        let entry = RegistryEntries::WeeklySchedule.to_string();
+        while let Some(event) = subscription.next().await {
+            match event {
+                Event::WindowChanged { open: true } => follower.open(&context),
+                Event::WindowChanged { open: false } => follower.close(&context),
+                Event::ModeChanged { mode } => follower.mode_changed(mode),
+                Event::SettingChanged { setting, value } if setting == entry => {
+                    match value.parse() {
+                        Ok(schedule) => follower.rules_changed(&context, schedule),
+                        Err(err) => error!("Found invalid weekly schedule {value:?}, err: {err}"),
+                    }
+                }
+                _ => {}
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+
+    use event_bus::EventBus;
+    use registry_ops::SettingsStore;
+
+    use super::*;
+    use crate::testing::{MockClock, noon};
+
+    fn schedule(rules: &str) -> WeeklySchedule {
+        rules.parse().unwrap()
+    }
+
+    /// Local time on Wednesday 2026-06-10, plus `days`.
+    fn at(days: u64, hour: u32, minute: u32) -> DateTime<Local> {
+        let date = NaiveDate::from_ymd_opt(2026, 6, 10).unwrap() + Days::new(days);
+        let time = date.and_hms_opt(hour, minute, 0).unwrap();
+        Local.from_local_datetime(&time).earliest().unwrap()
+    }
+
+    #[test]
+    fn opens_at_the_start_and_closes_at_the_end() {
+        let schedule = schedule("wed 09:00-17:00");
+        let open = |hour, minute| is_open(&schedule, at(0, hour, minute).naive_local());
+        assert!(!open(8, 59));
+        assert!(open(9, 0));
+        assert!(open(16, 59));
+        assert!(!open(17, 0));
+        assert!(!is_open(&schedule, at(1, 12, 0).naive_local()));
+    }
+
+    #[test]
+    fn finds_the_next_edge() {
+        let weekdays = schedule("mon-fri 09:00-17:00");
+        assert_eq!(next_transition(&weekdays, at(0, 12, 0)), Some(at(0, 17, 0)));
+        assert_eq!(next_transition(&weekdays, at(0, 17, 0)), Some(at(1, 9, 0)));
+        // From Friday evening to Monday morning.
+        assert_eq!(next_transition(&weekdays, at(2, 18, 0)), Some(at(5, 9, 0)));
+        // A week later when only today's window is left and it passed.
+        let wednesday = schedule("wed 09:00-10:00");
+        assert_eq!(next_transition(&wednesday, at(0, 11, 0)), Some(at(7, 9, 0)));
+        assert_eq!(
+            next_transition(&WeeklySchedule::default(), at(0, 12, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn merges_touching_and_overlapping_windows() {
+        let touching = schedule("wed 09:00-12:00; wed 12:00-14:00");
+        assert_eq!(next_transition(&touching, at(0, 10, 0)), Some(at(0, 14, 0)));
+        let overlapping = schedule("wed 09:00-12:00; wed 11:00-13:00; wed 10:00-11:30");
+        assert_eq!(
+            next_transition(&overlapping, at(0, 9, 30)),
+            Some(at(0, 13, 0))
+        );
+    }
+
+    #[test]
+    fn describes_the_window() {
+        assert_eq!(window(&WeeklySchedule::default(), at(0, 12, 0)), None);
+        let schedule = schedule("wed 09:00-17:00");
+        assert_eq!(
+            window(&schedule, at(0, 12, 0)),
+            Some(Window {
+                open: true,
+                until: Some(at(0, 17, 0)),
+            })
+        );
+        assert_eq!(
+            window(&schedule, at(0, 18, 0)),
+            Some(Window {
+                open: false,
+                until: Some(at(7, 9, 0)),
+            })
+        );
+    }
+
+    /// A context at `now` on an in-memory store with `rules`, following them.
+    fn follow(rules: &str, now: DateTime<Local>) -> (Arc<AppContext>, Arc<MockClock>) {
+        let clock = Arc::new(MockClock::new(now));
+        let context = Arc::new(AppContext::with_store(
+            SettingsStore::in_memory(),
+            EventBus::new(),
+            Arc::clone(&clock),
+        ));
+        context
+            .store(RegistryEntries::WeeklySchedule, &rules)
+            .unwrap();
+        context.start_services();
+        spawn(Arc::clone(&context));
+        (context, clock)
+    }
+
+    fn service(context: &AppContext) -> &IdleService {
+        context.idle_service().unwrap()
+    }
+
+    /// Lets the scheduler and the follower handle what was published.
+    async fn settle(context: &AppContext) {
+        context.scheduler().query().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    async fn until_paused(context: &AppContext, paused: bool) {
+        for _ in 0..200 {
+            if service(context).is_paused() == paused {
+                return settle(context).await;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!(
+            "Idle service not {}",
+            if paused { "paused" } else { "active" }
+        );
+    }
+
+    async fn advance(context: &AppContext, clock: &MockClock, delta: TimeDelta) {
+        clock.advance(delta);
+        settle(context).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pauses_outside_of_the_windows() {
+        let (context, clock) = follow("wed 09:00-17:00", noon() + TimeDelta::hours(6));
+        until_paused(&context, true).await;
+
+        advance(&context, &clock, TimeDelta::days(7) - TimeDelta::hours(9)).await;
+        until_paused(&context, false).await;
+        advance(&context, &clock, TimeDelta::hours(8)).await;
+        until_paused(&context, true).await;
+        context.shutdown();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn leaves_manual_changes_alone() {
+        let (context, clock) = follow("wed 09:00-17:00", noon());
+        settle(&context).await;
+        assert!(!service(&context).is_paused());
+
+        // Paused and resumed by hand in the window, kept awake once it closes.
+        service(&context).pause();
+        until_paused(&context, true).await;
+        service(&context).resume();
+        until_paused(&context, false).await;
+        advance(&context, &clock, TimeDelta::hours(5)).await;
+        assert!(!service(&context).is_paused());
+
+        // The next opening starts afresh.
+        advance(&context, &clock, TimeDelta::days(7) - TimeDelta::hours(8)).await;
+        advance(&context, &clock, TimeDelta::hours(8)).await;
+        until_paused(&context, true).await;
+
+        // A keep-awake timer outlasting the window is left running.
+        advance(&context, &clock, TimeDelta::days(7) - TimeDelta::hours(8)).await;
+        service(&context)
+            .keep_awake_for(Duration::from_secs(24 * 60 * 60))
+            .unwrap();
+        settle(&context).await;
+        advance(&context, &clock, TimeDelta::hours(8)).await;
+        assert!(!service(&context).is_paused());
+        assert!(service(&context).timer().is_some());
+        context.shutdown();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn applies_edited_rules() {
+        let (context, _clock) = follow("wed 09:00-17:00", noon() + TimeDelta::hours(6));
+        until_paused(&context, true).await;
+        service(&context).resume();
+        until_paused(&context, false).await;
+
+        // Editing the rules applies them again, over the manual resume.
+        add_rule(&context, "thu 09:00-10:00").unwrap();
+        until_paused(&context, true).await;
+        add_rule(&context, "wed 17:30-19:00").unwrap();
+        until_paused(&context, false).await;
+        remove_rule(&context, 3).unwrap();
+        until_paused(&context, true).await;
+
+        // Without rules, the system is kept awake again.
+        remove_rule(&context, 1).unwrap();
+        remove_rule(&context, 1).unwrap();
+        until_paused(&context, false).await;
+        context.shutdown();
+    }
+}
@@ -0,0 +1,166 @@
+use std::{env, fmt, path::PathBuf};
+use tracing::info;
+
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+
+// The `Run` key of the current user on Windows and an XDG autostart entry elsewhere.
+#[cfg(windows)]
+mod run_key;
+#[cfg(windows)]
+use run_key as backend;
+#[cfg(not(windows))]
+mod xdg;
+#[cfg(not(windows))]
+use xdg as backend;
+
+pub use backend::location;
+
+/// Which front-end an entry starts. Each has its own entry, so enabling one doesn't replace
+/// the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// The tray app, started without arguments.
+    Tray,
+    /// The headless daemon of the command line tool.
+    Daemon,
+}
+
+impl Mode {
+    fn args(self) -> &'static [&'static str] {
+        match self {
+            Mode::Tray => &[],
+            Mode::Daemon => &["daemon"],
+        }
+    }
+}
+
+/// Whether the idler is started with the session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum Autostart {
+    Disabled,
+    Enabled {
+        executable: PathBuf,
+    },
+    /// The entry launches another executable than the running one, e.g. after the app moved.
+    /// Enabling again points it at the current one.
+    Stale {
+        executable: PathBuf,
+    },
+}
+
+impl Autostart {
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        matches!(self, Autostart::Enabled { .. })
+    }
+}
+
+impl fmt::Display for Autostart {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Autostart::Disabled => write!(f, "off"),
+            Autostart::Enabled { executable } => write!(f, "on ({})", executable.display()),
+            Autostart::Stale { executable } => {
+                write!(
+                    f,
+                    "stale, starts {} instead of this executable",
+                    executable.display()
+                )
+            }
+        }
+    }
+}
+
+/// Reads the autostart entry of `mode`.
+///
+/// # Errors
+///
+/// Returns an error if the entry exists but can't be read, or the running executable can't be
+/// found.
+pub fn status(mode: Mode) -> Result<Autostart> {
+    let Some(command) = backend::read(mode)? else {
+        return Ok(Autostart::Disabled);
+    };
+    let executable = backend::executable(&command)
+        .ok_or_else(|| anyhow!("Found autostart entry without executable: {command:?}"))?;
+    // Both sides are resolved, so links and relative paths to the running executable match.
+    let current = env::current_exe()?.canonicalize()?;
+    if executable
+        .canonicalize()
+        .is_ok_and(|executable| executable == current)
+    {
+        Ok(Autostart::Enabled { executable })
+    } else {
+        Ok(Autostart::Stale { executable })
+    }
+}
+
+/// Starts the running executable in `mode` at login, replacing any previous entry of `mode`.
+///
+/// # Errors
+///
+/// Returns an error if the running executable can't be found or the entry can't be written.
+pub fn enable(mode: Mode) -> Result<Autostart> {
+    let executable = env::current_exe()?;
+    backend::write(mode, &backend::command(&executable, mode.args()))?;
+    info!(
+        "Enabled autostart of {} in {}",
+        executable.display(),
+        location(mode)
+    );
+    Ok(Autostart::Enabled { executable })
+}
+
+/// Removes the autostart entry of `mode`, if any.
+///
+/// # Errors
+///
+/// Returns an error if the entry exists but can't be removed.
+pub fn disable(mode: Mode) -> Result<Autostart> {
+    backend::remove(mode)?;
+    info!("Disabled autostart in {}", location(mode));
+    Ok(Autostart::Disabled)
+}
+
+// The entries are written to a scratch `$XDG_CONFIG_HOME`, which only exists outside of
+// Windows.
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use std::{fs, process};
+
+    use super::*;
+
+    #[test]
+    fn keeps_an_entry_per_mode_for_the_running_executable() {
+        let dir = env::temp_dir().join(format!("smart-idler-autostart-{}", process::id()));
+        env::set_var("XDG_CONFIG_HOME", &dir);
+        let current = env::current_exe().unwrap();
+
+        assert_eq!(status(Mode::Tray).unwrap(), Autostart::Disabled);
+        assert!(enable(Mode::Daemon).unwrap().is_enabled());
+        assert!(enable(Mode::Tray).unwrap().is_enabled());
+        assert_ne!(location(Mode::Tray), location(Mode::Daemon));
+        let daemon = fs::read_to_string(location(Mode::Daemon)).unwrap();
+        assert!(daemon.contains(&format!("Exec={} \"daemon\"", xdg::command(&current, &[]))));
+
+        disable(Mode::Tray).unwrap();
+        assert_eq!(status(Mode::Tray).unwrap(), Autostart::Disabled);
+        assert_eq!(
+            status(Mode::Daemon).unwrap(),
+            Autostart::Enabled {
+                executable: current
+            }
+        );
+        disable(Mode::Daemon).unwrap();
+
+        // Entries of another executable, existing or not, are stale.
+        for executable in ["/bin/sh", "/nonexistent/smart-idler"] {
+            let executable = PathBuf::from(executable);
+            xdg::write(Mode::Tray, &xdg::command(&executable, &[])).unwrap();
+            assert_eq!(status(Mode::Tray).unwrap(), Autostart::Stale { executable });
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,138 @@
+use std::{
+    iter,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Result, anyhow};
+use windows::Win32::Foundation::ERROR_FILE_NOT_FOUND;
+use windows_registry::CURRENT_USER;
+
+use crate::Mode;
+
+const RUN_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Run";
+
+fn value_name(mode: Mode) -> &'static str {
+    match mode {
+        Mode::Tray => "SmartIdler",
+        Mode::Daemon => "SmartIdlerDaemon",
+    }
+}
+
+/// Where the entry of `mode` lives, for messages.
+#[must_use]
+pub fn location(mode: Mode) -> String {
+    format!(r"HKCU\{RUN_KEY}\{}", value_name(mode))
+}
+
+fn is_not_found(err: &windows_registry::Result<impl Sized>) -> bool {
+    err.as_ref()
+        .is_err_and(|err| err.code() == ERROR_FILE_NOT_FOUND.to_hresult())
+}
+
+/// Quotes `argument` the way `CommandLineToArgvW` splits it again: backslashes only escape
+/// a following `"`, so those before a quote or the closing quote are doubled.
+fn quote(argument: &str) -> String {
+    if !argument.is_empty() && !argument.contains([' ', '\t', '"']) {
+        return argument.to_string();
+    }
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in argument.chars() {
+        if c == '\\' {
+            backslashes += 1;
+            continue;
+        }
+        let escapes = if c == '"' {
+            backslashes * 2 + 1
+        } else {
+            backslashes
+        };
+        quoted.extend(iter::repeat_n('\\', escapes));
+        quoted.push(c);
+        backslashes = 0;
+    }
+    quoted.extend(iter::repeat_n('\\', backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+pub(crate) fn command(executable: &Path, args: &[&str]) -> String {
+    // The executable is always quoted, so a path with spaces can't be misread.
+    std::iter::once(format!("\"{}\"", executable.display()))
+        .chain(args.iter().map(|argument| quote(argument)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The first argument of a `Run` value, unquoted.
+pub(crate) fn executable(command: &str) -> Option<PathBuf> {
+    let command = command.trim_start();
+    let executable = match command.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next()?,
+        None => command.split_whitespace().next()?,
+    };
+    (!executable.is_empty()).then(|| PathBuf::from(executable))
+}
+
+/// The command line of the entry, `None` without one.
+pub(crate) fn read(mode: Mode) -> Result<Option<String>> {
+    let key = CURRENT_USER.open(RUN_KEY);
+    if is_not_found(&key) {
+        return Ok(None);
+    }
+    let value = key?.get_string(value_name(mode));
+    if is_not_found(&value) {
+        return Ok(None);
+    }
+    value
+        .map(Some)
+        .map_err(|err| anyhow!("Failed to read {}, err: {err}", location(mode)))
+}
+
+pub(crate) fn write(mode: Mode, command: &str) -> Result<()> {
+    CURRENT_USER
+        .create(RUN_KEY)
+        .and_then(|key| key.set_string(value_name(mode), command))
+        .map_err(|err| anyhow!("Failed to write {}, err: {err}", location(mode)))
+}
+
+pub(crate) fn remove(mode: Mode) -> Result<()> {
+    let key = CURRENT_USER.open(RUN_KEY);
+    if is_not_found(&key) {
+        return Ok(());
+    }
+    let removed = key?.remove_value(value_name(mode));
+    if is_not_found(&removed) {
+        return Ok(());
+    }
+    removed.map_err(|err| anyhow!("Failed to remove {}, err: {err}", location(mode)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_arguments_for_the_command_line() {
+        assert_eq!(quote("daemon"), "daemon");
+        assert_eq!(quote(""), "\"\"");
+        assert_eq!(quote("two words"), "\"two words\"");
+        assert_eq!(quote(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote(r#"a\"b"#), r#""a\\\"b""#);
+        assert_eq!(quote(r"C:\My Dir\"), r#""C:\My Dir\\""#);
+        assert_eq!(quote(r"C:\dir\file"), r"C:\dir\file");
+    }
+
+    #[test]
+    fn reads_back_the_executable() {
+        let command = command(
+            Path::new(r"C:\Program Files\Smart Idler\tray.exe"),
+            &["daemon"],
+        );
+        assert_eq!(command, r#""C:\Program Files\Smart Idler\tray.exe" daemon"#);
+        assert_eq!(
+            executable(&command),
+            Some(PathBuf::from(r"C:\Program Files\Smart Idler\tray.exe"))
+        );
+    }
+}
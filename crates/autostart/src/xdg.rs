@@ -0,0 +1,130 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Result, anyhow};
+
+use crate::Mode;
+
+const AUTOSTART_DIR: &str = "autostart";
+
+/// `smart-idler.desktop` for the tray and `smart-idler-daemon.desktop` for the daemon, in
+/// `$XDG_CONFIG_HOME/autostart` or `~/.config/autostart`.
+fn path(mode: Mode) -> PathBuf {
+    let file = match mode {
+        Mode::Tray => "smart-idler.desktop",
+        Mode::Daemon => "smart-idler-daemon.desktop",
+    };
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(env::temp_dir)
+        .join(AUTOSTART_DIR)
+        .join(file)
+}
+
+/// Where the entry of `mode` lives, for messages.
+#[must_use]
+pub fn location(mode: Mode) -> String {
+    path(mode).display().to_string()
+}
+
+/// Quotes `argument` for the `Exec` key, escaping what the desktop entry spec reserves.
+fn quote(argument: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in argument.chars() {
+        match c {
+            '"' | '`' | '$' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '%' => quoted.push_str("%%"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+pub(crate) fn command(executable: &Path, args: &[&str]) -> String {
+    std::iter::once(executable.to_string_lossy().as_ref())
+        .chain(args.iter().copied())
+        .map(quote)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The first argument of an `Exec` value, unquoted.
+pub(crate) fn executable(command: &str) -> Option<PathBuf> {
+    let command = command.trim_start();
+    let executable = match command.strip_prefix('"') {
+        Some(quoted) => {
+            let mut executable = String::new();
+            let mut chars = quoted.chars();
+            loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => executable.push(chars.next()?),
+                    c => executable.push(c),
+                }
+            }
+            executable
+        }
+        None => command.split_whitespace().next()?.to_string(),
+    };
+    let executable = executable.replace("%%", "%");
+    (!executable.is_empty()).then(|| PathBuf::from(executable))
+}
+
+/// The `Exec` value of the entry, `None` without one or when the desktop turned it off.
+pub(crate) fn read(mode: Mode) -> Result<Option<String>> {
+    let path = path(mode);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(anyhow!("Failed to read {path:?}, err: {err}")),
+    };
+    let mut exec = None;
+    for line in content.lines() {
+        match line
+            .split_once('=')
+            .map(|(key, value)| (key.trim(), value.trim()))
+        {
+            Some(("Hidden", "true") | ("X-GNOME-Autostart-enabled", "false")) => return Ok(None),
+            Some(("Exec", value)) => exec = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    Ok(exec)
+}
+
+pub(crate) fn write(mode: Mode, command: &str) -> Result<()> {
+    let path = path(mode);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let name = match mode {
+        Mode::Tray => "Smart Idler",
+        Mode::Daemon => "Smart Idler daemon",
+    };
+    let entry = format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Name={name}\n\
+         Comment=Keeps the system awake while you are away\n\
+         Exec={command}\n\
+         Terminal=false\n\
+         X-GNOME-Autostart-enabled=true\n"
+    );
+    fs::write(&path, entry).map_err(|err| anyhow!("Failed to write {path:?}, err: {err}"))
+}
+
+pub(crate) fn remove(mode: Mode) -> Result<()> {
+    let path = path(mode);
+    match fs::remove_file(&path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(anyhow!("Failed to remove {path:?}, err: {err}")),
+    }
+}
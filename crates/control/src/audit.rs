@@ -0,0 +1,159 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+use tracing::{error, warn};
+
+use anyhow::Result;
+use chrono::Local;
+use serde::Serialize;
+
+use registry_ops::Permission;
+
+use crate::auth::Peer;
+
+const AUDIT_FILE: &str = "control-audit.log";
+/// Size past which the log moves to `control-audit.log.1`, replacing the previous one, so it
+/// takes at most twice as much space.
+const MAX_SIZE: u64 = 1024 * 1024;
+
+/// Default location of the audit log, next to the statistics under `%LOCALAPPDATA%`.
+#[cfg(windows)]
+#[must_use]
+pub fn default_path() -> PathBuf {
+    env::var_os("LOCALAPPDATA")
+        .map_or_else(env::temp_dir, PathBuf::from)
+        .join("SmartIdler")
+        .join(AUDIT_FILE)
+}
+
+/// Default location of the audit log, under `$XDG_STATE_HOME` or `~/.local/state`.
+#[cfg(not(windows))]
+#[must_use]
+pub fn default_path() -> PathBuf {
+    env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .unwrap_or_else(env::temp_dir)
+        .join("smart-idler")
+        .join(AUDIT_FILE)
+}
+
+/// One refused request, a JSON object per line of the audit log.
+#[derive(Debug, Serialize)]
+struct Denial<'a> {
+    time: String,
+    user: Option<&'a str>,
+    pid: Option<u32>,
+    request: &'a str,
+    required: String,
+    granted: String,
+}
+
+/// Moves the log at `path` aside once it reached `max_size`.
+fn rotate(path: &Path, max_size: u64) -> Result<()> {
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.len() >= max_size => {
+            fs::rename(path, path.with_extension("log.1"))?;
+            Ok(())
+        }
+        Ok(_) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn append(path: &Path, denial: &Denial, max_size: u64) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    rotate(path, max_size)?;
+    let mut line = serde_json::to_vec(denial)?;
+    line.push(b'\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(&line)?;
+    Ok(())
+}
+
+/// Records a request refused to `peer`, `None` when the peer couldn't be identified.
+pub(crate) fn denied(
+    peer: Option<&Peer>,
+    request: &str,
+    required: Permission,
+    granted: Permission,
+) {
+    if let Some(peer) = peer {
+        warn!(
+            target: "audit",
+            "Denied {request} to {peer}, needs {required}, granted {granted}"
+        );
+    } else {
+        warn!(target: "audit", "Denied {request} to an unidentified peer");
+    }
+    let denial = Denial {
+        time: Local::now().to_rfc3339(),
+        user: peer.map(|peer| peer.user.as_str()),
+        pid: peer.and_then(|peer| peer.pid),
+        request,
+        required: required.to_string(),
+        granted: granted.to_string(),
+    };
+    let path = default_path();
+    if let Err(err) = append(&path, &denial, MAX_SIZE) {
+        error!("Failed to write the audit log {path:?}, err: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn denial(request: &str) -> Denial<'_> {
+        Denial {
+            time: "2026-10-19T12:00:00+00:00".to_string(),
+            user: Some("guest"),
+            pid: Some(42),
+            request,
+            required: Permission::Admin.to_string(),
+            granted: Permission::Read.to_string(),
+        }
+    }
+
+    fn requests(path: &Path) -> Vec<String> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let denial: serde_json::Value = serde_json::from_str(line).unwrap();
+                denial["request"].as_str().unwrap().to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rotates_the_log() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("smart-idler").join(AUDIT_FILE);
+        let max_size = serde_json::to_vec(&denial("Quit")).unwrap().len() as u64 * 2;
+
+        append(&path, &denial("Quit"), max_size).unwrap();
+        append(&path, &denial("Set"), max_size).unwrap();
+        assert_eq!(requests(&path), ["Quit", "Set"]);
+
+        // Full, so the next denial starts a new log.
+        append(&path, &denial("Pause"), max_size).unwrap();
+        let rotated = path.with_extension("log.1");
+        assert_eq!(requests(&rotated), ["Quit", "Set"]);
+        assert_eq!(requests(&path), ["Pause"]);
+
+        append(&path, &denial("Stop"), max_size).unwrap();
+        append(&path, &denial("Start"), max_size).unwrap();
+        assert_eq!(requests(&rotated), ["Pause", "Stop"]);
+        assert_eq!(requests(&path), ["Start"]);
+    }
+}
@@ -0,0 +1,216 @@
+use std::fmt;
+use tracing::error;
+
+use app_controller::CommandError;
+use registry_ops::{Permission, RegistryEntries, RegistrySetting};
+
+use crate::{audit, protocol::Request};
+
+/// The process on the other end of a control connection, identified by the OS.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Peer {
+    /// Uid on Unix, user SID on Windows.
+    pub user: String,
+    pub pid: Option<u32>,
+    /// Whether the peer runs as the user of this app, or as root / `LocalSystem`.
+    pub owner: bool,
+}
+
+#[cfg(unix)]
+impl Peer {
+    /// A peer identified by its credentials, owner when it shares the effective uid of this
+    /// process or is root.
+    #[must_use]
+    pub fn unix(uid: u32, pid: Option<u32>) -> Peer {
+        // SAFETY: `geteuid` has no preconditions and can't fail.
+        let own_uid = unsafe { libc::geteuid() };
+        Peer {
+            user: uid.to_string(),
+            pid,
+            owner: uid == own_uid || uid == 0,
+        }
+    }
+}
+
+impl fmt::Display for Peer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "user {}", self.user)?;
+        if let Some(pid) = self.pid {
+            write!(f, " (pid {pid})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Who may do what over the control endpoint, set by an administrator in the
+/// `ControlOwnerPermission` and `ControlOthersPermission` settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Policy {
+    /// Granted to processes of the user running the app.
+    pub owner: Permission,
+    /// Granted to processes of any other local user.
+    pub others: Permission,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy {
+            owner: Permission::Admin,
+            others: Permission::None,
+        }
+    }
+}
+
+fn load_permission(entry: RegistryEntries, fallback: Permission) -> Permission {
+    let setting = RegistrySetting::new(&entry);
+    Permission::parse(entry, &setting.last_data).unwrap_or_else(|err| {
+        error!("Ignoring stored {entry}, using {fallback}, err: {err}");
+        fallback
+    })
+}
+
+impl Policy {
+    /// Reads the policy from the settings, keeping the default of an invalid entry.
+    #[must_use]
+    pub fn load() -> Policy {
+        let default = Policy::default();
+        Policy {
+            owner: load_permission(RegistryEntries::ControlOwnerPermission, default.owner),
+            others: load_permission(RegistryEntries::ControlOthersPermission, default.others),
+        }
+    }
+
+    #[must_use]
+    pub fn granted(&self, peer: &Peer) -> Permission {
+        if peer.owner { self.owner } else { self.others }
+    }
+
+    /// Checks `peer` was granted `required`, auditing a refusal of the request described by
+    /// `request`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CommandError::Denied`] if the peer lacks the permission.
+    pub fn authorize(
+        &self,
+        peer: &Peer,
+        required: Permission,
+        request: &str,
+    ) -> Result<(), CommandError> {
+        let granted = self.granted(peer);
+        if granted >= required {
+            return Ok(());
+        }
+        audit::denied(Some(peer), request, required, granted);
+        Err(CommandError::Denied(format!(
+            "This request needs the {required} permission, {peer} has {granted}"
+        )))
+    }
+
+    /// Whether other users may connect at all.
+    #[must_use]
+    pub fn admits_others(&self) -> bool {
+        self.others > Permission::None
+    }
+}
+
+impl Request {
+    /// The permission a peer needs for this request.
+    #[must_use]
+    pub fn permission(&self) -> Permission {
+        match self {
+            Request::Status | Request::Rules | Request::Subscribe => Permission::Read,
+            Request::Resume
+            | Request::Pause
+            | Request::PauseFor { .. }
+            | Request::KeepAwakeFor { .. }
+            | Request::CancelTimer
+            | Request::Schedule { .. }
+            | Request::Postpone { .. }
+            | Request::Set {
+                setting: RegistryEntries::ShutdownTime,
+                ..
+            }
+            | Request::Exit
+            | Request::Show => Permission::Control,
+            Request::Set { .. }
+            | Request::AddRule { .. }
+            | Request::ReplaceRule { .. }
+            | Request::RemoveRule { .. } => Permission::Admin,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(owner: bool) -> Peer {
+        Peer {
+            user: "1000".to_string(),
+            pid: Some(42),
+            owner,
+        }
+    }
+
+    #[test]
+    fn grants_by_peer() {
+        let policy = Policy {
+            owner: Permission::Control,
+            others: Permission::Read,
+        };
+        assert_eq!(policy.granted(&peer(true)), Permission::Control);
+        assert_eq!(policy.granted(&peer(false)), Permission::Read);
+        assert!(policy.admits_others());
+        assert!(!Policy::default().admits_others());
+    }
+
+    #[test]
+    fn authorizes_up_to_the_granted_permission() {
+        let policy = Policy {
+            owner: Permission::Control,
+            others: Permission::None,
+        };
+        for required in [Permission::None, Permission::Read, Permission::Control] {
+            assert_eq!(policy.authorize(&peer(true), required, "test"), Ok(()));
+        }
+    }
+
+    #[test]
+    fn requires_admin_only_for_settings() {
+        assert_eq!(Request::Status.permission(), Permission::Read);
+        assert_eq!(Request::Subscribe.permission(), Permission::Read);
+        assert_eq!(Request::Rules.permission(), Permission::Read);
+        assert_eq!(
+            Request::RemoveRule { number: 1 }.permission(),
+            Permission::Admin
+        );
+        assert_eq!(Request::Pause.permission(), Permission::Control);
+        assert_eq!(
+            Request::Postpone { seconds: 60 }.permission(),
+            Permission::Control
+        );
+        let stop_time = Request::Set {
+            setting: RegistryEntries::ShutdownTime,
+            value: "18:00".to_string(),
+        };
+        assert_eq!(stop_time.permission(), Permission::Control);
+        let interval = Request::Set {
+            setting: RegistryEntries::ForceInterval,
+            value: "120".to_string(),
+        };
+        assert_eq!(interval.permission(), Permission::Admin);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn treats_own_user_and_root_as_owner() {
+        let own_uid = unsafe { libc::geteuid() };
+        assert!(Peer::unix(own_uid, None).owner);
+        assert!(Peer::unix(0, None).owner);
+        assert_eq!(
+            Peer::unix(own_uid.wrapping_add(1), None).owner,
+            own_uid == u32::MAX
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use std::path::Path;
+use tracing::trace;
+
+use anyhow::{Result, anyhow};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines, ReadHalf, WriteHalf};
+
+use event_bus::Event;
+
+use crate::{
+    protocol::{PROTOCOL_VERSION, Reply, Request, RequestFrame, ServerFrame, ServerMessage},
+    transport::{self, ClientStream},
+};
+
+/// Connection to the control endpoint of a running app.
+pub struct Client {
+    lines: Lines<BufReader<ReadHalf<ClientStream>>>,
+    writer: WriteHalf<ClientStream>,
+    next_id: u64,
+}
+
+impl Client {
+    /// Connects to the default [`crate::endpoint`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no app is listening.
+    pub async fn connect() -> Result<Client> {
+        Client::connect_to(&transport::endpoint()).await
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if no app is listening on `path`.
+    pub async fn connect_to(path: &Path) -> Result<Client> {
+        let stream = transport::connect(path)
+            .await
+            .map_err(|err| anyhow!("No running idler on {path:?}, err: {err}"))?;
+        let (reader, writer) = tokio::io::split(stream);
+        Ok(Client {
+            lines: BufReader::new(reader).lines(),
+            writer,
+            next_id: 1,
+        })
+    }
+
+    /// Sends `request` and waits for its reply. Events received in the meantime are
+    /// dropped, use a dedicated connection to [`Client::subscribe`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection fails or the app rejects the request.
+    pub async fn request(&mut self, request: Request) -> Result<Reply> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let frame = RequestFrame {
+            version: PROTOCOL_VERSION,
+            id,
+            request,
+        };
+        let mut line = serde_json::to_vec(&frame)?;
+        line.push(b'\n');
+        self.writer.write_all(&line).await?;
+
+        loop {
+            let Some(message) = self.read().await? else {
+                return Err(anyhow!("The idler closed the connection"));
+            };
+            match message {
+                ServerMessage::Reply {
+                    id: reply_id,
+                    reply,
+                } if reply_id == id || reply_id == 0 => {
+                    return match reply {
+                        Reply::Error(err) => Err(err.into()),
+                        reply => Ok(reply),
+                    };
+                }
+                message => trace!("Skipping control message: {message:?}"),
+            }
+        }
+    }
+
+    /// Asks for every event published by the app, read with [`Client::next_event`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection fails.
+    pub async fn subscribe(&mut self) -> Result<()> {
+        self.request(Request::Subscribe).await.map(drop)
+    }
+
+    /// Waits for the next event, `None` once the app closed the connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection fails or a frame can't be parsed.
+    pub async fn next_event(&mut self) -> Result<Option<Event>> {
+        while let Some(message) = self.read().await? {
+            match message {
+                ServerMessage::Event { event } => return Ok(Some(event)),
+                message @ ServerMessage::Reply { .. } => {
+                    trace!("Skipping control message: {message:?}");
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// The next message, `None` once the app closed the connection.
+    async fn read(&mut self) -> Result<Option<ServerMessage>> {
+        let Some(line) = self.lines.next_line().await? else {
+            return Ok(None);
+        };
+        let frame: ServerFrame = serde_json::from_str(&line)?;
+        if frame.version != PROTOCOL_VERSION {
+            return Err(anyhow!(
+                "Unsupported protocol version {}, expected {PROTOCOL_VERSION}",
+                frame.version
+            ));
+        }
+        Ok(Some(frame.message))
+    }
+}
@@ -0,0 +1,22 @@
+mod audit;
+mod auth;
+mod client;
+mod protocol;
+mod server;
+
+// A Unix domain socket on Linux and a named pipe on Windows.
+#[cfg(windows)]
+mod pipe;
+#[cfg(windows)]
+use pipe as transport;
+#[cfg(not(windows))]
+mod unix;
+#[cfg(not(windows))]
+use unix as transport;
+
+pub use audit::default_path as audit_log_path;
+pub use auth::{Peer, Policy};
+pub use client::Client;
+pub use protocol::{PROTOCOL_VERSION, Reply, Request, RequestFrame, ServerFrame, ServerMessage};
+pub use server::{Listener, serve};
+pub use transport::{InstanceLock, endpoint};
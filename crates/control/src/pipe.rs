@@ -0,0 +1,256 @@
+use std::{
+    env, io, mem,
+    os::windows::io::AsRawHandle,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use tokio::net::windows::named_pipe::{
+    ClientOptions, NamedPipeClient, NamedPipeServer, ServerOptions,
+};
+use windows::{
+    Win32::{
+        Foundation::{CloseHandle, ERROR_ALREADY_EXISTS, GetLastError, HANDLE, HLOCAL, LocalFree},
+        Security::{
+            Authorization::{
+                ConvertSidToStringSidW, ConvertStringSecurityDescriptorToSecurityDescriptorW,
+                SDDL_REVISION_1,
+            },
+            GetTokenInformation, PSECURITY_DESCRIPTOR, RevertToSelf, SECURITY_ATTRIBUTES,
+            TOKEN_QUERY, TOKEN_USER, TokenUser,
+        },
+        System::{
+            Pipes::{GetNamedPipeClientProcessId, ImpersonateNamedPipeClient},
+            Threading::{
+                CreateMutexW, GetCurrentProcess, GetCurrentThread, OpenProcessToken,
+                OpenThreadToken,
+            },
+        },
+    },
+    core::{HSTRING, PWSTR},
+};
+
+use crate::auth::{Peer, Policy};
+
+const PIPE_NAME: &str = r"\\.\pipe\smart-idler";
+const LOCAL_SYSTEM: &str = "S-1-5-18";
+// Read and write, without `FILE_CREATE_PIPE_INSTANCE` so other users can't add instances.
+const CLIENT_ACCESS: &str = "0x12019b";
+const ERROR_PIPE_BUSY: i32 = 231;
+const BUSY_RETRIES: u32 = 20;
+const BUSY_DELAY: Duration = Duration::from_millis(50);
+
+pub(crate) type ServerStream = NamedPipeServer;
+pub(crate) type ClientStream = NamedPipeClient;
+
+/// `$SMART_IDLER_SOCKET`, else the `smart-idler` named pipe.
+#[must_use]
+pub fn endpoint() -> PathBuf {
+    env::var_os("SMART_IDLER_SOCKET").map_or_else(|| PathBuf::from(PIPE_NAME), PathBuf::from)
+}
+
+/// Held by the running idler, so a second launch knows to hand over its command line. The
+/// named mutex lives in the session while a handle to it is open, so it goes away with the
+/// process.
+pub struct InstanceLock {
+    mutex: HANDLE,
+}
+
+impl InstanceLock {
+    /// Creates the mutex named after the [`endpoint`], `None` when another idler owns it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the mutex can't be created.
+    pub fn acquire() -> Result<Option<InstanceLock>> {
+        InstanceLock::acquire_at(&endpoint())
+    }
+
+    /// Creates the mutex named after `endpoint`, `None` when another idler owns it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the mutex can't be created.
+    pub fn acquire_at(endpoint: &Path) -> Result<Option<InstanceLock>> {
+        // Backslashes are reserved for the namespace prefix.
+        let name = format!(r"Local\{}", endpoint.to_string_lossy().replace('\\', "/"));
+        // SAFETY: the name outlives the call, the handle is closed when the lock is dropped.
+        let mutex = unsafe { CreateMutexW(None, false, &HSTRING::from(name.as_str())) }
+            .map_err(|err| anyhow!("Failed to create mutex {name}, err: {err}"))?;
+        // SAFETY: `GetLastError` only reads the error of the call above on this thread.
+        if unsafe { GetLastError() } == ERROR_ALREADY_EXISTS {
+            // SAFETY: the handle was opened above and isn't used afterwards.
+            let _ = unsafe { CloseHandle(mutex) };
+            return Ok(None);
+        }
+        Ok(Some(InstanceLock { mutex }))
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // SAFETY: the handle was opened by `acquire` and is closed exactly once.
+        let _ = unsafe { CloseHandle(self.mutex) };
+    }
+}
+
+/// The pipe instance waiting for the next client.
+pub(crate) struct Transport {
+    next: NamedPipeServer,
+    path: PathBuf,
+    /// DACL of every instance, in SDDL.
+    access: String,
+}
+
+/// Full access for this user and `LocalSystem`, read and write for every other
+/// authenticated user when the policy admits them.
+fn access(policy: &Policy) -> io::Result<String> {
+    let owner = process_user()?;
+    let mut access = format!("D:P(A;;GA;;;{owner})(A;;GA;;;SY)");
+    if policy.admits_others() {
+        access.push_str(&format!("(A;;{CLIENT_ACCESS};;;AU)"));
+    }
+    Ok(access)
+}
+
+/// Creates an instance of the pipe at `path`, only open to local clients allowed by `access`.
+fn create(path: &Path, access: &str, first: bool) -> io::Result<NamedPipeServer> {
+    let mut descriptor = PSECURITY_DESCRIPTOR::default();
+    // SAFETY: `descriptor` is allocated by the call and freed below, after the pipe copied it.
+    unsafe {
+        ConvertStringSecurityDescriptorToSecurityDescriptorW(
+            &HSTRING::from(access),
+            SDDL_REVISION_1,
+            &mut descriptor,
+            None,
+        )?;
+    }
+    let mut attributes = SECURITY_ATTRIBUTES {
+        nLength: u32::try_from(mem::size_of::<SECURITY_ATTRIBUTES>()).unwrap_or(u32::MAX),
+        lpSecurityDescriptor: descriptor.0,
+        bInheritHandle: false.into(),
+    };
+    // SAFETY: `attributes` and its descriptor outlive the call.
+    let pipe = unsafe {
+        ServerOptions::new()
+            .first_pipe_instance(first)
+            .reject_remote_clients(true)
+            .create_with_security_attributes_raw(path, (&raw mut attributes).cast())
+    };
+    // SAFETY: `descriptor` was allocated with `LocalAlloc` and is no longer used.
+    unsafe {
+        LocalFree(Some(HLOCAL(descriptor.0)));
+    }
+    pipe
+}
+
+impl Transport {
+    /// Creates the first instance of the pipe, failing if another process owns it.
+    #[allow(clippy::unused_async)]
+    pub(crate) async fn bind(path: &Path, policy: &Policy) -> Result<Transport> {
+        let access = access(policy)?;
+        let next = create(path, &access, true)
+            .map_err(|err| anyhow!("Another instance owns {path:?}, err: {err}"))?;
+        Ok(Transport {
+            next,
+            path: path.to_path_buf(),
+            access,
+        })
+    }
+
+    /// Waits for a client on the current instance and creates the next one.
+    pub(crate) async fn accept(&mut self) -> io::Result<ServerStream> {
+        self.next.connect().await?;
+        let next = create(&self.path, &self.access, false)?;
+        Ok(mem::replace(&mut self.next, next))
+    }
+}
+
+/// SID of the user of `token`, as a string.
+///
+/// # Safety
+///
+/// `token` must be a valid token opened with `TOKEN_QUERY`.
+unsafe fn token_user(token: HANDLE) -> io::Result<String> {
+    let mut size = 0;
+    // The first call fails and reports the size of the `TOKEN_USER` and its SID.
+    let _ = unsafe { GetTokenInformation(token, TokenUser, None, 0, &mut size) };
+    // `u64` keeps the SID pointer aligned.
+    let mut buffer = vec![0u64; (size as usize).div_ceil(mem::size_of::<u64>())];
+    let user = unsafe {
+        GetTokenInformation(
+            token,
+            TokenUser,
+            Some(buffer.as_mut_ptr().cast()),
+            size,
+            &mut size,
+        )?;
+        &*buffer.as_ptr().cast::<TOKEN_USER>()
+    };
+    let mut sid = PWSTR::null();
+    unsafe {
+        ConvertSidToStringSidW(user.User.Sid, &mut sid)?;
+    }
+    let text = unsafe { sid.to_string() };
+    unsafe {
+        LocalFree(Some(HLOCAL(sid.0.cast())));
+    }
+    text.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// SID of the user running this process.
+fn process_user() -> io::Result<String> {
+    let mut token = HANDLE::default();
+    // SAFETY: the token is opened for querying and closed once read.
+    unsafe {
+        OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token)?;
+        let user = token_user(token);
+        CloseHandle(token)?;
+        user
+    }
+}
+
+/// SID of the client connected to `pipe`, read by impersonating it for a moment.
+fn client_user(pipe: HANDLE) -> io::Result<String> {
+    let mut token = HANDLE::default();
+    // SAFETY: the thread reverts to its own token before anything else runs on it.
+    unsafe {
+        ImpersonateNamedPipeClient(pipe)?;
+        let opened = OpenThreadToken(GetCurrentThread(), TOKEN_QUERY, true, &mut token);
+        RevertToSelf()?;
+        opened?;
+        let user = token_user(token);
+        CloseHandle(token)?;
+        user
+    }
+}
+
+/// Identifies the client from its token.
+pub(crate) fn peer(stream: &ServerStream) -> io::Result<Peer> {
+    let pipe = HANDLE(stream.as_raw_handle());
+    let mut pid = 0;
+    // SAFETY: `pipe` is a connected server instance.
+    unsafe { GetNamedPipeClientProcessId(pipe, &mut pid)? };
+    let user = client_user(pipe)?;
+    let owner = user == process_user()? || user == LOCAL_SYSTEM;
+    Ok(Peer {
+        user,
+        pid: Some(pid),
+        owner,
+    })
+}
+
+/// Opens the pipe, waiting a little while every instance is busy.
+pub(crate) async fn connect(path: &Path) -> io::Result<ClientStream> {
+    let mut retries = 0;
+    loop {
+        match ClientOptions::new().open(path) {
+            Err(err) if err.raw_os_error() == Some(ERROR_PIPE_BUSY) && retries < BUSY_RETRIES => {
+                retries += 1;
+                tokio::time::sleep(BUSY_DELAY).await;
+            }
+            result => return result,
+        }
+    }
+}
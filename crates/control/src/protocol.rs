@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+use app_controller::{CommandError, status::Status};
+use event_bus::Event;
+use registry_ops::RegistryEntries;
+
+/// Version of the protocol spoken on the control socket. Frames of another version are
+/// rejected, so it is bumped on every incompatible change.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// What a client asks the running app to do.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    Status,
+    /// Keep the system awake again, cancelling any timer.
+    Resume,
+    /// Stop keeping the system awake, cancelling any timer.
+    Pause,
+    PauseFor {
+        seconds: u64,
+    },
+    KeepAwakeFor {
+        seconds: u64,
+    },
+    CancelTimer,
+    /// Writes a user setting, validated like the UI does.
+    Set {
+        setting: RegistryEntries,
+        value: String,
+    },
+    /// Schedules the daily stop at `time` (`%H:%M`), or cancels it with `STOP`.
+    Schedule {
+        time: String,
+    },
+    /// Moves the pending stop later, once.
+    Postpone {
+        seconds: u64,
+    },
+    /// Lists the weekly schedule rules.
+    Rules,
+    /// Adds a weekly schedule rule, e.g. `mon-thu 08:30-17:30`.
+    AddRule {
+        rule: String,
+    },
+    /// Replaces the weekly schedule rule numbered `number`, counting from 1.
+    ReplaceRule {
+        number: usize,
+        rule: String,
+    },
+    /// Removes the weekly schedule rule numbered `number`, counting from 1.
+    RemoveRule {
+        number: usize,
+    },
+    /// Receive every event published by the app on this connection, after the reply.
+    Subscribe,
+    /// Ask the app to exit.
+    Exit,
+    /// Ask the app to show its window, ignored without one.
+    Show,
+}
+
+/// A request with the protocol version and an id echoed in the reply, one JSON object per
+/// line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestFrame {
+    pub version: u32,
+    pub id: u64,
+    #[serde(flatten)]
+    pub request: Request,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "reply", rename_all = "snake_case")]
+pub enum Reply {
+    Done,
+    Status(Box<Status>),
+    Subscribed,
+    /// The weekly schedule rules, after any change.
+    Rules {
+        rules: Vec<String>,
+    },
+    Error(CommandError),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerMessage {
+    Reply { id: u64, reply: Reply },
+    Event { event: Event },
+}
+
+/// A reply or, once subscribed, an event, one JSON object per line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerFrame {
+    pub version: u32,
+    #[serde(flatten)]
+    pub message: ServerMessage,
+}
+
+impl ServerFrame {
+    #[must_use]
+    pub fn new(message: ServerMessage) -> ServerFrame {
+        ServerFrame {
+            version: PROTOCOL_VERSION,
+            message,
+        }
+    }
+}
@@ -0,0 +1,350 @@
+use std::{io, mem, path::PathBuf, sync::Arc, time::Duration};
+use tracing::{debug, error, info, trace};
+
+use anyhow::Result;
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+
+use app_controller::{AppContext, CommandError, status, weekly};
+use event_bus::{Event, Subscription};
+use registry_ops::{Permission, RegistryEntries, ShutdownTime, WeeklySchedule};
+
+use crate::{
+    audit,
+    auth::{Peer, Policy},
+    protocol::{PROTOCOL_VERSION, Reply, Request, RequestFrame, ServerFrame, ServerMessage},
+    transport::{self, Transport},
+};
+
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Longest request line, far above any valid request. A peer sending more without a newline
+/// is disconnected, so it can't grow the memory of the app.
+const MAX_LINE: usize = 64 * 1024;
+
+/// The bound control endpoint, served with [`serve`].
+pub struct Listener {
+    transport: Transport,
+    path: PathBuf,
+    policy: Policy,
+}
+
+impl Listener {
+    /// Binds the default [`crate::endpoint`] with the stored [`Policy`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if another instance is listening or the endpoint can't be created.
+    pub async fn bind() -> Result<Listener> {
+        Listener::bind_to(transport::endpoint(), Policy::load()).await
+    }
+
+    /// Binds `path`, letting in the users `policy` grants some permission.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if another instance is listening or the endpoint can't be created.
+    pub async fn bind_to(path: PathBuf, policy: Policy) -> Result<Listener> {
+        let transport = Transport::bind(&path, &policy).await?;
+        Ok(Listener {
+            transport,
+            path,
+            policy,
+        })
+    }
+}
+
+/// Accepts clients forever, each handled by its own task.
+pub async fn serve(mut listener: Listener, context: Arc<AppContext>) {
+    info!(
+        "Listening for control clients on {:?}, granting {} to this user and {} to others",
+        listener.path, listener.policy.owner, listener.policy.others
+    );
+    loop {
+        match listener.transport.accept().await {
+            Ok(stream) => {
+                let peer = match transport::peer(&stream) {
+                    Ok(peer) => peer,
+                    Err(err) => {
+                        debug!("Failed to identify control client, err: {err}");
+                        audit::denied(None, "connection", Permission::Read, Permission::None);
+                        continue;
+                    }
+                };
+                debug!("Control client connected: {peer}");
+                let access = Access {
+                    peer,
+                    policy: listener.policy,
+                };
+                tokio::spawn(connection(stream, Arc::clone(&context), access));
+            }
+            Err(err) => {
+                error!("Failed to accept control client, err: {err}");
+                tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+            }
+        }
+    }
+}
+
+async fn next_event(subscription: &mut Option<Subscription>) -> Option<Event> {
+    match subscription {
+        Some(subscription) => subscription.next().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// A connected peer and the policy deciding what it may do.
+struct Access {
+    peer: Peer,
+    policy: Policy,
+}
+
+/// Reads the next line without its line ending, `None` at the end of the stream. Cancel safe:
+/// a partial line stays in `buffer` for the next call.
+///
+/// # Errors
+///
+/// Returns [`io::ErrorKind::InvalidData`] for lines longer than [`MAX_LINE`] or not UTF-8.
+async fn read_line(
+    reader: &mut (impl AsyncBufRead + Unpin),
+    buffer: &mut Vec<u8>,
+) -> io::Result<Option<String>> {
+    let limit = (MAX_LINE + 1).saturating_sub(buffer.len()) as u64;
+    let read = (&mut *reader).take(limit).read_until(b'\n', buffer).await?;
+    let complete = buffer.last() == Some(&b'\n');
+    if !complete && buffer.len() > MAX_LINE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Request line longer than {MAX_LINE} bytes"),
+        ));
+    }
+    if read == 0 && buffer.is_empty() {
+        return Ok(None);
+    }
+    let mut line = String::from_utf8(mem::take(buffer))
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let trimmed = line.trim_end_matches(['\n', '\r']).len();
+    line.truncate(trimmed);
+    Ok(Some(line))
+}
+
+async fn connection(stream: impl AsyncRead + AsyncWrite, context: Arc<AppContext>, access: Access) {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    let mut buffer = Vec::new();
+    let mut subscription = None;
+    loop {
+        let message = tokio::select! {
+            line = read_line(&mut reader, &mut buffer) => match line {
+                Ok(Some(line)) => respond(&context, &access, &line, &mut subscription).await,
+                Ok(None) => break,
+                Err(err) => {
+                    debug!("Failed to read from control client, err: {err}");
+                    break;
+                }
+            },
+            Some(event) = next_event(&mut subscription) => ServerMessage::Event { event },
+        };
+        if let Err(err) = write(&mut writer, &ServerFrame::new(message)).await {
+            debug!("Failed to write to control client, err: {err}");
+            break;
+        }
+    }
+    debug!("Control client disconnected");
+}
+
+async fn write(writer: &mut (impl AsyncWrite + Unpin), frame: &ServerFrame) -> Result<()> {
+    let mut line = serde_json::to_vec(frame)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    Ok(())
+}
+
+async fn respond(
+    context: &AppContext,
+    access: &Access,
+    line: &str,
+    subscription: &mut Option<Subscription>,
+) -> ServerMessage {
+    let frame: RequestFrame = match serde_json::from_str(line) {
+        Ok(frame) => frame,
+        Err(err) => {
+            return ServerMessage::Reply {
+                id: 0,
+                reply: Reply::Error(CommandError::InvalidInput(format!(
+                    "Invalid request, err: {err}"
+                ))),
+            };
+        }
+    };
+    trace!("Got control request: {frame:?}");
+    let reply = if frame.version != PROTOCOL_VERSION {
+        Err(CommandError::InvalidInput(format!(
+            "Unsupported protocol version {}, expected {PROTOCOL_VERSION}",
+            frame.version
+        )))
+    } else if let Err(err) = authorize(access, &frame.request) {
+        Err(err)
+    } else {
+        handle(context, frame.request, subscription).await
+    };
+    ServerMessage::Reply {
+        id: frame.id,
+        reply: reply.unwrap_or_else(Reply::Error),
+    }
+}
+
+fn authorize(access: &Access, request: &Request) -> Result<(), CommandError> {
+    let description = serde_json::to_string(request).unwrap_or_else(|_| format!("{request:?}"));
+    access
+        .policy
+        .authorize(&access.peer, request.permission(), &description)
+}
+
+fn duration(seconds: u64) -> Result<Duration, CommandError> {
+    if seconds == 0 {
+        return Err(CommandError::InvalidInput(
+            "Duration must be greater than zero".to_string(),
+        ));
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+fn schedule(context: &AppContext, time: &str) -> Result<Reply, CommandError> {
+    context.schedule_stop(time.parse::<ShutdownTime>()?)?;
+    Ok(Reply::Done)
+}
+
+fn rules(schedule: &WeeklySchedule) -> Reply {
+    Reply::Rules {
+        rules: schedule.rules().iter().map(ToString::to_string).collect(),
+    }
+}
+
+async fn handle(
+    context: &AppContext,
+    request: Request,
+    subscription: &mut Option<Subscription>,
+) -> Result<Reply, CommandError> {
+    let timer_error = |err: anyhow::Error| CommandError::InvalidInput(err.to_string());
+    match request {
+        Request::Status => Ok(Reply::Status(Box::new(status::current(context)?))),
+        Request::Resume => {
+            context.running_idle_service()?.resume();
+            Ok(Reply::Done)
+        }
+        Request::Pause => {
+            context.running_idle_service()?.pause();
+            Ok(Reply::Done)
+        }
+        Request::PauseFor { seconds } => {
+            let service = context.running_idle_service()?;
+            service.pause_for(duration(seconds)?).map_err(timer_error)?;
+            Ok(Reply::Done)
+        }
+        Request::KeepAwakeFor { seconds } => {
+            let service = context.running_idle_service()?;
+            service
+                .keep_awake_for(duration(seconds)?)
+                .map_err(timer_error)?;
+            Ok(Reply::Done)
+        }
+        Request::CancelTimer => {
+            context.running_idle_service()?.cancel_timer();
+            Ok(Reply::Done)
+        }
+        Request::Set {
+            setting: RegistryEntries::ShutdownTime,
+            value,
+        } => schedule(context, &value),
+        Request::Set { setting, value } => {
+            if !RegistryEntries::SETTINGS.contains(&setting) {
+                return Err(CommandError::InvalidInput(format!(
+                    "{setting} is not a user setting"
+                )));
+            }
+            registry_ops::validate(setting, &value)?;
+            context.store(setting, &value)?;
+            Ok(Reply::Done)
+        }
+        Request::Schedule { time } => schedule(context, &time),
+        Request::Postpone { seconds } => {
+            context.postpone_stop(duration(seconds)?).await?;
+            Ok(Reply::Done)
+        }
+        Request::Rules => Ok(rules(&weekly::rules(context)?)),
+        Request::AddRule { rule } => Ok(rules(&weekly::add_rule(context, &rule)?)),
+        Request::ReplaceRule { number, rule } => {
+            Ok(rules(&weekly::replace_rule(context, number, &rule)?))
+        }
+        Request::RemoveRule { number } => Ok(rules(&weekly::remove_rule(context, number)?)),
+        Request::Exit => {
+            context.events().publish(Event::ExitRequested);
+            Ok(Reply::Done)
+        }
+        Request::Show => {
+            context.events().publish(Event::ShowRequested);
+            Ok(Reply::Done)
+        }
+        Request::Subscribe => {
+            *subscription = Some(context.events().subscribe("control client"));
+            Ok(Reply::Subscribed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn lines(mut input: &[u8]) -> Vec<io::Result<Option<String>>> {
+        let mut buffer = Vec::new();
+        let mut lines = Vec::new();
+        loop {
+            let line = read_line(&mut input, &mut buffer).await;
+            let done = !matches!(line, Ok(Some(_)));
+            lines.push(line);
+            if done {
+                return lines;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_lines_without_endings() {
+        let lines = lines(b"{\"a\":1}\r\n{\"b\":2}\nlast").await;
+        let lines: Vec<_> = lines.into_iter().map(Result::unwrap).collect();
+        assert_eq!(
+            lines,
+            [
+                Some("{\"a\":1}".to_string()),
+                Some("{\"b\":2}".to_string()),
+                Some("last".to_string()),
+                None
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn accepts_the_longest_line() {
+        let mut input = vec![b'x'; MAX_LINE];
+        input.push(b'\n');
+        let lines = lines(&input).await;
+        assert_eq!(lines[0].as_ref().unwrap().as_ref().unwrap().len(), MAX_LINE);
+    }
+
+    #[tokio::test]
+    async fn rejects_endless_lines() {
+        let input = vec![b'x'; MAX_LINE * 4];
+        let lines = lines(&input).await;
+        assert_eq!(lines.len(), 1);
+        let err = lines.into_iter().next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_utf8() {
+        let err = lines(b"\xff\xfe\n").await.remove(0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
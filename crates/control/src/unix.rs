@@ -0,0 +1,223 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io,
+    os::{
+        fd::AsRawFd,
+        unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt},
+    },
+    path::{Path, PathBuf},
+};
+use tracing::{debug, error};
+
+use anyhow::{Result, anyhow};
+use tokio::net::{UnixListener, UnixStream};
+
+use crate::auth::{Peer, Policy};
+
+const SOCKET_FILE: &str = "smart-idler.sock";
+
+pub(crate) type ServerStream = UnixStream;
+pub(crate) type ClientStream = UnixStream;
+
+/// `$SMART_IDLER_SOCKET`, else `smart-idler.sock` in `$XDG_RUNTIME_DIR`, or in a folder of
+/// the temp folder only the current user may enter.
+#[must_use]
+pub fn endpoint() -> PathBuf {
+    if let Some(path) = env::var_os("SMART_IDLER_SOCKET") {
+        return PathBuf::from(path);
+    }
+    env::var_os("XDG_RUNTIME_DIR")
+        .map_or_else(fallback_dir, PathBuf::from)
+        .join(SOCKET_FILE)
+}
+
+/// `smart-idler-<uid>` in the temp folder, for sessions without `$XDG_RUNTIME_DIR`.
+fn fallback_dir() -> PathBuf {
+    // SAFETY: `geteuid` has no preconditions and can't fail.
+    let uid = unsafe { libc::geteuid() };
+    env::temp_dir().join(format!("smart-idler-{uid}"))
+}
+
+/// Makes sure the folder of `path` is private when it is the [`fallback_dir`], creating it
+/// if `create` is set. Anyone can create it in the shared temp folder first, so one another
+/// user owns or may enter is refused rather than trusted with the lock and the socket.
+fn check_fallback_dir(path: &Path, create: bool) -> io::Result<()> {
+    let dir = fallback_dir();
+    if path.parent() == Some(dir.as_path()) {
+        check_private_dir(&dir, create)?;
+    }
+    Ok(())
+}
+
+/// Fails unless `dir` is a folder, not a link, that only the current user owns and may
+/// enter. Creates it with mode 0700 first if `create` is set.
+fn check_private_dir(dir: &Path, create: bool) -> io::Result<()> {
+    if create {
+        match fs::DirBuilder::new().mode(0o700).create(dir) {
+            Ok(()) => debug!("Created {dir:?}"),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(err) => return Err(err),
+        }
+    }
+    let metadata = fs::symlink_metadata(dir)?;
+    // SAFETY: `geteuid` has no preconditions and can't fail.
+    let uid = unsafe { libc::geteuid() };
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "{} must be a folder only the current user owns and may enter",
+                dir.display()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Held by the running idler, so a second launch knows to hand over its command line. The
+/// `flock` goes away with the process, so a crash leaves no stale lock behind.
+pub struct InstanceLock {
+    _file: fs::File,
+}
+
+impl InstanceLock {
+    /// Locks the file next to the [`endpoint`], `None` when another idler holds it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lock file can't be opened or locked, or its folder isn't
+    /// private.
+    pub fn acquire() -> Result<Option<InstanceLock>> {
+        InstanceLock::acquire_at(&endpoint())
+    }
+
+    /// Locks the file next to `endpoint`, `None` when another idler holds it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lock file can't be opened or locked, or its folder isn't
+    /// private.
+    pub fn acquire_at(endpoint: &Path) -> Result<Option<InstanceLock>> {
+        let path = endpoint.with_extension("lock");
+        check_fallback_dir(&path, true)
+            .map_err(|err| anyhow!("Refusing to lock {path:?}, err: {err}"))?;
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .mode(0o600)
+            .open(&path)
+            .map_err(|err| anyhow!("Failed to open {path:?}, err: {err}"))?;
+        // SAFETY: the descriptor stays open for as long as `file` lives.
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::WouldBlock {
+                return Ok(None);
+            }
+            return Err(anyhow!("Failed to lock {path:?}, err: {err}"));
+        }
+        debug!("Locked {path:?}");
+        Ok(Some(InstanceLock { _file: file }))
+    }
+}
+
+/// Listening socket, removed from the file system when dropped.
+pub(crate) struct Transport {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl Transport {
+    /// Binds `path`, replacing a socket file left behind by an instance that crashed.
+    pub(crate) async fn bind(path: &Path, policy: &Policy) -> Result<Transport> {
+        check_fallback_dir(path, true)
+            .map_err(|err| anyhow!("Refusing to listen on {path:?}, err: {err}"))?;
+        if UnixStream::connect(path).await.is_ok() {
+            return Err(anyhow!("Another instance is listening on {path:?}"));
+        }
+        match fs::remove_file(path) {
+            Ok(()) => debug!("Removed stale socket {path:?}"),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+        let listener = UnixListener::bind(path)?;
+        // Only the current user may connect, unless the policy grants others some access.
+        // They also need to reach the socket, which neither `$XDG_RUNTIME_DIR` nor the
+        // fallback folder allows.
+        let mode = if policy.admits_others() { 0o666 } else { 0o600 };
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+        Ok(Transport {
+            listener,
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub(crate) async fn accept(&mut self) -> io::Result<ServerStream> {
+        let (stream, _) = self.listener.accept().await?;
+        Ok(stream)
+    }
+}
+
+impl Drop for Transport {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            error!("Failed to remove socket {:?}, err: {err}", self.path);
+        }
+    }
+}
+
+pub(crate) async fn connect(path: &Path) -> io::Result<ClientStream> {
+    check_fallback_dir(path, false)?;
+    UnixStream::connect(path).await
+}
+
+/// Identifies the client with `SO_PEERCRED`.
+pub(crate) fn peer(stream: &ServerStream) -> io::Result<Peer> {
+    let credentials = stream.peer_cred()?;
+    let pid = credentials.pid().and_then(|pid| u32::try_from(pid).ok());
+    Ok(Peer::unix(credentials.uid(), pid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_denied(result: io::Result<()>) -> bool {
+        result.is_err_and(|err| err.kind() == io::ErrorKind::PermissionDenied)
+    }
+
+    #[test]
+    fn locks_one_instance_at_a_time() {
+        let temp = tempfile::tempdir().unwrap();
+        let endpoint = temp.path().join(SOCKET_FILE);
+
+        let first = InstanceLock::acquire_at(&endpoint).unwrap();
+        assert!(first.is_some());
+        assert!(InstanceLock::acquire_at(&endpoint).unwrap().is_none());
+        drop(first);
+        assert!(InstanceLock::acquire_at(&endpoint).unwrap().is_some());
+    }
+
+    #[test]
+    fn keeps_the_fallback_folder_private() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("smart-idler");
+
+        assert!(check_private_dir(&dir, false).is_err());
+        check_private_dir(&dir, true).unwrap();
+        let mode = fs::metadata(&dir).unwrap().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        check_private_dir(&dir, true).unwrap();
+
+        // Others could swap the socket in a folder they may enter.
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(is_denied(check_private_dir(&dir, true)));
+
+        // A link could point anywhere.
+        let link = temp.path().join("link");
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700)).unwrap();
+        std::os::unix::fs::symlink(&dir, &link).unwrap();
+        assert!(is_denied(check_private_dir(&link, true)));
+    }
+}
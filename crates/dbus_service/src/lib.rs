@@ -0,0 +1,6 @@
+// Desktops only look for the idler on the D-Bus session bus, which Windows doesn't have.
+#[cfg(not(windows))]
+mod service;
+
+#[cfg(not(windows))]
+pub use service::{BUS_NAME, INTERFACE, OBJECT_PATH, serve, serve_on};
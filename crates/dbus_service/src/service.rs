@@ -0,0 +1,287 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::{debug, error, info};
+
+use anyhow::{Result, anyhow};
+use zbus::{Connection, fdo, interface, message::Header, object_server::InterfaceRef};
+
+use app_controller::{AppContext, CommandError};
+use control::{Peer, Policy};
+use event_bus::{Event, IdlerMode, Subscription};
+use registry_ops::{ForceInterval, Permission, RegistryEntries, ShutdownTime};
+
+/// Well-known name requested on the session bus.
+pub const BUS_NAME: &str = "org.smartidler.Idler1";
+pub const OBJECT_PATH: &str = "/org/smartidler/Idler1";
+pub const INTERFACE: &str = "org.smartidler.Idler1";
+
+fn to_fdo(err: CommandError) -> fdo::Error {
+    match err {
+        CommandError::InvalidInput(message) => fdo::Error::InvalidArgs(message),
+        CommandError::Denied(message) => fdo::Error::AccessDenied(message),
+        err => fdo::Error::Failed(err.to_string()),
+    }
+}
+
+/// Values of the D-Bus properties at one point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Properties {
+    /// `-1` while the idle time isn't tracked on this platform.
+    idle_seconds: i64,
+    active: bool,
+    /// Unix time of the next injection, `0` while paused.
+    next_injection: u64,
+}
+
+impl Properties {
+    fn read(context: &AppContext) -> Result<Properties, CommandError> {
+        let service = context.running_idle_service()?;
+        let next_injection = service
+            .until_injection()
+            .and_then(|remaining| SystemTime::now().checked_add(remaining))
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |time| time.as_secs());
+        Ok(Properties {
+            idle_seconds: idler_utils::get_last_input()
+                .and_then(|seconds| i64::try_from(seconds).ok())
+                .unwrap_or(-1),
+            active: matches!(
+                service.mode(),
+                IdlerMode::Active | IdlerMode::TimedKeepAwake
+            ),
+            next_injection,
+        })
+    }
+}
+
+/// The idle engine, exported at [`OBJECT_PATH`].
+struct Idler {
+    context: Arc<AppContext>,
+    policy: Policy,
+}
+
+impl Idler {
+    /// Checks the sender of `header` against the control policy, like clients of the control
+    /// endpoint.
+    async fn authorize(
+        &self,
+        connection: &Connection,
+        header: &Header<'_>,
+        required: Permission,
+        request: &str,
+    ) -> fdo::Result<()> {
+        let sender = header
+            .sender()
+            .ok_or_else(|| fdo::Error::AccessDenied("The call has no sender".to_string()))?;
+        let bus = fdo::DBusProxy::new(connection).await?;
+        let uid = bus.get_connection_unix_user(sender.clone().into()).await?;
+        let pid = bus
+            .get_connection_unix_process_id(sender.clone().into())
+            .await
+            .ok();
+        self.policy
+            .authorize(&Peer::unix(uid, pid), required, request)
+            .map_err(to_fdo)
+    }
+}
+
+#[interface(name = "org.smartidler.Idler1")]
+impl Idler {
+    /// Keeps the system awake, cancelling any timer.
+    async fn start(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+    ) -> fdo::Result<()> {
+        self.authorize(connection, &header, Permission::Control, "Start")
+            .await?;
+        self.context
+            .running_idle_service()
+            .map_err(to_fdo)?
+            .resume();
+        Ok(())
+    }
+
+    /// Stops keeping the system awake, cancelling any timer.
+    async fn stop(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+    ) -> fdo::Result<()> {
+        self.authorize(connection, &header, Permission::Control, "Stop")
+            .await?;
+        self.context.running_idle_service().map_err(to_fdo)?.pause();
+        Ok(())
+    }
+
+    /// Stops keeping the system awake for `seconds`.
+    async fn pause(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        seconds: u64,
+    ) -> fdo::Result<()> {
+        self.authorize(connection, &header, Permission::Control, "Pause")
+            .await?;
+        if seconds == 0 {
+            return Err(fdo::Error::InvalidArgs(
+                "Duration must be greater than zero".to_string(),
+            ));
+        }
+        self.context
+            .running_idle_service()
+            .map_err(to_fdo)?
+            .pause_for(Duration::from_secs(seconds))
+            .map_err(|err| fdo::Error::InvalidArgs(err.to_string()))
+    }
+
+    /// Sets the force interval, in seconds.
+    async fn set_interval(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        seconds: u64,
+    ) -> fdo::Result<()> {
+        self.authorize(connection, &header, Permission::Admin, "SetInterval")
+            .await?;
+        let interval = ForceInterval::try_from(seconds).map_err(CommandError::from);
+        self.context
+            .store(RegistryEntries::ForceInterval, &interval.map_err(to_fdo)?)
+            .map_err(to_fdo)
+    }
+
+    /// Schedules the daily stop at `time` (`HH:MM`), or cancels it with `STOP`.
+    async fn schedule_stop(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        time: &str,
+    ) -> fdo::Result<()> {
+        self.authorize(connection, &header, Permission::Control, "ScheduleStop")
+            .await?;
+        let time: ShutdownTime = time.parse().map_err(CommandError::from).map_err(to_fdo)?;
+        self.context.schedule_stop(time).map_err(to_fdo)?;
+        Ok(())
+    }
+
+    /// Seconds since the last user input, `-1` when unknown.
+    #[zbus(property)]
+    fn idle_seconds(&self) -> fdo::Result<i64> {
+        Ok(Properties::read(&self.context)
+            .map_err(to_fdo)?
+            .idle_seconds)
+    }
+
+    /// Whether the system is kept awake.
+    #[zbus(property)]
+    fn active(&self) -> fdo::Result<bool> {
+        Ok(Properties::read(&self.context).map_err(to_fdo)?.active)
+    }
+
+    /// Unix time of the next injection, `0` while paused.
+    #[zbus(property)]
+    fn next_injection(&self) -> fdo::Result<u64> {
+        Ok(Properties::read(&self.context)
+            .map_err(to_fdo)?
+            .next_injection)
+    }
+}
+
+/// Emits `PropertiesChanged` for every property that differs between `last` and `current`,
+/// or for all of them when there is nothing to compare to. The next injection is recomputed
+/// from the time left, so it only counts as changed when it moved by more than a second.
+async fn announce(
+    idler: &InterfaceRef<Idler>,
+    last: Option<Properties>,
+    current: Properties,
+) -> zbus::Result<()> {
+    let emitter = idler.signal_emitter();
+    let idler = idler.get().await;
+    if last.is_none_or(|last| last.idle_seconds != current.idle_seconds) {
+        idler.idle_seconds_changed(emitter).await?;
+    }
+    if last.is_none_or(|last| last.active != current.active) {
+        idler.active_changed(emitter).await?;
+    }
+    if last.is_none_or(|last| last.next_injection.abs_diff(current.next_injection) > 1) {
+        idler.next_injection_changed(emitter).await?;
+    }
+    Ok(())
+}
+
+/// Whether `event` can change one of the properties: injections and returning users reset
+/// the idle time, pauses and timers change the mode, and the force interval moves the next
+/// injection.
+fn changes_properties(event: &Event) -> bool {
+    match event {
+        Event::InputInjected { .. } | Event::UserReturned { .. } | Event::ModeChanged { .. } => {
+            true
+        }
+        Event::SettingChanged { setting, .. } => {
+            *setting == RegistryEntries::ForceInterval.to_string()
+        }
+        _ => false,
+    }
+}
+
+/// Announces the properties that changed after each event that can change them.
+async fn announce_changes(
+    idler: InterfaceRef<Idler>,
+    context: &AppContext,
+    mut subscription: Subscription,
+) {
+    let mut last = Properties::read(context).ok();
+    while let Some(event) = subscription.next().await {
+        if !changes_properties(&event) {
+            continue;
+        }
+        let current = match Properties::read(context) {
+            Ok(current) => current,
+            Err(err) => {
+                debug!("Skipping property update, err: {err}");
+                continue;
+            }
+        };
+        if let Err(err) = announce(&idler, last.replace(current), current).await {
+            error!("Failed to announce property changes, err: {err}");
+        }
+    }
+}
+
+/// Serves the idler on the session bus from `$DBUS_SESSION_BUS_ADDRESS`, so a private
+/// `dbus-daemon` can stand in for the desktop one.
+///
+/// # Errors
+///
+/// Returns an error if the bus can't be reached or another idler owns [`BUS_NAME`].
+pub async fn serve(context: Arc<AppContext>) -> Result<()> {
+    let connection = Connection::session().await?;
+    serve_on(&connection, context).await
+}
+
+/// Exports the idler on `connection`, requests [`BUS_NAME`] and announces property changes
+/// until the task is aborted.
+///
+/// # Errors
+///
+/// Returns an error if the object can't be exported or another idler owns [`BUS_NAME`].
+pub async fn serve_on(connection: &Connection, context: Arc<AppContext>) -> Result<()> {
+    // Subscribe first, so no change made through the exported object goes unannounced.
+    let subscription = context.events().subscribe("dbus");
+    let idler = Idler {
+        context: Arc::clone(&context),
+        policy: Policy::load(),
+    };
+    let object_server = connection.object_server();
+    object_server.at(OBJECT_PATH, idler).await?;
+    connection
+        .request_name(BUS_NAME)
+        .await
+        .map_err(|err| anyhow!("Another idler owns {BUS_NAME}, err: {err}"))?;
+    info!("Serving {BUS_NAME} on D-Bus");
+    let idler = object_server.interface::<_, Idler>(OBJECT_PATH).await?;
+    announce_changes(idler, &context, subscription).await;
+    Ok(())
+}
@@ -0,0 +1,125 @@
+//! Serves the idler on a private `dbus-daemon`, which must be installed, and drives it like a
+//! desktop would.
+#![cfg(target_os = "linux")]
+
+use std::{env, sync::Arc, time::Duration};
+
+use futures_util::StreamExt;
+use zbus::{fdo, proxy};
+
+use app_controller::AppContext;
+use dbus_service::{BUS_NAME, INTERFACE, OBJECT_PATH};
+use event_bus::EventBus;
+use registry_ops::RegistryEntries;
+use test_support::DbusDaemon;
+
+#[proxy(
+    interface = "org.smartidler.Idler1",
+    default_service = "org.smartidler.Idler1",
+    default_path = "/org/smartidler/Idler1"
+)]
+trait Idler {
+    fn start(&self) -> zbus::Result<()>;
+    fn stop(&self) -> zbus::Result<()>;
+    fn pause(&self, seconds: u64) -> zbus::Result<()>;
+    fn set_interval(&self, seconds: u64) -> zbus::Result<()>;
+    fn schedule_stop(&self, time: &str) -> zbus::Result<()>;
+    #[zbus(property)]
+    fn idle_seconds(&self) -> zbus::Result<i64>;
+    #[zbus(property)]
+    fn active(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn next_injection(&self) -> zbus::Result<u64>;
+}
+
+fn is_invalid_args(err: &zbus::Error) -> bool {
+    matches!(err, zbus::Error::MethodError(name, _, _) if name.as_str() == "org.freedesktop.DBus.Error.InvalidArgs")
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn serves_methods_and_properties() {
+    let bus = DbusDaemon::start();
+    let dir = bus.dir();
+    // Keep the settings, statistics and audit log of this run out of the user's.
+    env::set_var("SMART_IDLER_CONFIG", dir.join("settings.json"));
+    for variable in ["XDG_CONFIG_HOME", "XDG_DATA_HOME", "XDG_STATE_HOME"] {
+        env::set_var(variable, dir);
+    }
+
+    let context = Arc::new(AppContext::new(EventBus::new()));
+    context.start_services();
+    let server = bus.connect().await;
+    let shared = Arc::clone(&context);
+    let service = tokio::spawn(async move { dbus_service::serve_on(&server, shared).await });
+
+    let client = bus.connect().await;
+    let bus_proxy = fdo::DBusProxy::new(&client).await.unwrap();
+    for _ in 0..50 {
+        if bus_proxy
+            .name_has_owner(BUS_NAME.try_into().unwrap())
+            .await
+            .unwrap()
+        {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let idler = IdlerProxy::builder(&client)
+        .path(OBJECT_PATH)
+        .unwrap()
+        .cache_properties(proxy::CacheProperties::No)
+        .build()
+        .await
+        .unwrap();
+
+    idler.stop().await.unwrap();
+    assert!(!idler.active().await.unwrap());
+    assert_eq!(idler.next_injection().await.unwrap(), 0);
+    idler.start().await.unwrap();
+    assert!(idler.active().await.unwrap());
+    assert!(idler.idle_seconds().await.unwrap() >= -1);
+
+    // Pausing is announced without anyone reading the properties.
+    let properties = fdo::PropertiesProxy::builder(&client)
+        .destination(BUS_NAME)
+        .unwrap()
+        .path(OBJECT_PATH)
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    let mut changes = properties.receive_properties_changed().await.unwrap();
+    idler.pause(60).await.unwrap();
+    // Each property comes in its own signal.
+    let active = tokio::time::timeout(Duration::from_secs(5), async {
+        while let Some(changed) = changes.next().await {
+            let args = changed.args().unwrap();
+            assert_eq!(args.interface_name.as_str(), INTERFACE);
+            if let Some(active) = args.changed_properties.get("Active") {
+                return bool::try_from(active).unwrap();
+            }
+        }
+        panic!("The bus closed");
+    })
+    .await
+    .expect("PropertiesChanged of Active after Pause");
+    assert!(!active);
+    assert!(!idler.active().await.unwrap());
+    assert!(is_invalid_args(&idler.pause(0).await.unwrap_err()));
+
+    idler.set_interval(120).await.unwrap();
+    let interval = context.settings().get(RegistryEntries::ForceInterval);
+    assert_eq!(interval.lock().unwrap().last_data, "120");
+    assert!(is_invalid_args(&idler.set_interval(0).await.unwrap_err()));
+
+    idler.schedule_stop("18:00").await.unwrap();
+    assert!(context.scheduler().query().await.unwrap().is_active());
+    idler.schedule_stop("STOP").await.unwrap();
+    assert!(!context.scheduler().query().await.unwrap().is_active());
+    assert!(is_invalid_args(
+        &idler.schedule_stop("25:00").await.unwrap_err()
+    ));
+
+    service.abort();
+    context.shutdown();
+}
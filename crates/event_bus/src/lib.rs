@@ -0,0 +1,257 @@
+use std::fmt;
+use tracing::{trace, warn};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+const CAPACITY: usize = 256;
+
+/// How the idle loop got the idle timer reset.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Strategy {
+    Mouse,
+    Keyboard,
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Strategy::Mouse => write!(f, "Mouse"),
+            Strategy::Keyboard => write!(f, "Keyboard"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum PowerEventKind {
+    DisplayOff,
+}
+
+/// What the idle service is currently doing.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IdlerMode {
+    Active,
+    Paused,
+    TimedPause,
+    TimedKeepAwake,
+}
+
+/// Everything components tell each other about. Serialized with a `type` tag for the UI and
+/// the control socket.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum Event {
+    InputInjected {
+        strategy: Strategy,
+    },
+    /// An injection did not reset the idle time, which was `idle_seconds` before it.
+    IdleResetFailed {
+        idle_seconds: u64,
+    },
+    PowerEvent {
+        kind: PowerEventKind,
+    },
+    /// A setting was saved. `setting` is the registry value name.
+    SettingChanged {
+        setting: String,
+        value: String,
+    },
+    /// The scheduled shutdown time `time` (`%H:%M`) was reached.
+    ScheduleFired {
+        time: String,
+    },
+    /// The scheduled shutdown at `time` (`%H:%M`) is `minutes` away.
+    StopWarning {
+        time: String,
+        minutes: u32,
+    },
+    /// A window of the weekly schedule opened or closed, or the rules changed which one the
+    /// current time is in.
+    WindowChanged {
+        open: bool,
+    },
+    /// The execution state asking the system and display to stay on is held.
+    AssertionAcquired,
+    AssertionReleased,
+    /// The user came back after being away for `idle_seconds`.
+    UserReturned {
+        idle_seconds: u64,
+    },
+    /// The idle service was paused or resumed, or a timer started or ended.
+    ModeChanged {
+        mode: IdlerMode,
+    },
+    /// A client of the control socket asked the app to exit.
+    ExitRequested,
+    /// A second launch or a control client asked to show the window.
+    ShowRequested,
+}
+
+impl Event {
+    pub fn setting_changed(setting: impl fmt::Display, value: impl Into<String>) -> Event {
+        Event::SettingChanged {
+            setting: setting.to_string(),
+            value: value.into(),
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::InputInjected { strategy } => write!(f, "Injected {strategy} input"),
+            Event::IdleResetFailed { idle_seconds } => {
+                write!(f, "Failed to reset idle time of {idle_seconds}s")
+            }
+            Event::PowerEvent { kind } => write!(f, "Power event: {kind:?}"),
+            Event::SettingChanged { setting, value } => write!(f, "{setting} set to {value:?}"),
+            Event::ScheduleFired { time } => write!(f, "Scheduled shutdown at {time} reached"),
+            Event::StopWarning { time, minutes } => {
+                write!(f, "Scheduled shutdown at {time} in {minutes} min")
+            }
+            Event::WindowChanged { open: true } => write!(f, "Weekly schedule window opened"),
+            Event::WindowChanged { open: false } => write!(f, "Weekly schedule window closed"),
+            Event::AssertionAcquired => write!(f, "Execution state acquired"),
+            Event::AssertionReleased => write!(f, "Execution state released"),
+            Event::UserReturned { idle_seconds } => {
+                write!(f, "User returned after {idle_seconds}s")
+            }
+            Event::ModeChanged { mode } => write!(f, "Mode changed to {mode:?}"),
+            Event::ExitRequested => write!(f, "Exit requested"),
+            Event::ShowRequested => write!(f, "Window requested"),
+        }
+    }
+}
+
+/// In-process broadcast channel. Cloning the bus is cheap and every clone publishes to the
+/// same subscribers.
+#[derive(Clone, Debug)]
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus::new()
+    }
+}
+
+impl EventBus {
+    #[must_use]
+    pub fn new() -> EventBus {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        EventBus { sender }
+    }
+
+    /// Sends `event` to every current subscriber.
+    pub fn publish(&self, event: Event) {
+        trace!("Publishing event: {event:?}");
+        // Having no subscribers is not an error, the event is simply dropped.
+        let _ = self.sender.send(event);
+    }
+
+    /// Subscribes to the events published from now on. `name` identifies the subscriber in
+    /// the logs.
+    #[must_use]
+    pub fn subscribe(&self, name: &'static str) -> Subscription {
+        Subscription {
+            name,
+            receiver: self.sender.subscribe(),
+        }
+    }
+}
+
+pub struct Subscription {
+    name: &'static str,
+    receiver: broadcast::Receiver<Event>,
+}
+
+impl Subscription {
+    /// Waits for the next event. Returns `None` once every publisher is gone.
+    ///
+    /// Events missed because the subscriber fell behind are skipped with a warning.
+    pub async fn next(&mut self) -> Option<Event> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) => return Some(event),
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("{} subscriber skipped {skipped} events", self.name);
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An event numbered `n`, to tell them apart.
+    fn numbered(n: u64) -> Event {
+        Event::UserReturned { idle_seconds: n }
+    }
+
+    async fn receive(subscription: &mut Subscription, count: u64) -> Vec<Event> {
+        let mut events = Vec::new();
+        for _ in 0..count {
+            events.push(subscription.next().await.unwrap());
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn delivers_in_order_to_every_subscriber() {
+        let bus = EventBus::new();
+        bus.publish(numbered(0));
+        let mut first = bus.subscribe("first");
+        let mut second = bus.clone().subscribe("second");
+
+        for n in 1..=3 {
+            bus.publish(numbered(n));
+        }
+        let published: Vec<_> = (1..=3).map(numbered).collect();
+        assert_eq!(receive(&mut first, 3).await, published);
+        assert_eq!(receive(&mut second, 3).await, published);
+
+        // Clones publish to the same subscribers, whose subscriptions end with the last clone.
+        let clone = bus.clone();
+        drop(bus);
+        clone.publish(numbered(4));
+        drop(clone);
+        assert_eq!(first.next().await, Some(numbered(4)));
+        assert_eq!(first.next().await, None);
+    }
+
+    #[tokio::test]
+    async fn skips_the_events_a_lagging_subscriber_missed() {
+        let bus = EventBus::new();
+        let mut slow = bus.subscribe("slow");
+        let mut fast = bus.subscribe("fast");
+
+        let total = CAPACITY as u64 + 10;
+        let mut received = Vec::new();
+        for n in 0..total {
+            bus.publish(numbered(n));
+            received.push(fast.next().await.unwrap());
+        }
+        assert_eq!(received, (0..total).map(numbered).collect::<Vec<_>>());
+
+        // Only the last `CAPACITY` events are kept, the slow subscriber goes on from the oldest.
+        let kept = receive(&mut slow, CAPACITY as u64).await;
+        assert_eq!(kept, (10..total).map(numbered).collect::<Vec<_>>());
+        bus.publish(numbered(total));
+        assert_eq!(slow.next().await, Some(numbered(total)));
+    }
+
+    #[test]
+    fn serializes_with_a_type_tag() {
+        let event = Event::ModeChanged {
+            mode: IdlerMode::TimedPause,
+        };
+        let json = serde_json::to_string(&event).unwrap();
+        assert_eq!(json, r#"{"type":"ModeChanged","mode":"timed_pause"}"#);
+        assert_eq!(serde_json::from_str::<Event>(&json).unwrap(), event);
+    }
+}
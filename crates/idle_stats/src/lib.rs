@@ -0,0 +1,466 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex, OnceLock,
+        atomic::{AtomicBool, AtomicU32, Ordering},
+    },
+    time::{Duration, Instant},
+};
+use tracing::{debug, error, info, trace, warn};
+
+use anyhow::Result;
+use chrono::{DateTime, Local, TimeDelta};
+use serde::{Deserialize, Serialize};
+
+pub use event_bus::Strategy;
+use event_bus::{Event, EventBus};
+
+const STATISTICS_FILE: &str = "statistics.json";
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const HOUR_FORMAT: &str = "%Y-%m-%d %H:00";
+const DAY_FORMAT: &str = "%Y-%m-%d";
+
+pub const DEFAULT_RETENTION_DAYS: u32 = 30;
+
+static ENGINE: OnceLock<StatsEngine> = OnceLock::new();
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatEvent {
+    Injection(Strategy),
+    ResetFailed,
+    PowerEvent,
+    KeepAwake(Duration),
+    UserIdle(Duration),
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Counters {
+    pub injections: BTreeMap<Strategy, u64>,
+    pub failed_resets: u64,
+    pub power_events: u64,
+    pub keep_awake_seconds: u64,
+    pub user_idle_periods: u64,
+    pub user_idle_seconds: u64,
+}
+
+impl Counters {
+    fn record(&mut self, event: StatEvent) {
+        match event {
+            StatEvent::Injection(strategy) => {
+                *self.injections.entry(strategy).or_default() += 1;
+            }
+            StatEvent::ResetFailed => self.failed_resets += 1,
+            StatEvent::PowerEvent => self.power_events += 1,
+            StatEvent::KeepAwake(duration) => {
+                self.keep_awake_seconds += duration.as_secs();
+            }
+            StatEvent::UserIdle(duration) => {
+                self.user_idle_periods += 1;
+                self.user_idle_seconds += duration.as_secs();
+            }
+        }
+    }
+}
+
+/// Counters aggregated per hour (`%Y-%m-%d %H:00`) and per day (`%Y-%m-%d`), local time.
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct Statistics {
+    pub hourly: BTreeMap<String, Counters>,
+    pub daily: BTreeMap<String, Counters>,
+}
+
+impl Statistics {
+    pub fn record(&mut self, at: DateTime<Local>, event: StatEvent) {
+        self.hourly
+            .entry(at.format(HOUR_FORMAT).to_string())
+            .or_default()
+            .record(event);
+        self.daily
+            .entry(at.format(DAY_FORMAT).to_string())
+            .or_default()
+            .record(event);
+    }
+
+    /// Drops every bucket older than `retention_days` days before `now`.
+    pub fn prune(&mut self, now: DateTime<Local>, retention_days: u32) {
+        let Some(oldest) = now.checked_sub_signed(TimeDelta::days(i64::from(retention_days)))
+        else {
+            error!("Failed to compute oldest statistics day for {retention_days} days");
+            return;
+        };
+        let oldest_hour = oldest.format(HOUR_FORMAT).to_string();
+        let oldest_day = oldest.format(DAY_FORMAT).to_string();
+        self.hourly = self.hourly.split_off(&oldest_hour);
+        self.daily = self.daily.split_off(&oldest_day);
+    }
+
+    /// Loads the statistics stored at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or parsed.
+    pub fn load(path: &Path) -> Result<Statistics> {
+        let data = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    /// Writes the statistics to `path`, replacing the previous file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file or its directory can't be written.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+}
+
+struct StatsEngine {
+    path: PathBuf,
+    enabled: AtomicBool,
+    retention_days: AtomicU32,
+    dirty: AtomicBool,
+    statistics: Mutex<Statistics>,
+    /// Start of the keep-awake time not yet recorded.
+    awake_since: Mutex<Option<Instant>>,
+}
+
+impl StatsEngine {
+    /// Records the keep-awake time since the last call and restarts counting if `still_awake`.
+    fn record_keep_awake(&self, still_awake: bool) {
+        let since = match self.awake_since.lock() {
+            Ok(mut awake_since) => {
+                let since = awake_since.take();
+                if still_awake {
+                    *awake_since = Some(Instant::now());
+                }
+                since
+            }
+            Err(err) => {
+                error!("Failed to lock keep-awake start, err: {err}");
+                return;
+            }
+        };
+        if let Some(since) = since {
+            record(StatEvent::KeepAwake(since.elapsed()));
+        }
+    }
+
+    fn handle(&self, event: &Event) {
+        match event {
+            Event::InputInjected { strategy } => record(StatEvent::Injection(*strategy)),
+            Event::IdleResetFailed { .. } => record(StatEvent::ResetFailed),
+            Event::PowerEvent { .. } => record(StatEvent::PowerEvent),
+            Event::UserReturned { idle_seconds } => {
+                record(StatEvent::UserIdle(Duration::from_secs(*idle_seconds)));
+            }
+            Event::AssertionAcquired => self.record_keep_awake(true),
+            Event::AssertionReleased => self.record_keep_awake(false),
+            Event::SettingChanged { setting, value } => match setting.as_str() {
+                "LogStatistics" => set_enabled(value == "Enabled"),
+                "StatisticsRetention" => match value.parse() {
+                    Ok(days) => set_retention(days),
+                    Err(err) => warn!("Found invalid statistics retention {value:?}, err: {err}"),
+                },
+                _ => {}
+            },
+            Event::ScheduleFired { .. }
+            | Event::StopWarning { .. }
+            | Event::WindowChanged { .. }
+            | Event::ModeChanged { .. }
+            | Event::ExitRequested
+            | Event::ShowRequested => {}
+        }
+    }
+
+    fn flush(&self) {
+        let still_awake = self.awake_since.lock().is_ok_and(|since| since.is_some());
+        self.record_keep_awake(still_awake);
+
+        if !self.dirty.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        let status = match self.statistics.lock() {
+            Ok(mut statistics) => {
+                statistics.prune(Local::now(), self.retention_days.load(Ordering::SeqCst));
+                statistics.save(&self.path)
+            }
+            Err(err) => {
+                error!("Failed to lock statistics, err: {err}");
+                return;
+            }
+        };
+        match status {
+            Ok(()) => debug!("Saved statistics to {:?}", self.path),
+            Err(err) => {
+                error!("Failed to save statistics to {:?}, err: {err}", self.path);
+                self.dirty.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+/// Default location of the statistics file, under the local app data folder.
+#[cfg(windows)]
+#[must_use]
+pub fn default_path() -> PathBuf {
+    env::var_os("LOCALAPPDATA")
+        .map_or_else(env::temp_dir, PathBuf::from)
+        .join("SmartIdler")
+        .join(STATISTICS_FILE)
+}
+
+/// Default location of the statistics file, under `$XDG_DATA_HOME` or `~/.local/share`.
+#[cfg(not(windows))]
+#[must_use]
+pub fn default_path() -> PathBuf {
+    env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .unwrap_or_else(env::temp_dir)
+        .join("smart-idler")
+        .join(STATISTICS_FILE)
+}
+
+/// Loads the stored statistics and starts accepting events. Only the first call has an effect.
+pub fn init(path: PathBuf, enabled: bool, retention_days: u32) {
+    let statistics = match Statistics::load(&path) {
+        Ok(statistics) => statistics,
+        Err(err) => {
+            info!("No statistics loaded from {path:?}, err: {err}");
+            Statistics::default()
+        }
+    };
+    let engine = StatsEngine {
+        path,
+        enabled: AtomicBool::new(enabled),
+        retention_days: AtomicU32::new(retention_days),
+        dirty: AtomicBool::new(false),
+        statistics: Mutex::new(statistics),
+        awake_since: Mutex::new(None),
+    };
+    if ENGINE.set(engine).is_err() {
+        error!("Statistics were already initialized");
+    }
+}
+
+/// Records the events published on `events` and saves the statistics periodically, on the
+/// current tokio runtime.
+///
+/// # Panics
+///
+/// Panics if called outside of a tokio runtime.
+pub fn spawn_tasks(events: &EventBus) {
+    let mut subscription = events.subscribe("statistics");
+    tokio::spawn(async move {
+        while let Some(event) = subscription.next().await {
+            if let Some(engine) = ENGINE.get() {
+                engine.handle(&event);
+            }
+        }
+    });
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            interval.tick().await;
+            flush();
+        }
+    });
+}
+
+/// Records `event` for the current hour and day. Does nothing while statistics are disabled.
+fn record(event: StatEvent) {
+    let Some(engine) = ENGINE.get() else {
+        return;
+    };
+    if !engine.enabled.load(Ordering::SeqCst) {
+        return;
+    }
+    trace!("Recording statistics event: {event:?}");
+    match engine.statistics.lock() {
+        Ok(mut statistics) => statistics.record(Local::now(), event),
+        Err(err) => {
+            error!("Failed to lock statistics, err: {err}");
+            return;
+        }
+    }
+    engine.dirty.store(true, Ordering::SeqCst);
+}
+
+fn set_enabled(enabled: bool) {
+    if let Some(engine) = ENGINE.get() {
+        info!("Statistics enabled: {enabled}");
+        engine.enabled.store(enabled, Ordering::SeqCst);
+    }
+}
+
+/// Changes how many days of statistics are kept. Older buckets are dropped on the next save.
+fn set_retention(days: u32) {
+    if let Some(engine) = ENGINE.get() {
+        info!("Statistics retention: {days} days");
+        engine.retention_days.store(days, Ordering::SeqCst);
+        engine.dirty.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Writes pending statistics to disk, including the keep-awake time up to now.
+pub fn flush() {
+    if let Some(engine) = ENGINE.get() {
+        engine.flush();
+    }
+}
+
+/// Returns a copy of the recorded statistics.
+#[must_use]
+pub fn snapshot() -> Statistics {
+    let Some(engine) = ENGINE.get() else {
+        return Statistics::default();
+    };
+    match engine.statistics.lock() {
+        Ok(statistics) => statistics.clone(),
+        Err(err) => {
+            error!("Failed to lock statistics, err: {err}");
+            Statistics::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2026, 10, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn injections(counters: &Counters, strategy: Strategy) -> u64 {
+        counters
+            .injections
+            .get(&strategy)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn accumulates_per_hour_and_day() {
+        let mut statistics = Statistics::default();
+        statistics.record(at(19, 9, 10), StatEvent::Injection(Strategy::Mouse));
+        statistics.record(at(19, 9, 50), StatEvent::Injection(Strategy::Mouse));
+        statistics.record(at(19, 10, 5), StatEvent::Injection(Strategy::Keyboard));
+        statistics.record(at(19, 10, 6), StatEvent::KeepAwake(Duration::from_secs(90)));
+        statistics.record(at(19, 10, 7), StatEvent::UserIdle(Duration::from_secs(300)));
+        statistics.record(at(19, 10, 8), StatEvent::UserIdle(Duration::from_secs(60)));
+        statistics.record(at(20, 8, 0), StatEvent::ResetFailed);
+        statistics.record(at(20, 8, 1), StatEvent::PowerEvent);
+
+        assert_eq!(
+            statistics.hourly.keys().collect::<Vec<_>>(),
+            ["2026-10-19 09:00", "2026-10-19 10:00", "2026-10-20 08:00"]
+        );
+        let nine = &statistics.hourly["2026-10-19 09:00"];
+        assert_eq!(injections(nine, Strategy::Mouse), 2);
+        assert_eq!(injections(nine, Strategy::Keyboard), 0);
+
+        let day = &statistics.daily["2026-10-19"];
+        assert_eq!(injections(day, Strategy::Mouse), 2);
+        assert_eq!(injections(day, Strategy::Keyboard), 1);
+        assert_eq!(day.keep_awake_seconds, 90);
+        assert_eq!(day.user_idle_periods, 2);
+        assert_eq!(day.user_idle_seconds, 360);
+        assert_eq!(day.failed_resets, 0);
+
+        let next_day = &statistics.daily["2026-10-20"];
+        assert_eq!(next_day.failed_resets, 1);
+        assert_eq!(next_day.power_events, 1);
+    }
+
+    #[test]
+    fn prunes_buckets_past_the_retention() {
+        let mut statistics = Statistics::default();
+        for day in [1, 10, 19] {
+            statistics.record(at(day, 12, 0), StatEvent::PowerEvent);
+        }
+
+        statistics.prune(at(19, 13, 0), 9);
+        assert_eq!(
+            statistics.daily.keys().collect::<Vec<_>>(),
+            ["2026-10-10", "2026-10-19"]
+        );
+        // Hours are cut to the hour: 12:00 on the 10th is older than 13:00 nine days later.
+        assert_eq!(
+            statistics.hourly.keys().collect::<Vec<_>>(),
+            ["2026-10-19 12:00"]
+        );
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("smart-idler").join(STATISTICS_FILE);
+        assert!(Statistics::load(&path).is_err());
+
+        let mut statistics = Statistics::default();
+        statistics.record(at(19, 9, 0), StatEvent::Injection(Strategy::Keyboard));
+        statistics.record(at(19, 9, 1), StatEvent::KeepAwake(Duration::from_secs(30)));
+        statistics.save(&path).unwrap();
+        statistics.record(at(19, 9, 2), StatEvent::PowerEvent);
+        statistics.save(&path).unwrap();
+
+        let loaded = Statistics::load(&path).unwrap();
+        assert_eq!(loaded.hourly, statistics.hourly);
+        assert_eq!(loaded.daily, statistics.daily);
+        assert!(!path.with_extension("json.tmp").exists());
+
+        fs::write(&path, "{").unwrap();
+        assert!(Statistics::load(&path).is_err());
+    }
+
+    /// The only test using the engine, which can be initialized once per process.
+    #[tokio::test]
+    async fn records_and_flushes_bus_events() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join(STATISTICS_FILE);
+        let engine = || ENGINE.get().unwrap();
+        init(path.clone(), true, DEFAULT_RETENTION_DAYS);
+
+        engine().handle(&Event::InputInjected {
+            strategy: Strategy::Mouse,
+        });
+        engine().handle(&Event::UserReturned { idle_seconds: 120 });
+        engine().handle(&Event::AssertionAcquired);
+        flush();
+        let today = Local::now().format(DAY_FORMAT).to_string();
+        let saved = Statistics::load(&path).unwrap();
+        let day = &saved.daily[&today];
+        assert_eq!(injections(day, Strategy::Mouse), 1);
+        assert_eq!(day.user_idle_seconds, 120);
+
+        // Disabled, nothing is counted nor saved.
+        engine().handle(&Event::AssertionReleased);
+        engine().handle(&Event::setting_changed("LogStatistics", "Disabled"));
+        engine().handle(&Event::InputInjected {
+            strategy: Strategy::Mouse,
+        });
+        flush();
+        assert_eq!(injections(&snapshot().daily[&today], Strategy::Mouse), 1);
+
+        engine().handle(&Event::setting_changed("LogStatistics", "Enabled"));
+        engine().handle(&Event::InputInjected {
+            strategy: Strategy::Keyboard,
+        });
+        flush();
+        let saved = Statistics::load(&path).unwrap();
+        assert_eq!(injections(&saved.daily[&today], Strategy::Keyboard), 1);
+    }
+}
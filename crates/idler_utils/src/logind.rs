@@ -0,0 +1,233 @@
+use std::sync::Arc;
+use tracing::{debug, error, info};
+
+use anyhow::{Result, anyhow};
+use tokio::sync::mpsc;
+use zbus::{Connection, Proxy, zvariant::OwnedFd};
+
+use event_bus::{Event, EventBus};
+use registry_ops::SettingsStore;
+
+use crate::{InputType, systemd};
+
+const LOGIND_SERVICE: &str = "org.freedesktop.login1";
+const LOGIND_PATH: &str = "/org/freedesktop/login1";
+const LOGIND_MANAGER: &str = "org.freedesktop.login1.Manager";
+const LOGIND_SESSION: &str = "org.freedesktop.login1.Session";
+/// The session of the caller, or the graphical session of the user when the caller runs
+/// outside of one, e.g. as a user service.
+const AUTO_SESSION_PATH: &str = "/org/freedesktop/login1/session/auto";
+
+/// There is no execution state to set outside of Windows, the inhibitor lock held by the
+/// power window keeps the system awake instead.
+#[non_exhaustive]
+pub struct ExecState;
+
+impl ExecState {
+    #[inline]
+    pub fn start() {}
+
+    #[inline]
+    pub fn stop() {}
+
+    pub fn user_present() {}
+}
+
+/// Input can't be injected on headless hosts, the inhibitor lock is enough to stay awake.
+pub(crate) fn send_input(input_type: InputType) -> Result<()> {
+    debug!("Skipping {input_type:?} input, not supported on this platform");
+    Err(anyhow!("Input injection is not supported on this platform"))
+}
+
+/// Seconds since the last user input. Not tracked outside of Windows, so input is never
+/// injected there.
+#[must_use]
+pub fn get_last_input() -> Option<u64> {
+    None
+}
+
+/// Holds a logind `sleep:idle` inhibitor lock while the idle service keeps the system awake.
+///
+/// The lock is taken and released by an async task, the same way the Windows power window
+/// thread owns the execution state.
+pub(crate) struct PowerWindow {
+    keep_awake: mpsc::UnboundedSender<bool>,
+}
+
+impl PowerWindow {
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub(crate) fn spawn(
+        keep_awake: bool,
+        _settings: Arc<SettingsStore>,
+        events: EventBus,
+    ) -> PowerWindow {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let _ = tx.send(keep_awake);
+        tokio::spawn(async move {
+            let mut inhibitor: Option<OwnedFd> = None;
+            let mut ready = false;
+            while let Some(keep_awake) = rx.recv().await {
+                if keep_awake && inhibitor.is_none() {
+                    match inhibit().await {
+                        Ok(fd) => {
+                            info!("Acquired inhibitor lock");
+                            inhibitor = Some(fd);
+                            events.publish(Event::AssertionAcquired);
+                        }
+                        Err(err) => {
+                            error!("Failed to take inhibitor lock, err: {err}");
+                            systemd::notify(&format!(
+                                "STATUS=Not keeping the system awake, failed to take the inhibitor lock: {err}"
+                            ));
+                        }
+                    }
+                } else if !keep_awake && inhibitor.take().is_some() {
+                    info!("Released inhibitor lock");
+                    events.publish(Event::AssertionReleased);
+                }
+                // The service is only up once the initial keep-awake state was applied.
+                if !ready {
+                    ready = true;
+                    systemd::notify("READY=1");
+                }
+            }
+            if inhibitor.take().is_some() {
+                events.publish(Event::AssertionReleased);
+            }
+            info!("Inhibitor task stopped");
+        });
+        PowerWindow { keep_awake: tx }
+    }
+
+    pub(crate) fn set_keep_awake(&self, keep_awake: bool) {
+        if let Err(err) = self.keep_awake.send(keep_awake) {
+            error!("Failed to send keep awake state, err: {err}");
+        }
+    }
+
+    /// Releases the inhibitor lock, if held, and stops the task.
+    pub(crate) fn close(self) {
+        self.set_keep_awake(false);
+    }
+}
+
+async fn inhibit() -> zbus::Result<OwnedFd> {
+    let connection = Connection::system().await?;
+    let manager = Proxy::new(&connection, LOGIND_SERVICE, LOGIND_PATH, LOGIND_MANAGER).await?;
+    manager
+        .call(
+            "Inhibit",
+            &(
+                "sleep:idle",
+                "Smart Idler",
+                "Keeping the system awake",
+                "block",
+            ),
+        )
+        .await
+}
+
+/// The power actions of logind, reached on the system bus or on any connection serving
+/// `org.freedesktop.login1`, e.g. a private bus with a mock service in tests.
+pub struct LoginManager {
+    connection: Connection,
+}
+
+impl LoginManager {
+    /// # Errors
+    ///
+    /// Returns an error if the system bus can't be reached.
+    pub async fn system() -> zbus::Result<LoginManager> {
+        Ok(LoginManager::new(Connection::system().await?))
+    }
+
+    #[must_use]
+    pub fn new(connection: Connection) -> LoginManager {
+        LoginManager { connection }
+    }
+
+    async fn manager(&self) -> zbus::Result<Proxy<'_>> {
+        Proxy::new(
+            &self.connection,
+            LOGIND_SERVICE,
+            LOGIND_PATH,
+            LOGIND_MANAGER,
+        )
+        .await
+    }
+
+    /// Suspends the system without asking for authentication.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if logind refuses, e.g. while another program blocks sleep.
+    pub async fn suspend(&self) -> zbus::Result<()> {
+        self.manager().await?.call("Suspend", &(false,)).await
+    }
+
+    /// Hibernates the system without asking for authentication.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if logind refuses, e.g. when no swap is set up for hibernation.
+    pub async fn hibernate(&self) -> zbus::Result<()> {
+        self.manager().await?.call("Hibernate", &(false,)).await
+    }
+
+    /// Locks the session of the user.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the user has no session logind can lock.
+    pub async fn lock_session(&self) -> zbus::Result<()> {
+        let session = Proxy::new(
+            &self.connection,
+            LOGIND_SERVICE,
+            AUTO_SESSION_PATH,
+            LOGIND_SESSION,
+        )
+        .await?;
+        session.call("Lock", &()).await
+    }
+}
+
+/// Locks the session through logind.
+///
+/// # Errors
+///
+/// Returns an error if logind can't be reached or refuses.
+pub async fn lock_session() -> Result<()> {
+    let manager = LoginManager::system().await?;
+    manager
+        .lock_session()
+        .await
+        .map_err(|err| anyhow!("Failed to lock the session, err: {err}"))
+}
+
+/// Suspends the system through logind.
+///
+/// # Errors
+///
+/// Returns an error if logind can't be reached or refuses.
+pub async fn suspend() -> Result<()> {
+    let manager = LoginManager::system().await?;
+    manager
+        .suspend()
+        .await
+        .map_err(|err| anyhow!("Failed to suspend, err: {err}"))
+}
+
+/// Hibernates the system through logind.
+///
+/// # Errors
+///
+/// Returns an error if logind can't be reached or refuses.
+pub async fn hibernate() -> Result<()> {
+    let manager = LoginManager::system().await?;
+    manager
+        .hibernate()
+        .await
+        .map_err(|err| anyhow!("Failed to hibernate, err: {err}"))
+}
@@ -0,0 +1,470 @@
+use std::{
+    sync::{Arc, Mutex, mpsc},
+    time::Duration,
+};
+use tracing::{debug, error, info};
+
+use anyhow::{Result, anyhow};
+use event_bus::{Event, EventBus, IdlerMode, Subscription};
+use registry_ops::{RegistryEntries, SettingsStore};
+use tokio::{
+    sync::{mpsc as tokio_mpsc, watch},
+    time::{Instant, Interval, MissedTickBehavior, sleep_until},
+};
+
+use crate::{
+    ExecState, InputType, PowerWindow, get_last_input, send_mixed_input, systemd,
+    timer::{TimedMode, Timer},
+};
+
+const STARTUP_DELAY: Duration = Duration::from_secs(10);
+const RESET_CHECK_DELAY: Duration = Duration::from_secs(10);
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+const MINIMUM_INTERVAL: u64 = 60;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct ServiceState {
+    paused: bool,
+    timer: Option<Timer>,
+    /// Idle seconds after which input is injected.
+    threshold: u64,
+}
+
+impl ServiceState {
+    fn mode(&self) -> IdlerMode {
+        match (self.paused, self.timer) {
+            (true, Some(_)) => IdlerMode::TimedPause,
+            (true, None) => IdlerMode::Paused,
+            (false, Some(_)) => IdlerMode::TimedKeepAwake,
+            (false, None) => IdlerMode::Active,
+        }
+    }
+
+    /// One line for `systemctl status`.
+    fn describe(&self) -> String {
+        let until = |timer: Timer| timer.deadline.format("%H:%M");
+        match (self.paused, self.timer) {
+            (true, Some(timer)) => format!("Paused until {}", until(timer)),
+            (true, None) => "Paused".to_string(),
+            (false, Some(timer)) => format!("Keeping the system awake until {}", until(timer)),
+            (false, None) => "Keeping the system awake".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum IdleCommand {
+    Pause,
+    Resume,
+    StartTimer(Timer),
+    CancelTimer,
+    Shutdown,
+}
+
+/// Handle to the async idle task and the power notification window.
+///
+/// Commands are delivered over a channel, so the idle task reacts to them right away instead
+/// of at its next wake up. Timed pauses and timed keep-awake sessions are persisted, so a
+/// running timer survives an app restart. The force interval is reloaded whenever a
+/// `SettingChanged` event for it is published.
+///
+/// Under systemd, the idle task reports readiness and its mode to `$NOTIFY_SOCKET` and pings
+/// the watchdog, so a stuck loop gets the service restarted.
+pub struct IdleService {
+    commands: tokio_mpsc::UnboundedSender<IdleCommand>,
+    state: watch::Receiver<ServiceState>,
+    stopped: Mutex<Option<mpsc::Receiver<()>>>,
+}
+
+impl IdleService {
+    /// Spawns the idle task on the current tokio runtime and opens the power notification window.
+    /// The force interval and the timer are read from `settings`. Injections, failed resets and
+    /// execution state changes are published on `events`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    #[must_use]
+    pub fn start(settings: Arc<SettingsStore>, events: &EventBus) -> IdleService {
+        let max_idle = load_force_interval(&settings);
+        let mut initial_state = ServiceState {
+            threshold: injection_threshold(max_idle),
+            ..ServiceState::default()
+        };
+        match Timer::load(&settings) {
+            Some(timer) if !timer.is_expired() => {
+                info!("Restoring {} timer until {}", timer.mode, timer.deadline);
+                initial_state.paused = timer.mode == TimedMode::Pause;
+                initial_state.timer = Some(timer);
+            }
+            Some(_) => Timer::store(&settings, None),
+            None => {}
+        }
+
+        let (commands, command_rx) = tokio_mpsc::unbounded_channel();
+        let (state_tx, state) = watch::channel(initial_state);
+        let (stopped_tx, stopped) = mpsc::channel();
+
+        let idle_task = IdleTask {
+            commands: command_rx,
+            subscription: events.subscribe("idle service"),
+            events: events.clone(),
+            state: state_tx,
+            window: PowerWindow::spawn(
+                !initial_state.paused,
+                Arc::clone(&settings),
+                events.clone(),
+            ),
+            settings,
+            max_idle,
+            pending_reset: None,
+            idle_period: IdlePeriod::default(),
+            watchdog: systemd::watchdog_interval().map(|period| {
+                let mut watchdog = tokio::time::interval(period);
+                watchdog.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                watchdog
+            }),
+        };
+        tokio::spawn(async move {
+            idle_task.run().await;
+            let _ = stopped_tx.send(());
+        });
+
+        IdleService {
+            commands,
+            state,
+            stopped: Mutex::new(Some(stopped)),
+        }
+    }
+
+    /// Pauses the idle loop and releases the execution state. Cancels any running timer.
+    pub fn pause(&self) {
+        self.send(IdleCommand::Pause);
+    }
+
+    /// Resumes a paused idle loop. Cancels any running timer.
+    pub fn resume(&self) {
+        self.send(IdleCommand::Resume);
+    }
+
+    /// Pauses the idle loop and resumes it once `duration` has elapsed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the deadline can't be computed.
+    pub fn pause_for(&self, duration: Duration) -> Result<()> {
+        self.send(IdleCommand::StartTimer(Timer::new(
+            TimedMode::Pause,
+            duration,
+        )?));
+        Ok(())
+    }
+
+    /// Keeps the system awake for `duration`, then pauses the idle loop.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the deadline can't be computed.
+    pub fn keep_awake_for(&self, duration: Duration) -> Result<()> {
+        self.send(IdleCommand::StartTimer(Timer::new(
+            TimedMode::KeepAwake,
+            duration,
+        )?));
+        Ok(())
+    }
+
+    /// Cancels the running timer and goes back to keeping the system awake.
+    pub fn cancel_timer(&self) {
+        self.send(IdleCommand::CancelTimer);
+    }
+
+    #[must_use]
+    pub fn timer(&self) -> Option<Timer> {
+        self.state.borrow().timer
+    }
+
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.state.borrow().paused
+    }
+
+    #[must_use]
+    pub fn mode(&self) -> IdlerMode {
+        self.state.borrow().mode()
+    }
+
+    /// Time left before input is injected if the user stays idle, `None` while paused.
+    #[must_use]
+    pub fn until_injection(&self) -> Option<Duration> {
+        let state = *self.state.borrow();
+        if state.paused {
+            return None;
+        }
+        let idle_time = get_last_input()?;
+        Some(Duration::from_secs(
+            state.threshold.saturating_sub(idle_time),
+        ))
+    }
+
+    /// Stops the idle task, closes the power window and waits for both to exit.
+    /// Calling it more than once is a no-op.
+    ///
+    /// Blocks the calling thread, so it must not be called from a current-thread runtime.
+    pub fn shutdown(&self) {
+        let stopped = match self.stopped.lock() {
+            Ok(mut stopped) => stopped.take(),
+            Err(err) => {
+                error!("Failed to lock idle service, err: {err}");
+                return;
+            }
+        };
+        let Some(stopped) = stopped else {
+            debug!("Idle service already stopped");
+            return;
+        };
+        info!("Shutting down idle service");
+        self.send(IdleCommand::Shutdown);
+        match stopped.recv_timeout(SHUTDOWN_TIMEOUT) {
+            Ok(()) | Err(mpsc::RecvTimeoutError::Disconnected) => info!("Idle service stopped"),
+            Err(err) => error!("Idle service did not stop in time, err: {err}"),
+        }
+    }
+
+    fn send(&self, command: IdleCommand) {
+        debug!("Sending idle command: {command:?}");
+        if let Err(err) = self.commands.send(command) {
+            error!("Failed to send idle command, err: {err}");
+        }
+    }
+}
+
+struct IdleTask {
+    commands: tokio_mpsc::UnboundedReceiver<IdleCommand>,
+    subscription: Subscription,
+    settings: Arc<SettingsStore>,
+    events: EventBus,
+    state: watch::Sender<ServiceState>,
+    window: PowerWindow,
+    max_idle: u64,
+    /// Idle time seen before the last injection, checked on the next wake up.
+    pending_reset: Option<u64>,
+    idle_period: IdlePeriod,
+    /// Pings the systemd watchdog, `None` when it's off.
+    watchdog: Option<Interval>,
+}
+
+/// Waits for the next watchdog ping, forever when the watchdog is off.
+async fn watchdog_tick(watchdog: &mut Option<Interval>) {
+    match watchdog {
+        Some(watchdog) => {
+            watchdog.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Tracks how long the user has been away, across the idle time resets caused by injections.
+#[derive(Debug, Default)]
+struct IdlePeriod {
+    last_idle: u64,
+    bridged: u64,
+    injected: bool,
+}
+
+impl IdlePeriod {
+    /// Feeds the latest idle time. Returns the length of the idle period once the user is back.
+    fn observe(&mut self, idle_time: u64) -> Option<Duration> {
+        let mut finished = None;
+        if idle_time < self.last_idle {
+            if self.injected {
+                self.bridged += self.last_idle;
+            } else {
+                finished = Some(self.bridged + self.last_idle);
+                self.bridged = 0;
+            }
+        }
+        self.last_idle = idle_time;
+        self.injected = false;
+        finished
+            .filter(|seconds| *seconds >= MINIMUM_INTERVAL)
+            .map(Duration::from_secs)
+    }
+}
+
+impl IdleTask {
+    async fn run(mut self) {
+        debug!("Start idle task");
+        let mut next_check = Instant::now() + STARTUP_DELAY;
+        // The power window sends READY=1 once it took the inhibitor lock or failed to.
+        systemd::notify(&format!("STATUS={}", self.state.borrow().describe()));
+
+        loop {
+            let current = *self.state.borrow();
+            let timer_deadline = current
+                .timer
+                .map(|timer| Instant::now() + timer.remaining());
+
+            tokio::select! {
+                command = self.commands.recv() => {
+                    let Some(command) = command else {
+                        info!("Idle command channel closed");
+                        break;
+                    };
+                    if matches!(command, IdleCommand::Shutdown) {
+                        break;
+                    }
+                    self.apply(command);
+                    next_check = Instant::now();
+                }
+                Some(event) = self.subscription.next() => {
+                    if !is_force_interval_change(&event) {
+                        continue;
+                    }
+                    self.reload_force_interval();
+                    next_check = Instant::now();
+                }
+                () = sleep_until(timer_deadline.unwrap_or(next_check)), if timer_deadline.is_some() => {
+                    self.expire_timer();
+                    next_check = Instant::now();
+                }
+                () = sleep_until(next_check), if !current.paused => {
+                    next_check = Instant::now() + self.check_idle_time();
+                }
+                () = watchdog_tick(&mut self.watchdog) => {
+                    systemd::notify("WATCHDOG=1");
+                }
+            }
+        }
+
+        systemd::notify("STOPPING=1");
+        self.window.close();
+        info!("Idle task stopped");
+    }
+
+    fn apply(&mut self, command: IdleCommand) {
+        let (paused, timer) = match command {
+            IdleCommand::Pause => (true, None),
+            IdleCommand::Resume | IdleCommand::CancelTimer => (false, None),
+            IdleCommand::StartTimer(timer) => {
+                info!("Starting {} timer until {}", timer.mode, timer.deadline);
+                (timer.mode == TimedMode::Pause, Some(timer))
+            }
+            IdleCommand::Shutdown => return,
+        };
+        Timer::store(&self.settings, timer.as_ref());
+        self.set_state(paused, timer);
+    }
+
+    fn expire_timer(&mut self) {
+        let Some(timer) = self.state.borrow().timer else {
+            return;
+        };
+        info!("{} timer expired", timer.mode);
+        Timer::store(&self.settings, None);
+        self.set_state(timer.mode == TimedMode::KeepAwake, None);
+    }
+
+    fn set_state(&mut self, paused: bool, timer: Option<Timer>) {
+        let previous = *self.state.borrow();
+        if paused != previous.paused {
+            info!("Idle task {}", if paused { "paused" } else { "resumed" });
+            self.window.set_keep_awake(!paused);
+            self.pending_reset = None;
+            self.idle_period = IdlePeriod::default();
+            if !paused {
+                self.reload_force_interval();
+            }
+        }
+        self.state.send_modify(|state| {
+            state.paused = paused;
+            state.timer = timer;
+        });
+        let mode = self.state.borrow().mode();
+        if mode != previous.mode() || timer != previous.timer {
+            self.events.publish(Event::ModeChanged { mode });
+            systemd::notify(&format!("STATUS={}", self.state.borrow().describe()));
+        }
+    }
+
+    fn reload_force_interval(&mut self) {
+        self.max_idle = load_force_interval(&self.settings);
+        debug!("Reloaded force interval: {}", self.max_idle);
+        let threshold = injection_threshold(self.max_idle);
+        self.state.send_modify(|state| state.threshold = threshold);
+    }
+
+    /// Injects input once the user has been idle for long enough.
+    ///
+    /// Returns how long to wait before the next check.
+    fn check_idle_time(&mut self) -> Duration {
+        let threshold = injection_threshold(self.max_idle);
+        let idle_time = get_last_input().unwrap_or(0);
+        if let Some(period) = self.idle_period.observe(idle_time) {
+            self.events.publish(Event::UserReturned {
+                idle_seconds: period.as_secs(),
+            });
+        }
+
+        if let Some(previous) = self.pending_reset.take() {
+            if idle_time >= previous {
+                error!("Failed to reset idle time, skipping");
+                self.events.publish(Event::IdleResetFailed {
+                    idle_seconds: previous,
+                });
+            }
+        }
+
+        if idle_time < threshold {
+            return Duration::from_secs(threshold - idle_time);
+        }
+
+        ExecState::user_present();
+        self.idle_period.injected = true;
+        send_mixed_input(InputType::Mouse, &self.settings, &self.events);
+        if get_last_input() >= Some(idle_time) {
+            send_mixed_input(InputType::Keyboard, &self.settings, &self.events);
+            self.pending_reset = Some(idle_time);
+            return RESET_CHECK_DELAY;
+        }
+        Duration::from_secs(threshold)
+    }
+}
+
+/// Input is injected a bit before the force interval runs out.
+fn injection_threshold(max_idle: u64) -> u64 {
+    max_idle * 94 / 100
+}
+
+fn is_force_interval_change(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::SettingChanged { setting, .. } if *setting == RegistryEntries::ForceInterval.to_string()
+    )
+}
+
+fn load_force_interval(settings: &SettingsStore) -> u64 {
+    let max_idle = match settings.get(RegistryEntries::ForceInterval).lock() {
+        Ok(setting) => setting.last_data.parse().map_err(|err| anyhow!("{err}")),
+        Err(err) => Err(anyhow!("Failed to lock force interval, err: {err}")),
+    };
+    let max_idle = match max_idle {
+        Ok(data) => data,
+        Err(err) => {
+            error!("Failed to parse force interval data with err: {err}");
+            0
+        }
+    };
+    if max_idle >= MINIMUM_INTERVAL {
+        return max_idle;
+    }
+
+    info!("Force interval is less than 60 seconds, setting to 60 seconds");
+    let status = match settings.get(RegistryEntries::ForceInterval).lock() {
+        Ok(mut setting) => setting.set_registry_data(MINIMUM_INTERVAL.to_string()),
+        Err(err) => Err(anyhow!("Failed to lock force interval, err: {err}")),
+    };
+    if status.is_err() {
+        error!("Failed to set force interval to 60 seconds");
+    }
+    MINIMUM_INTERVAL
+}
@@ -0,0 +1,142 @@
+use std::{env, time::Duration};
+
+/// Sends `state`, e.g. `READY=1`, to the service manager at `$NOTIFY_SOCKET`. Does nothing
+/// when the variable isn't set, i.e. when not started by systemd with `Type=notify`.
+#[cfg(unix)]
+pub(crate) fn notify(state: &str) {
+    use std::os::unix::{ffi::OsStrExt, net::UnixDatagram};
+    use tracing::{debug, warn};
+
+    let Some(path) = env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let sent =
+        UnixDatagram::unbound().and_then(|socket| match path.as_bytes().strip_prefix(b"@") {
+            Some(name) => send_abstract(&socket, name, state),
+            None => socket.send_to(state.as_bytes(), &path),
+        });
+    match sent {
+        Ok(_) => debug!("Notified service manager: {state}"),
+        Err(err) => warn!("Failed to notify service manager at {path:?}, err: {err}"),
+    }
+}
+
+/// `$NOTIFY_SOCKET` starting with `@` names a socket in the abstract namespace.
+#[cfg(target_os = "linux")]
+fn send_abstract(
+    socket: &std::os::unix::net::UnixDatagram,
+    name: &[u8],
+    state: &str,
+) -> std::io::Result<usize> {
+    use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+
+    socket.send_to_addr(state.as_bytes(), &SocketAddr::from_abstract_name(name)?)
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn send_abstract(
+    _socket: &std::os::unix::net::UnixDatagram,
+    _name: &[u8],
+    _state: &str,
+) -> std::io::Result<usize> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "abstract sockets only exist on Linux",
+    ))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn notify(_state: &str) {}
+
+/// How often to send `WATCHDOG=1`: half the `WatchdogSec=` of the unit, as systemd
+/// recommends. `None` when the watchdog is off or meant for another process.
+pub(crate) fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = env::var("WATCHDOG_PID") {
+        if pid.parse() != Ok(std::process::id()) {
+            return None;
+        }
+    }
+    let usec: u64 = env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec / 2))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{
+        os::unix::net::UnixDatagram,
+        process,
+        sync::{Mutex, PoisonError},
+    };
+
+    use super::*;
+
+    /// Serializes the tests setting `$NOTIFY_SOCKET`.
+    static NOTIFY_SOCKET: Mutex<()> = Mutex::new(());
+
+    /// Sends `state` through [`notify`] to `address` and returns what `socket` received.
+    fn notified(socket: &UnixDatagram, address: &str, state: &str) -> String {
+        let _guard = NOTIFY_SOCKET.lock().unwrap_or_else(PoisonError::into_inner);
+        env::set_var("NOTIFY_SOCKET", address);
+        notify(state);
+        env::remove_var("NOTIFY_SOCKET");
+        socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut buffer = [0; 256];
+        let read = socket.recv(&mut buffer).unwrap();
+        String::from_utf8_lossy(&buffer[..read]).into_owned()
+    }
+
+    #[test]
+    fn notifies_a_socket_path() {
+        let path = env::temp_dir().join(format!("smart-idler-notify-{}", process::id()));
+        let _ = std::fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+        let received = notified(&socket, path.to_str().unwrap(), "READY=1\nSTATUS=Active");
+        assert_eq!(received, "READY=1\nSTATUS=Active");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn notifies_an_abstract_socket() {
+        use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+
+        let name = format!("smart-idler-notify-{}", process::id());
+        let address = SocketAddr::from_abstract_name(name.as_bytes()).unwrap();
+        let socket = UnixDatagram::bind_addr(&address).unwrap();
+        assert_eq!(
+            notified(&socket, &format!("@{name}"), "WATCHDOG=1"),
+            "WATCHDOG=1"
+        );
+    }
+
+    #[test]
+    fn ignores_a_missing_socket() {
+        let _guard = NOTIFY_SOCKET.lock().unwrap_or_else(PoisonError::into_inner);
+        env::remove_var("NOTIFY_SOCKET");
+        notify("READY=1");
+        env::set_var("NOTIFY_SOCKET", "/nonexistent/notify");
+        notify("READY=1");
+        env::remove_var("NOTIFY_SOCKET");
+    }
+
+    #[test]
+    fn halves_the_watchdog_timeout() {
+        env::remove_var("WATCHDOG_PID");
+        env::remove_var("WATCHDOG_USEC");
+        assert_eq!(watchdog_interval(), None);
+        env::set_var("WATCHDOG_USEC", "30000000");
+        assert_eq!(watchdog_interval(), Some(Duration::from_secs(15)));
+        env::set_var("WATCHDOG_PID", process::id().to_string());
+        assert_eq!(watchdog_interval(), Some(Duration::from_secs(15)));
+        env::set_var("WATCHDOG_PID", process::id().wrapping_add(1).to_string());
+        assert_eq!(watchdog_interval(), None);
+        env::remove_var("WATCHDOG_PID");
+        for usec in ["0", "soon", ""] {
+            env::set_var("WATCHDOG_USEC", usec);
+            assert_eq!(watchdog_interval(), None, "{usec:?}");
+        }
+        env::remove_var("WATCHDOG_USEC");
+    }
+}
@@ -0,0 +1,246 @@
+use std::{fmt, time::Duration};
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Local, TimeDelta};
+use tracing::{error, trace};
+
+use registry_ops::{RegistryEntries, RegistryState, SettingsStore};
+
+/// What happens while a [`Timer`] is running. Once the deadline passes the service does the
+/// opposite: a timed pause resumes the idler and a timed keep-awake pauses it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TimedMode {
+    Pause,
+    KeepAwake,
+}
+
+impl fmt::Display for TimedMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimedMode::Pause => write!(f, "Pause"),
+            TimedMode::KeepAwake => write!(f, "KeepAwake"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Timer {
+    pub mode: TimedMode,
+    pub deadline: DateTime<Local>,
+}
+
+impl Timer {
+    /// Creates a timer ending `duration` from now.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the deadline does not fit in a `DateTime`.
+    pub fn new(mode: TimedMode, duration: Duration) -> Result<Timer> {
+        let deadline = TimeDelta::from_std(duration)
+            .ok()
+            .and_then(|delta| Local::now().checked_add_signed(delta))
+            .ok_or_else(|| anyhow!("Duration {duration:?} is out of range"))?;
+        Ok(Timer { mode, deadline })
+    }
+
+    /// Time left until the deadline, zero once it has passed.
+    #[must_use]
+    pub fn remaining(&self) -> Duration {
+        self.deadline
+            .signed_duration_since(Local::now())
+            .to_std()
+            .unwrap_or_default()
+    }
+
+    #[must_use]
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    /// Loads the timer persisted in `settings`, if any.
+    #[must_use]
+    pub fn load(settings: &SettingsStore) -> Option<Timer> {
+        let setting = match settings.get(RegistryEntries::TimedMode).lock() {
+            Ok(setting) => setting,
+            Err(err) => {
+                error!("Failed to lock timed mode, err: {err}");
+                return None;
+            }
+        };
+        Timer::from_registry(&setting.last_data)
+    }
+
+    /// Persists `timer` in `settings`, or clears the stored timer when `None`.
+    pub fn store(settings: &SettingsStore, timer: Option<&Timer>) {
+        let data = timer.map_or_else(
+            || RegistryState::Disabled.to_string(),
+            Timer::registry_value,
+        );
+        let status = match settings.get(RegistryEntries::TimedMode).lock() {
+            Ok(mut setting) => setting.set_registry_data(data),
+            Err(err) => {
+                error!("Failed to lock timed mode, err: {err}");
+                return;
+            }
+        };
+        trace!("Stored timer: {status:?}");
+    }
+
+    fn registry_value(&self) -> String {
+        format!("{}@{}", self.mode, self.deadline.to_rfc3339())
+    }
+
+    fn from_registry(data: &str) -> Option<Timer> {
+        let (mode, deadline) = data.split_once('@')?;
+        let mode = match mode {
+            "Pause" => TimedMode::Pause,
+            "KeepAwake" => TimedMode::KeepAwake,
+            _ => return None,
+        };
+        let deadline = DateTime::parse_from_rfc3339(deadline)
+            .ok()?
+            .with_timezone(&Local);
+        Some(Timer { mode, deadline })
+    }
+}
+
+/// Parses durations such as `90` (minutes), `45s`, `30m`, `2h` or `1h30m`.
+///
+/// # Errors
+///
+/// Returns an error if the text is empty, has an unknown unit or the duration is zero.
+pub fn parse_duration(text: &str) -> Result<Duration> {
+    let text = text.trim();
+    if let Ok(minutes) = text.parse::<u64>() {
+        return check_duration(Duration::from_secs(minutes.saturating_mul(60)), text);
+    }
+
+    let mut total: u64 = 0;
+    let mut number = String::new();
+    for character in text.chars() {
+        if character.is_ascii_digit() {
+            number.push(character);
+            continue;
+        }
+        let value: u64 = number
+            .parse()
+            .map_err(|_| anyhow!("Invalid duration: {text:?}"))?;
+        let unit = match character.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return Err(anyhow!("Unknown unit {character:?} in duration: {text:?}")),
+        };
+        total = total.saturating_add(value.saturating_mul(unit));
+        number.clear();
+    }
+    if !number.is_empty() {
+        return Err(anyhow!("Missing unit in duration: {text:?}"));
+    }
+    check_duration(Duration::from_secs(total), text)
+}
+
+fn check_duration(duration: Duration, text: &str) -> Result<Duration> {
+    if duration.is_zero() {
+        return Err(anyhow!("Duration must be greater than zero: {text:?}"));
+    }
+    Ok(duration)
+}
+
+/// Formats a countdown as `H:MM:SS`.
+#[must_use]
+pub fn format_countdown(remaining: Duration) -> String {
+    let seconds = remaining.as_secs();
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        (seconds / 60) % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90 * 60));
+        assert_eq!(parse_duration("45s").unwrap(), Duration::from_secs(45));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(30 * 60));
+        assert_eq!(
+            parse_duration(" 2H ").unwrap(),
+            Duration::from_secs(2 * 3600)
+        );
+        assert_eq!(
+            parse_duration("1h30m").unwrap(),
+            Duration::from_secs(90 * 60)
+        );
+        assert_eq!(parse_duration("1d").unwrap(), Duration::from_secs(86_400));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        for text in ["", "0", "0m", "h", "10x", "1h30", "-5m", "1.5h"] {
+            assert!(parse_duration(text).is_err(), "{text:?} was accepted");
+        }
+    }
+
+    #[test]
+    fn saturates_huge_durations() {
+        let err = parse_duration("99999999999999999999h").unwrap_err();
+        assert!(err.to_string().contains("Invalid duration"));
+        assert_eq!(
+            parse_duration(&format!("{}h", u64::MAX)).unwrap(),
+            Duration::from_secs(u64::MAX)
+        );
+    }
+
+    #[test]
+    fn formats_countdowns() {
+        assert_eq!(format_countdown(Duration::ZERO), "0:00:00");
+        assert_eq!(format_countdown(Duration::from_secs(59)), "0:00:59");
+        assert_eq!(format_countdown(Duration::from_secs(3661)), "1:01:01");
+        assert_eq!(
+            format_countdown(Duration::from_secs(100 * 3600)),
+            "100:00:00"
+        );
+    }
+
+    #[test]
+    fn round_trips_registry_value() {
+        for mode in [TimedMode::Pause, TimedMode::KeepAwake] {
+            let timer = Timer::new(mode, Duration::from_secs(90)).unwrap();
+            let value = timer.registry_value();
+            assert!(value.starts_with(&format!("{mode}@")));
+            assert_eq!(Timer::from_registry(&value), Some(timer));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_registry_values() {
+        for data in [
+            "",
+            "Disabled",
+            "Pause",
+            "Sleep@2026-01-01T00:00:00+00:00",
+            "Pause@tomorrow",
+        ] {
+            assert_eq!(Timer::from_registry(data), None, "{data:?} was accepted");
+        }
+    }
+
+    #[test]
+    fn reports_remaining_time() {
+        let timer = Timer::new(TimedMode::Pause, Duration::from_secs(3600)).unwrap();
+        assert!(timer.remaining() > Duration::from_secs(3590));
+        assert!(!timer.is_expired());
+        let expired = Timer {
+            mode: TimedMode::Pause,
+            deadline: Local::now() - TimeDelta::seconds(1),
+        };
+        assert_eq!(expired.remaining(), Duration::ZERO);
+        assert!(expired.is_expired());
+    }
+}
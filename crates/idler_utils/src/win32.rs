@@ -0,0 +1,453 @@
+use std::{
+    cell::OnceCell,
+    mem::size_of_val,
+    sync::{
+        Arc, LazyLock,
+        atomic::{AtomicBool, AtomicU32, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+use tracing::{debug, error, info};
+
+use anyhow::{Result, anyhow};
+
+use windows::{
+    Win32::{
+        Foundation::{GetLastError, HINSTANCE, HWND, LPARAM, LRESULT, WPARAM},
+        System::{
+            LibraryLoader::GetModuleHandleW,
+            Power::{
+                ES_CONTINUOUS, ES_DISPLAY_REQUIRED, ES_SYSTEM_REQUIRED, ES_USER_PRESENT,
+                POWERBROADCAST_SETTING, RegisterPowerSettingNotification, SetSuspendState,
+                SetThreadExecutionState,
+            },
+            Shutdown::LockWorkStation,
+            SystemInformation::GetTickCount64,
+            Threading::{GetCurrentProcess, GetCurrentThreadId},
+        },
+        UI::{
+            Input::KeyboardAndMouse::{
+                GetLastInputInfo, INPUT, INPUT_0, INPUT_KEYBOARD, INPUT_MOUSE, KEYBD_EVENT_FLAGS,
+                KEYBDINPUT, KEYEVENTF_KEYUP, LASTINPUTINFO, MOUSEEVENTF_WHEEL, MOUSEINPUT,
+                SendInput, VK_ESCAPE,
+            },
+            WindowsAndMessaging::{
+                CS_HREDRAW, CS_VREDRAW, CreateWindowExW, DefWindowProcW, DestroyWindow,
+                DispatchMessageW, GetMessageW, HWND_MESSAGE, IDC_ARROW, LoadCursorW, MSG,
+                PBT_APMQUERYSUSPEND, PostThreadMessageW, REGISTER_NOTIFICATION_FLAGS,
+                RegisterClassW, TranslateMessage, UnregisterClassW, WINDOW_EX_STYLE, WINDOW_STYLE,
+                WM_APP, WM_POWERBROADCAST, WM_QUIT, WNDCLASSW,
+            },
+        },
+    },
+    core::{BOOL, GUID, w},
+};
+
+use event_bus::{Event, EventBus, PowerEventKind};
+use registry_ops::SettingsStore;
+
+use crate::{InputType, send_mixed_input};
+
+/// Thread message asking the window thread to re-apply the wanted execution state.
+const WM_EXECUTION_STATE: u32 = WM_APP + 1;
+
+thread_local! {
+    /// The state of the power window running on this thread, used by `wndproc`.
+    static WINDOW_STATE: OnceCell<Arc<WindowState>> = const { OnceCell::new() };
+}
+
+static MONITOR_GUID: LazyLock<GUID> =
+    LazyLock::new(|| GUID::try_from("6FE69556-704A-47A0-8F24-C28D936FDA47").unwrap());
+
+const MOUSE_INPUT: INPUT = INPUT {
+    r#type: INPUT_MOUSE,
+    Anonymous: INPUT_0 {
+        mi: MOUSEINPUT {
+            dx: 0,
+            dy: 0,
+            mouseData: 1,
+            dwFlags: MOUSEEVENTF_WHEEL,
+            time: 0,
+            dwExtraInfo: 0,
+        },
+    },
+};
+
+const KEYBOARD_INPUT: [INPUT; 2] = [
+    INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: VK_ESCAPE,
+                wScan: 1,
+                dwFlags: KEYBD_EVENT_FLAGS(0),
+                time: 0,
+                dwExtraInfo: 0,
+            },
+        },
+    },
+    INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: VK_ESCAPE,
+                wScan: 1,
+                dwFlags: KEYEVENTF_KEYUP,
+                time: 0,
+                dwExtraInfo: 0,
+            },
+        },
+    },
+];
+
+#[non_exhaustive]
+pub struct ExecState;
+
+impl ExecState {
+    #[inline]
+    pub fn start() {
+        unsafe {
+            let state =
+                SetThreadExecutionState(ES_CONTINUOUS | ES_SYSTEM_REQUIRED | ES_DISPLAY_REQUIRED);
+            info!("{:?} - ENABLE", state);
+        }
+    }
+    #[inline]
+    pub fn stop() {
+        unsafe {
+            let state = SetThreadExecutionState(ES_CONTINUOUS);
+            info!("{:?} - DISABLE", state);
+        }
+    }
+
+    pub fn user_present() {
+        unsafe {
+            let state = SetThreadExecutionState(ES_USER_PRESENT);
+            info!("{:?} - USER_PRESENT", state);
+        }
+    }
+}
+
+fn send_key_input() -> Result<()> {
+    for item in KEYBOARD_INPUT {
+        let value = unsafe { SendInput(&[item], size_of_val(&[item]).try_into()?) };
+        if value == 1 {
+            info!("Sent KeyboardInput");
+        } else {
+            let err = unsafe { GetLastError() };
+            error!("Failed to send KeyboardInput, last err {:?}", err);
+            return Err(anyhow!("{:?}", err));
+        }
+    }
+    Ok(())
+}
+
+fn send_mouse_input() -> Result<()> {
+    if unsafe { SendInput(&[MOUSE_INPUT], size_of_val(&[MOUSE_INPUT]).try_into()?) } == 1 {
+        info!("Sent MouseInput");
+        Ok(())
+    } else {
+        let err = unsafe { GetLastError() };
+        error!("Failed to send MouseInput, last err {:?}", err);
+        Err(anyhow!("{:?}", err))
+    }
+}
+
+/// Injects a mouse wheel tick or an escape key press.
+pub(crate) fn send_input(input_type: InputType) -> Result<()> {
+    if input_type == InputType::Mouse {
+        send_mouse_input()
+    } else {
+        send_key_input()
+    }
+}
+
+#[derive(Debug)]
+struct WindowState {
+    settings: Arc<SettingsStore>,
+    events: EventBus,
+    thread_id: AtomicU32,
+    keep_awake: AtomicBool,
+    /// Whether the window thread currently holds the execution state.
+    held: AtomicBool,
+    stopped: AtomicBool,
+}
+
+/// The message-only window receiving power notifications.
+///
+/// `SetThreadExecutionState` only applies to the calling thread and async tasks move between
+/// worker threads, so the window thread also holds the execution state for the idle service.
+pub(crate) struct PowerWindow {
+    state: Arc<WindowState>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PowerWindow {
+    pub(crate) fn spawn(
+        keep_awake: bool,
+        settings: Arc<SettingsStore>,
+        events: EventBus,
+    ) -> PowerWindow {
+        let state = Arc::new(WindowState {
+            settings,
+            events,
+            thread_id: AtomicU32::new(0),
+            keep_awake: AtomicBool::new(keep_awake),
+            held: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+        });
+        let window_state = Arc::clone(&state);
+        let thread = thread::spawn(move || {
+            mitigations::hide_current_thread_from_debuggers();
+            let status = spawn_window(&window_state);
+            ExecState::stop();
+            if window_state.held.swap(false, Ordering::SeqCst) {
+                window_state.events.publish(Event::AssertionReleased);
+            }
+            info!("Window thread stopped with status: {status:?}");
+        });
+        PowerWindow {
+            state,
+            thread: Some(thread),
+        }
+    }
+
+    pub(crate) fn set_keep_awake(&self, keep_awake: bool) {
+        self.state.keep_awake.store(keep_awake, Ordering::SeqCst);
+        self.post(WM_EXECUTION_STATE);
+    }
+
+    /// Posts `WM_QUIT` to the window thread and waits for it to exit.
+    pub(crate) fn close(mut self) {
+        self.state.stopped.store(true, Ordering::SeqCst);
+        self.post(WM_QUIT);
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("Window thread panicked");
+            }
+        }
+    }
+
+    fn post(&self, message: u32) {
+        let thread_id = self.state.thread_id.load(Ordering::SeqCst);
+        if thread_id == 0 {
+            // The window thread applies the current state once its message queue exists.
+            return;
+        }
+        let status = unsafe { PostThreadMessageW(thread_id, message, WPARAM(0), LPARAM(0)) };
+        debug!("Posted {message} to window thread: {status:?}");
+    }
+}
+
+fn apply_execution_state(state: &WindowState) {
+    let keep_awake = state.keep_awake.load(Ordering::SeqCst);
+    if keep_awake {
+        ExecState::start();
+    } else {
+        ExecState::stop();
+    }
+    if state.held.swap(keep_awake, Ordering::SeqCst) != keep_awake {
+        state.events.publish(if keep_awake {
+            Event::AssertionAcquired
+        } else {
+            Event::AssertionReleased
+        });
+    }
+}
+
+/// Spawns a new message-only window and pumps its messages until `WM_QUIT` is posted to
+/// the thread.
+///
+/// # Errors
+///
+/// This function will return an error if the window creation fails for any reason,
+/// such as if the window class could not be registered, or if the window could not be created.
+#[allow(clippy::missing_safety_doc)]
+fn spawn_window(state: &Arc<WindowState>) -> Result<()> {
+    WINDOW_STATE.with(|window_state| {
+        let _ = window_state.set(Arc::clone(state));
+    });
+    let instance: HINSTANCE = unsafe { GetModuleHandleW(None) }?.into();
+
+    let window_class = w!("window");
+
+    let wc = WNDCLASSW {
+        hCursor: unsafe { LoadCursorW(None, IDC_ARROW) }?,
+        hInstance: instance,
+        lpszClassName: window_class,
+
+        style: CS_HREDRAW | CS_VREDRAW,
+        lpfnWndProc: Some(wndproc),
+        ..Default::default()
+    };
+
+    let atom = unsafe { RegisterClassW(&wc) };
+    debug_assert!(atom != 0);
+
+    let window_handle: HWND;
+    unsafe {
+        match CreateWindowExW(
+            WINDOW_EX_STYLE(0),
+            window_class,
+            w!("LsWindow"),
+            WINDOW_STYLE(0),
+            0,
+            0,
+            0,
+            0,
+            Some(HWND_MESSAGE),
+            None,
+            Some(instance),
+            None,
+        ) {
+            Ok(hnd) => {
+                info!("Window created");
+                window_handle = hnd;
+            }
+            Err(err) => {
+                error!("Failed to create window with err: {:?}", err);
+                return Err(err.into());
+            }
+        }
+    };
+    match unsafe {
+        RegisterPowerSettingNotification(
+            GetCurrentProcess(),
+            std::ptr::from_ref(&MONITOR_GUID),
+            REGISTER_NOTIFICATION_FLAGS(0),
+        )
+    } {
+        Ok(hp) => {
+            info!("Registered for power notifications: {:?}", hp);
+        }
+        Err(err) => {
+            error!("Could not register for power notifications, err: {:?}", err);
+        }
+    }
+
+    state
+        .thread_id
+        .store(unsafe { GetCurrentThreadId() }, Ordering::SeqCst);
+    apply_execution_state(state);
+
+    let mut message = MSG::default();
+    while !state.stopped.load(Ordering::SeqCst)
+        && unsafe { GetMessageW(&mut message, None, 0, 0).into() }
+    {
+        if message.message == WM_EXECUTION_STATE {
+            apply_execution_state(state);
+            continue;
+        }
+        unsafe {
+            if !TranslateMessage(&message).as_bool() {
+                continue;
+            }
+            DispatchMessageW(&message);
+        }
+    }
+    unsafe {
+        DestroyWindow(window_handle)?;
+        UnregisterClassW(window_class, Some(instance))?;
+    }
+    Ok(())
+}
+
+unsafe extern "system" fn wndproc(
+    window: HWND,
+    message: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if message == PBT_APMQUERYSUSPEND {
+        debug!("PBT_APMQUERYSUSPEND");
+        LRESULT(0)
+    } else if message == WM_POWERBROADCAST {
+        debug!("WM_POWERBROADCAST: {:?} - {:?}", wparam, lparam);
+        if wparam == WPARAM(32787) {
+            let st: &mut POWERBROADCAST_SETTING =
+                unsafe { &mut *(lparam.0 as *mut POWERBROADCAST_SETTING) };
+            if st.PowerSetting == *MONITOR_GUID && st.Data == [0] {
+                WINDOW_STATE.with(|state| {
+                    if let Some(state) = state.get() {
+                        state.events.publish(Event::PowerEvent {
+                            kind: PowerEventKind::DisplayOff,
+                        });
+                        send_mixed_input(InputType::Mouse, &state.settings, &state.events);
+                    }
+                });
+            }
+        }
+        LRESULT(0)
+    } else {
+        debug!(
+            "msg-only message: {} - {:?} - {:?}",
+            message, wparam, lparam
+        );
+        unsafe { DefWindowProcW(window, message, wparam, lparam) }
+    }
+}
+
+/// Seconds since the last user or injected input.
+#[must_use]
+pub fn get_last_input() -> Option<u64> {
+    let mut last_input = LASTINPUTINFO::default();
+
+    last_input.cbSize = if let Ok(val) = size_of_val(&last_input).try_into() {
+        val
+    } else {
+        error!("Failed to get size of last input");
+        return None;
+    };
+    let total_ticks;
+    unsafe {
+        if GetLastInputInfo(std::ptr::from_mut(&mut last_input)) != BOOL(1) {
+            error!("Failed to get last input info, {:?}", GetLastError());
+            return None;
+        }
+        total_ticks = GetTickCount64();
+    }
+    Some(Duration::from_millis(total_ticks - u64::from(last_input.dwTime)).as_secs())
+}
+
+/// Locks the workstation, like Win+L.
+///
+/// # Errors
+///
+/// Returns an error if the workstation can't be locked, e.g. from a service session.
+pub async fn lock_session() -> Result<()> {
+    tokio::task::spawn_blocking(|| unsafe { LockWorkStation() })
+        .await?
+        .map_err(|err| anyhow!("Failed to lock the workstation, err: {err}"))
+}
+
+/// Suspends the system. Returns once it woke up again.
+///
+/// # Errors
+///
+/// Returns an error if the system refused to suspend.
+pub async fn suspend() -> Result<()> {
+    set_suspend_state(false).await
+}
+
+/// Hibernates the system. Returns once it woke up again.
+///
+/// # Errors
+///
+/// Returns an error if the system refused to hibernate, e.g. with hibernation turned off.
+pub async fn hibernate() -> Result<()> {
+    set_suspend_state(true).await
+}
+
+async fn set_suspend_state(hibernate: bool) -> Result<()> {
+    // The call blocks until the system resumes.
+    let suspended = tokio::task::spawn_blocking(move || {
+        if unsafe { SetSuspendState(hibernate, false, false) } {
+            Ok(())
+        } else {
+            Err(unsafe { GetLastError() })
+        }
+    })
+    .await?;
+    suspended.map_err(|err| anyhow!("Failed to suspend, hibernate: {hibernate}, err: {err:?}"))
+}
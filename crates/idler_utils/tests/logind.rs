@@ -0,0 +1,101 @@
+//! Drives [`LoginManager`] against a fake `org.freedesktop.login1` on a private `dbus-daemon`,
+//! which must be installed.
+#![cfg(target_os = "linux")]
+
+use std::sync::{Arc, Mutex};
+
+use zbus::{Connection, fdo, interface};
+
+use idler_utils::LoginManager;
+use test_support::DbusDaemon;
+
+/// Calls the fake logind got, e.g. `Suspend(false)`.
+type Calls = Arc<Mutex<Vec<String>>>;
+
+struct Manager {
+    calls: Calls,
+    /// Refuses to hibernate, like logind without swap.
+    no_swap: bool,
+}
+
+#[interface(name = "org.freedesktop.login1.Manager")]
+impl Manager {
+    fn suspend(&self, interactive: bool) {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("Suspend({interactive})"));
+    }
+
+    fn hibernate(&self, interactive: bool) -> fdo::Result<()> {
+        if self.no_swap {
+            return Err(fdo::Error::NotSupported(
+                "Not enough swap space".to_string(),
+            ));
+        }
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("Hibernate({interactive})"));
+        Ok(())
+    }
+}
+
+struct Session {
+    calls: Calls,
+}
+
+#[interface(name = "org.freedesktop.login1.Session")]
+impl Session {
+    fn lock(&self) {
+        self.calls.lock().unwrap().push("Lock".to_string());
+    }
+}
+
+/// Serves a fake logind recording into `calls`.
+async fn serve_logind(bus: &DbusDaemon, calls: &Calls, no_swap: bool) -> Connection {
+    let manager = Manager {
+        calls: Arc::clone(calls),
+        no_swap,
+    };
+    let session = Session {
+        calls: Arc::clone(calls),
+    };
+    bus.builder()
+        .name("org.freedesktop.login1")
+        .unwrap()
+        .serve_at("/org/freedesktop/login1", manager)
+        .unwrap()
+        .serve_at("/org/freedesktop/login1/session/auto", session)
+        .unwrap()
+        .build()
+        .await
+        .expect("serve the fake logind")
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn asks_logind_without_interaction() {
+    let bus = DbusDaemon::start();
+    let calls = Calls::default();
+
+    let logind = serve_logind(&bus, &calls, false).await;
+    let manager = LoginManager::new(bus.connect().await);
+    manager.suspend().await.unwrap();
+    manager.hibernate().await.unwrap();
+    manager.lock_session().await.unwrap();
+    assert_eq!(
+        *calls.lock().unwrap(),
+        ["Suspend(false)", "Hibernate(false)", "Lock"]
+    );
+    drop(logind);
+
+    // A refusal reaches the caller.
+    calls.lock().unwrap().clear();
+    let _logind = serve_logind(&bus, &calls, true).await;
+    let err = manager.hibernate().await.unwrap_err();
+    assert!(
+        matches!(&err, zbus::Error::MethodError(name, _, _) if name.as_str() == "org.freedesktop.DBus.Error.NotSupported"),
+        "{err}"
+    );
+    assert!(calls.lock().unwrap().is_empty());
+}
@@ -0,0 +1,386 @@
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr, TcpListener as StdTcpListener},
+    sync::Arc,
+    time::Duration,
+};
+use tracing::{debug, error, info, trace};
+
+use anyhow::{Result, anyhow};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::oneshot,
+    time::timeout,
+};
+
+const MAX_REQUEST_SIZE: usize = 16 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Builds the response for a parsed request.
+pub type Handler = Arc<dyn Fn(&Request) -> Response + Send + Sync>;
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    /// Empty unless the request had a `Content-Length`.
+    pub body: String,
+}
+
+impl Request {
+    /// Value of the first header named `name`, compared case-insensitively.
+    #[must_use]
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn parse(head: &str, body: String) -> Result<Request> {
+        let mut lines = head.split("\r\n");
+        let request_line = lines.next().unwrap_or_default();
+        let mut parts = request_line.split_whitespace();
+        let (Some(method), Some(target), Some(_version)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(anyhow!("Malformed request line: {request_line:?}"));
+        };
+        let path = target.split('?').next().unwrap_or(target).to_string();
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+            .collect();
+        Ok(Request {
+            method: method.to_string(),
+            path,
+            headers,
+            body,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: String,
+    pub body: String,
+}
+
+impl Response {
+    #[must_use]
+    pub fn new(status: u16, content_type: &str, body: String) -> Response {
+        Response {
+            status,
+            content_type: content_type.to_string(),
+            body,
+        }
+    }
+
+    #[must_use]
+    pub fn text(status: u16, body: &str) -> Response {
+        Response::new(status, "text/plain; charset=utf-8", format!("{body}\n"))
+    }
+
+    #[must_use]
+    pub fn not_found() -> Response {
+        Response::text(404, "Not Found")
+    }
+
+    #[must_use]
+    pub fn method_not_allowed() -> Response {
+        Response::text(405, "Method Not Allowed")
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            204 => "No Content",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Payload Too Large",
+            503 => "Service Unavailable",
+            _ => "Internal Server Error",
+        }
+    }
+
+    fn to_bytes(&self, include_body: bool) -> Vec<u8> {
+        let mut data = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            self.reason(),
+            self.content_type,
+            self.body.len()
+        )
+        .into_bytes();
+        if include_body {
+            data.extend_from_slice(self.body.as_bytes());
+        }
+        data
+    }
+}
+
+/// An HTTP/1.1 listener on `127.0.0.1`. Every connection serves a single request.
+///
+/// The listener stops when the server is stopped or dropped.
+pub struct HttpServer {
+    address: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl HttpServer {
+    /// Binds `127.0.0.1:port` and serves requests with `handler` on the current tokio runtime.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the port can't be bound.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub fn start(port: u16, handler: Handler) -> Result<HttpServer> {
+        let listener = StdTcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        listener.set_nonblocking(true)?;
+        let listener = TcpListener::from_std(listener)?;
+        let address = listener.local_addr()?;
+        let (shutdown, mut shutdown_rx) = oneshot::channel();
+        info!("Listening on http://{address}");
+
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = &mut shutdown_rx => break,
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, peer)) => {
+                            trace!("Accepted connection from {peer}");
+                            tokio::spawn(serve(stream, Arc::clone(&handler)));
+                        }
+                        Err(err) => error!("Failed to accept connection, err: {err}"),
+                    },
+                }
+            }
+            info!("Stopped listening on http://{address}");
+        });
+
+        Ok(HttpServer {
+            address,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    #[must_use]
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+impl Drop for HttpServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Why a request was rejected before reaching the handler.
+#[derive(Debug)]
+enum ReadError {
+    /// The request is malformed, or the connection closed before its end.
+    Bad(anyhow::Error),
+    /// The headers or the announced body exceed [`MAX_REQUEST_SIZE`].
+    TooLarge,
+}
+
+impl ReadError {
+    fn response(&self) -> Response {
+        match self {
+            ReadError::Bad(_) => Response::text(400, "Bad Request"),
+            ReadError::TooLarge => Response::text(413, "Payload Too Large"),
+        }
+    }
+}
+
+impl std::fmt::Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadError::Bad(err) => write!(f, "{err}"),
+            ReadError::TooLarge => write!(f, "Request is larger than {MAX_REQUEST_SIZE} bytes"),
+        }
+    }
+}
+
+impl From<anyhow::Error> for ReadError {
+    fn from(err: anyhow::Error) -> ReadError {
+        ReadError::Bad(err)
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> ReadError {
+        ReadError::Bad(err.into())
+    }
+}
+
+async fn serve(mut stream: TcpStream, handler: Handler) {
+    let response = match timeout(READ_TIMEOUT, read_request(&mut stream)).await {
+        Ok(Ok((head, body))) => match Request::parse(&head, body) {
+            Ok(request) => {
+                debug!("{} {}", request.method, request.path);
+                let response = handler(&request);
+                let include_body = request.method != "HEAD";
+                response.to_bytes(include_body)
+            }
+            Err(err) => {
+                debug!("Rejecting request, err: {err}");
+                Response::text(400, "Bad Request").to_bytes(true)
+            }
+        },
+        Ok(Err(err)) => {
+            debug!("Rejecting request, err: {err}");
+            err.response().to_bytes(true)
+        }
+        Err(_) => {
+            debug!("Timed out reading request");
+            return;
+        }
+    };
+    if let Err(err) = stream.write_all(&response).await {
+        debug!("Failed to write response, err: {err}");
+    }
+    let _ = stream.shutdown().await;
+}
+
+fn content_length(head: &str) -> Result<usize> {
+    let Some(value) = head
+        .split("\r\n")
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case("Content-Length"))
+        .map(|(_, value)| value.trim())
+    else {
+        return Ok(0);
+    };
+    value
+        .parse()
+        .map_err(|_| anyhow!("Invalid Content-Length {value:?}"))
+}
+
+/// Reads the request headers and the body announced by `Content-Length`. Requests larger than
+/// [`MAX_REQUEST_SIZE`] are rejected before their body is read.
+async fn read_request(
+    stream: &mut (impl AsyncRead + Unpin),
+) -> Result<(String, String), ReadError> {
+    let mut data = Vec::new();
+    let mut buffer = [0; 1024];
+    let (head, body_start, length) = loop {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            return Err(anyhow!("Connection closed before the end of the headers").into());
+        }
+        data.extend_from_slice(&buffer[..read]);
+        if let Some(end) = data.windows(4).position(|window| window == b"\r\n\r\n") {
+            let head = String::from_utf8_lossy(&data[..end]).into_owned();
+            let length = content_length(&head)?;
+            break (head, end + 4, length);
+        }
+        if data.len() > MAX_REQUEST_SIZE {
+            return Err(ReadError::TooLarge);
+        }
+    };
+    // The length comes from the peer, so it may be anything up to `usize::MAX`.
+    if body_start
+        .checked_add(length)
+        .is_none_or(|size| size > MAX_REQUEST_SIZE)
+    {
+        return Err(ReadError::TooLarge);
+    }
+    let mut body = data.split_off(body_start);
+    body.truncate(length);
+    while body.len() < length {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            return Err(anyhow!("Connection closed before the end of the body").into());
+        }
+        let missing = length - body.len();
+        body.extend_from_slice(&buffer[..read.min(missing)]);
+    }
+    let body = String::from_utf8(body).map_err(|_| anyhow!("Request body is not UTF-8"))?;
+    Ok((head, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read(request: &[u8]) -> Result<(String, String), ReadError> {
+        let mut stream = request;
+        read_request(&mut stream).await
+    }
+
+    #[tokio::test]
+    async fn reads_head_and_body() {
+        let (head, body) = read(b"POST /pause HTTP/1.1\r\nContent-Length: 4\r\n\r\n60s\nextra")
+            .await
+            .unwrap();
+        assert_eq!(head, "POST /pause HTTP/1.1\r\nContent-Length: 4");
+        assert_eq!(body, "60s\n");
+        let request = Request::parse(&head, body).unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/pause");
+        assert_eq!(request.header("content-length"), Some("4"));
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_requests() {
+        for request in [
+            &b"GET / HTTP/1.1\r\nContent-Length: four\r\n\r\n"[..],
+            b"GET / HTTP/1.1\r\nContent-Length: -1\r\n\r\n",
+            b"GET / HTTP/1.1\r\n",
+            b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort",
+            b"POST / HTTP/1.1\r\nContent-Length: 2\r\n\r\n\xff\xfe",
+        ] {
+            assert!(matches!(read(request).await, Err(ReadError::Bad(_))));
+        }
+        assert!(Request::parse("GET /", String::new()).is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_oversized_requests() {
+        let huge = format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", usize::MAX);
+        let body = format!("POST / HTTP/1.1\r\nContent-Length: {MAX_REQUEST_SIZE}\r\n\r\n");
+        let headers = format!(
+            "GET / HTTP/1.1\r\nX-Padding: {}",
+            "a".repeat(MAX_REQUEST_SIZE)
+        );
+        for request in [huge, body, headers] {
+            assert!(matches!(
+                read(request.as_bytes()).await,
+                Err(ReadError::TooLarge)
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn answers_oversized_requests_with_413() {
+        let handler: Handler = Arc::new(|_| Response::text(200, "OK"));
+        let server = HttpServer::start(0, handler).unwrap();
+        let mut stream = TcpStream::connect(server.address()).await.unwrap();
+        let request = format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", usize::MAX);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+    }
+}
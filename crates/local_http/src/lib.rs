@@ -0,0 +1,5 @@
+mod http;
+mod metrics;
+
+pub use http::{Handler, HttpServer, Request, Response};
+pub use metrics::{CONTENT_TYPE as METRICS_CONTENT_TYPE, MetricsSnapshot, metrics_handler, render};
@@ -0,0 +1,170 @@
+use std::{fmt::Write, sync::Arc, time::Duration};
+
+use crate::http::{Handler, Request, Response};
+
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+const PREFIX: &str = "smart_idler";
+
+/// Values reported on each scrape.
+#[derive(Debug, Clone, Default)]
+pub struct MetricsSnapshot {
+    /// Seconds since the last user or injected input, `None` if it couldn't be read.
+    pub idle_seconds: Option<u64>,
+    pub force_interval_seconds: u64,
+    /// Injections sent since the app started, by input type.
+    pub injections: Vec<(String, u64)>,
+    pub last_injection_age: Option<Duration>,
+    /// Execution state assertions and whether they are currently held.
+    pub assertions: Vec<(String, bool)>,
+    pub paused: bool,
+    /// Mode and remaining time of the running pause or keep-awake timer.
+    pub timer: Option<(String, Duration)>,
+    pub schedule_active: bool,
+    pub schedule_remaining: Option<Duration>,
+}
+
+/// Renders `snapshot` in the `OpenMetrics` text format.
+#[must_use]
+pub fn render(snapshot: &MetricsSnapshot) -> String {
+    let mut writer = MetricsWriter::default();
+
+    writer.family(
+        "idle_seconds",
+        "gauge",
+        "Seconds since the last user or injected input.",
+    );
+    if let Some(idle_seconds) = snapshot.idle_seconds {
+        writer.sample("idle_seconds", &[], idle_seconds);
+    }
+
+    writer.family(
+        "force_interval_seconds",
+        "gauge",
+        "Configured idle time before input is injected.",
+    );
+    writer.sample(
+        "force_interval_seconds",
+        &[],
+        snapshot.force_interval_seconds,
+    );
+
+    writer.family(
+        "injections",
+        "counter",
+        "Inputs injected since the app started.",
+    );
+    for (input_type, count) in &snapshot.injections {
+        writer.sample("injections_total", &[("type", input_type)], count);
+    }
+
+    writer.family(
+        "last_injection_age_seconds",
+        "gauge",
+        "Seconds since the last injected input.",
+    );
+    if let Some(age) = snapshot.last_injection_age {
+        writer.sample("last_injection_age_seconds", &[], age.as_secs_f64());
+    }
+
+    writer.family(
+        "assertion_active",
+        "gauge",
+        "Whether the execution state assertion is held.",
+    );
+    for (assertion, active) in &snapshot.assertions {
+        writer.sample(
+            "assertion_active",
+            &[("assertion", assertion)],
+            u8::from(*active),
+        );
+    }
+
+    writer.family("paused", "gauge", "Whether the idler is paused.");
+    writer.sample("paused", &[], u8::from(snapshot.paused));
+
+    writer.family(
+        "timer_remaining_seconds",
+        "gauge",
+        "Seconds left on the running pause or keep-awake timer.",
+    );
+    if let Some((mode, remaining)) = &snapshot.timer {
+        writer.sample(
+            "timer_remaining_seconds",
+            &[("mode", mode)],
+            remaining.as_secs(),
+        );
+    }
+
+    writer.family(
+        "schedule_active",
+        "gauge",
+        "Whether a scheduled shutdown is pending.",
+    );
+    writer.sample("schedule_active", &[], u8::from(snapshot.schedule_active));
+
+    writer.family(
+        "schedule_remaining_seconds",
+        "gauge",
+        "Seconds until the scheduled shutdown.",
+    );
+    if let Some(remaining) = snapshot.schedule_remaining {
+        writer.sample("schedule_remaining_seconds", &[], remaining.as_secs());
+    }
+
+    writer.finish()
+}
+
+/// Handler serving `GET /metrics` from the snapshot returned by `collect`.
+pub fn metrics_handler<F>(collect: F) -> Handler
+where
+    F: Fn() -> MetricsSnapshot + Send + Sync + 'static,
+{
+    Arc::new(move |request: &Request| {
+        if request.path != "/metrics" {
+            return Response::not_found();
+        }
+        if request.method != "GET" && request.method != "HEAD" {
+            return Response::method_not_allowed();
+        }
+        Response::new(200, CONTENT_TYPE, render(&collect()))
+    })
+}
+
+#[derive(Default)]
+struct MetricsWriter {
+    output: String,
+}
+
+impl MetricsWriter {
+    fn family(&mut self, name: &str, metric_type: &str, help: &str) {
+        let _ = writeln!(self.output, "# TYPE {PREFIX}_{name} {metric_type}");
+        if let Some(unit) = name.strip_suffix("_seconds").map(|_| "seconds") {
+            let _ = writeln!(self.output, "# UNIT {PREFIX}_{name} {unit}");
+        }
+        let _ = writeln!(self.output, "# HELP {PREFIX}_{name} {help}");
+    }
+
+    fn sample<T: std::fmt::Display>(&mut self, name: &str, labels: &[(&str, &str)], value: T) {
+        let _ = write!(self.output, "{PREFIX}_{name}");
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(key, value)| format!("{key}=\"{}\"", escape_label(value)))
+                .collect();
+            let _ = write!(self.output, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.output, " {value}");
+    }
+
+    fn finish(mut self) -> String {
+        self.output.push_str("# EOF\n");
+        self.output
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
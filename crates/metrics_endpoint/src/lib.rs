@@ -0,0 +1,240 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{error, info, warn};
+
+use app_controller::AppContext;
+use event_bus::{Event, Strategy};
+use idler_utils::{IdleService, TimedMode};
+use local_http::{HttpServer, MetricsSnapshot};
+use registry_ops::RegistryEntries;
+
+/// What the endpoint learned from the event bus since the app started.
+#[derive(Debug)]
+struct Observed {
+    injections: BTreeMap<Strategy, u64>,
+    last_injection: Option<Instant>,
+    assertion_held: bool,
+}
+
+/// The optional `OpenMetrics` listener on `127.0.0.1`, controlled by the `MetricsEndpoint`
+/// and `MetricsPort` settings.
+struct MetricsEndpoint {
+    context: Arc<AppContext>,
+    observed: Arc<Mutex<Observed>>,
+    server: Option<HttpServer>,
+}
+
+/// Starts the listener when it is enabled in the settings, and follows the event bus to keep
+/// the counters and the listener state up to date.
+///
+/// # Panics
+///
+/// Panics if called outside of a tokio runtime.
+pub fn spawn(context: Arc<AppContext>) {
+    let mut subscription = context.events().subscribe("metrics");
+    let enabled = context
+        .settings()
+        .get(RegistryEntries::MetricsEndpoint)
+        .lock()
+        .is_ok_and(|setting| setting.is_enabled());
+    let mut endpoint = MetricsEndpoint::new(context);
+    endpoint.set_enabled(enabled);
+
+    tokio::spawn(async move {
+        while let Some(event) = subscription.next().await {
+            endpoint.handle(&event);
+        }
+    });
+}
+
+impl MetricsEndpoint {
+    fn new(context: Arc<AppContext>) -> MetricsEndpoint {
+        MetricsEndpoint {
+            context,
+            observed: Arc::new(Mutex::new(Observed {
+                injections: [(Strategy::Mouse, 0), (Strategy::Keyboard, 0)].into(),
+                last_injection: None,
+                assertion_held: false,
+            })),
+            server: None,
+        }
+    }
+
+    fn handle(&mut self, event: &Event) {
+        match event {
+            Event::SettingChanged { setting, value } => {
+                if *setting == RegistryEntries::MetricsEndpoint.to_string() {
+                    self.set_enabled(value == "Enabled");
+                } else if *setting == RegistryEntries::MetricsPort.to_string() {
+                    self.restart();
+                }
+            }
+            Event::InputInjected { strategy } => self.observe(|observed| {
+                *observed.injections.entry(*strategy).or_default() += 1;
+                observed.last_injection = Some(Instant::now());
+            }),
+            Event::AssertionAcquired => self.observe(|observed| observed.assertion_held = true),
+            Event::AssertionReleased => self.observe(|observed| observed.assertion_held = false),
+            _ => {}
+        }
+    }
+
+    fn observe(&self, update: impl FnOnce(&mut Observed)) {
+        match self.observed.lock() {
+            Ok(mut observed) => update(&mut observed),
+            Err(err) => error!("Failed to lock metrics, err: {err}"),
+        }
+    }
+
+    /// Rebinds a running listener, e.g. after the port changed.
+    fn restart(&mut self) {
+        if self.server.is_some() {
+            self.set_enabled(false);
+            self.set_enabled(true);
+        }
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            if let Some(server) = self.server.take() {
+                info!("Stopping metrics endpoint");
+                server.stop();
+            }
+            return;
+        }
+        if self.server.is_some() {
+            return;
+        }
+
+        let Some(port) = load_port(&self.context) else {
+            return;
+        };
+        let context = Arc::clone(&self.context);
+        let observed = Arc::clone(&self.observed);
+        let handler = local_http::metrics_handler(move || collect(&context, &observed));
+        match HttpServer::start(port, handler) {
+            Ok(server) => self.server = Some(server),
+            Err(err) => error!("Failed to start metrics endpoint on port {port}, err: {err}"),
+        }
+    }
+}
+
+fn load_port(context: &AppContext) -> Option<u16> {
+    let setting = match context.settings().get(RegistryEntries::MetricsPort).lock() {
+        Ok(setting) => setting,
+        Err(err) => {
+            error!("Failed to lock metrics port, err: {err}");
+            return None;
+        }
+    };
+    match setting.last_data.parse() {
+        Ok(port) => Some(port),
+        Err(err) => {
+            warn!(
+                "Found invalid metrics port {:?}, err: {err}",
+                setting.last_data
+            );
+            None
+        }
+    }
+}
+
+fn collect(context: &AppContext, observed: &Mutex<Observed>) -> MetricsSnapshot {
+    let force_interval_seconds = context
+        .settings()
+        .get(RegistryEntries::ForceInterval)
+        .lock()
+        .ok()
+        .and_then(|setting| setting.last_data.parse().ok())
+        .unwrap_or_default();
+    let (injections, last_injection_age, assertion_held) = match observed.lock() {
+        Ok(observed) => (
+            observed
+                .injections
+                .iter()
+                .map(|(strategy, count)| (strategy.to_string().to_lowercase(), *count))
+                .collect(),
+            observed.last_injection.map(|at| at.elapsed()),
+            observed.assertion_held,
+        ),
+        Err(err) => {
+            error!("Failed to lock metrics, err: {err}");
+            (Vec::new(), None, false)
+        }
+    };
+
+    let service = context.idle_service();
+    let paused = service.is_none_or(IdleService::is_paused);
+    let timer = service.and_then(IdleService::timer).map(|timer| {
+        let mode = match timer.mode {
+            TimedMode::Pause => "pause",
+            TimedMode::KeepAwake => "keep_awake",
+        };
+        (mode.to_string(), timer.remaining())
+    });
+
+    let (schedule_active, schedule_remaining) = match app_controller::status::schedule(context) {
+        Ok(schedule) => (
+            schedule.active,
+            schedule.remaining_seconds.map(Duration::from_secs),
+        ),
+        Err(err) => {
+            error!("Failed to get schedule, err: {err}");
+            (false, None)
+        }
+    };
+
+    MetricsSnapshot {
+        idle_seconds: idler_utils::get_last_input(),
+        force_interval_seconds,
+        injections,
+        last_injection_age,
+        // The power window holds both assertions at once.
+        assertions: vec![
+            ("system_required".to_string(), assertion_held),
+            ("display_required".to_string(), assertion_held),
+        ],
+        paused,
+        timer,
+        schedule_active,
+        schedule_remaining,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use app_controller::SystemClock;
+    use event_bus::EventBus;
+    use registry_ops::SettingsStore;
+
+    #[tokio::test]
+    async fn counts_what_the_bus_reports() {
+        let context =
+            AppContext::with_store(SettingsStore::in_memory(), EventBus::new(), SystemClock);
+        let mut endpoint = MetricsEndpoint::new(Arc::new(context));
+
+        let injected = |strategy| Event::InputInjected { strategy };
+        endpoint.handle(&injected(Strategy::Keyboard));
+        endpoint.handle(&injected(Strategy::Keyboard));
+        endpoint.handle(&Event::AssertionAcquired);
+        let snapshot = collect(&endpoint.context, &endpoint.observed);
+        assert_eq!(
+            snapshot.injections,
+            [("mouse".to_string(), 0), ("keyboard".to_string(), 2)]
+        );
+        assert!(snapshot.last_injection_age.is_some());
+        assert!(snapshot.assertions.iter().all(|(_, held)| *held));
+        assert_eq!(snapshot.force_interval_seconds, 60);
+        // Nothing idles without a service.
+        assert!(snapshot.paused);
+
+        endpoint.handle(&Event::AssertionReleased);
+        let snapshot = collect(&endpoint.context, &endpoint.observed);
+        assert!(snapshot.assertions.iter().all(|(_, held)| !*held));
+    }
+}
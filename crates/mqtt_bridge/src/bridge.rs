@@ -0,0 +1,309 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tracing::{debug, error, info, warn};
+
+use rumqttc::{AsyncClient, Event as MqttEvent, EventLoop, LastWill, MqttOptions, Packet, QoS};
+use tokio::{sync::Notify, task::JoinHandle};
+
+use app_controller::{AppContext, CommandError, status};
+use event_bus::IdlerMode;
+use registry_ops::{ForceInterval, MqttBroker, MqttTopic, RegistryEntries};
+
+use crate::topics::{OFFLINE, ONLINE, Topics};
+
+/// How often the idle time is published between events.
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(10);
+/// Delay before connecting again after the broker went away.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+/// How long a stopping bridge may take to tell the broker it goes offline.
+const STOP_TIMEOUT: Duration = Duration::from_secs(2);
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+const CHANNEL_CAPACITY: usize = 64;
+
+/// A connection to the broker, publishing the state and taking commands until stopped.
+pub(crate) struct Bridge {
+    client: AsyncClient,
+    topics: Topics,
+    connection: JoinHandle<()>,
+    publisher: JoinHandle<()>,
+}
+
+impl Bridge {
+    /// Connects in the background, so an unreachable broker is retried instead of failing.
+    pub fn start(context: Arc<AppContext>, broker: &MqttBroker, topic: &MqttTopic) -> Bridge {
+        let topics = Topics::new(topic.as_str());
+        let mut options = MqttOptions::new(
+            format!("smart-idler-{}", topics.node_id()),
+            broker.host.clone(),
+            broker.port,
+        );
+        options
+            .set_keep_alive(KEEP_ALIVE)
+            .set_last_will(LastWill::new(
+                topics.availability(),
+                OFFLINE,
+                QoS::AtLeastOnce,
+                true,
+            ));
+        let (client, event_loop) = AsyncClient::new(options, CHANNEL_CAPACITY);
+        let connected = Arc::new(Notify::new());
+
+        info!("Connecting to MQTT broker {broker} under {topic}");
+        let connection = tokio::spawn(run_connection(
+            Arc::clone(&context),
+            client.clone(),
+            event_loop,
+            topics.clone(),
+            Arc::clone(&connected),
+        ));
+        let publisher = tokio::spawn(run_publisher(
+            context,
+            client.clone(),
+            topics.clone(),
+            connected,
+        ));
+        Bridge {
+            client,
+            topics,
+            connection,
+            publisher,
+        }
+    }
+
+    /// Marks the idler offline and disconnects, giving up after [`STOP_TIMEOUT`].
+    pub fn stop(self) {
+        info!("Disconnecting from MQTT broker");
+        self.publisher.abort();
+        let offline =
+            self.client
+                .try_publish(self.topics.availability(), QoS::AtLeastOnce, true, OFFLINE);
+        if let Err(err) = offline.and_then(|()| self.client.try_disconnect()) {
+            debug!("Failed to queue MQTT disconnect, err: {err}");
+        }
+        let mut connection = self.connection;
+        tokio::spawn(async move {
+            if tokio::time::timeout(STOP_TIMEOUT, &mut connection)
+                .await
+                .is_err()
+            {
+                connection.abort();
+            }
+        });
+    }
+}
+
+/// Drives the connection: subscribes to the command topic on every connect, runs the
+/// commands and reconnects after errors. Returns once the client disconnected.
+async fn run_connection(
+    context: Arc<AppContext>,
+    client: AsyncClient,
+    mut event_loop: EventLoop,
+    topics: Topics,
+    connected: Arc<Notify>,
+) {
+    let command_topic = topics.command();
+    loop {
+        match event_loop.poll().await {
+            Ok(MqttEvent::Incoming(Packet::ConnAck(_))) => {
+                info!("Connected to MQTT broker");
+                if let Err(err) = client.try_subscribe(&command_topic, QoS::AtLeastOnce) {
+                    error!("Failed to subscribe to {command_topic}, err: {err}");
+                }
+                connected.notify_one();
+            }
+            Ok(MqttEvent::Incoming(Packet::Publish(publish))) if publish.topic == command_topic => {
+                let payload = String::from_utf8_lossy(&publish.payload);
+                if let Err(err) = run_command(&context, payload.trim()) {
+                    warn!("Rejected MQTT command {payload:?}, err: {err}");
+                }
+            }
+            Ok(MqttEvent::Outgoing(rumqttc::Outgoing::Disconnect)) => return,
+            Ok(_) => {}
+            Err(err) => {
+                warn!("Lost MQTT connection, retrying in {RETRY_DELAY:?}, err: {err}");
+                tokio::time::sleep(RETRY_DELAY).await;
+            }
+        }
+    }
+}
+
+/// A command from the command topic.
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    /// Pauses, for the duration if one is given.
+    Pause(Option<Duration>),
+    Resume,
+    Interval(ForceInterval),
+}
+
+impl Command {
+    /// Parses `pause [DURATION]`, `resume` or `interval SECONDS`.
+    fn parse(command: &str) -> Result<Command, CommandError> {
+        let (name, argument) = command
+            .split_once(char::is_whitespace)
+            .map_or((command, None), |(name, argument)| {
+                (name, Some(argument.trim()))
+            });
+        let invalid = |err: &dyn std::fmt::Display| CommandError::InvalidInput(err.to_string());
+        match (name, argument) {
+            ("pause", None) => Ok(Command::Pause(None)),
+            ("pause", Some(duration)) => idler_utils::parse_duration(duration)
+                .map(|duration| Command::Pause(Some(duration)))
+                .map_err(|err| invalid(&err)),
+            ("resume", None) => Ok(Command::Resume),
+            ("interval", Some(seconds)) => Ok(Command::Interval(seconds.parse()?)),
+            _ => Err(CommandError::InvalidInput(format!(
+                "Unknown command {command:?}, expected pause [DURATION], resume or interval SECONDS"
+            ))),
+        }
+    }
+}
+
+/// Runs one command from the command topic.
+fn run_command(context: &AppContext, command: &str) -> Result<(), CommandError> {
+    let parsed = Command::parse(command)?;
+    let service = context.running_idle_service()?;
+    match parsed {
+        Command::Pause(None) => service.pause(),
+        Command::Pause(Some(duration)) => service
+            .pause_for(duration)
+            .map_err(|err| CommandError::InvalidInput(err.to_string()))?,
+        Command::Resume => service.resume(),
+        Command::Interval(interval) => context.store(RegistryEntries::ForceInterval, &interval)?,
+    }
+    info!("Ran MQTT command {command:?}");
+    Ok(())
+}
+
+/// State payloads by topic name. The idle time is left out while it isn't tracked on this
+/// platform.
+fn payloads(status: &status::Status) -> Vec<(&'static str, String)> {
+    let active = matches!(status.mode, IdlerMode::Active | IdlerMode::TimedKeepAwake);
+    let mode = serde_json::to_value(status.mode)
+        .ok()
+        .and_then(|mode| mode.as_str().map(str::to_string))
+        .unwrap_or_default();
+    let mut payloads = vec![
+        ("active", if active { "ON" } else { "OFF" }.to_string()),
+        ("mode", mode),
+        ("last_injection", status.last_injection.clone()),
+        ("interval", status.force_interval.to_string()),
+    ];
+    if let Some(seconds) = status.idle_seconds {
+        payloads.push(("idle_seconds", seconds.to_string()));
+    }
+    payloads
+}
+
+/// Publishes the retained state whenever an event may have changed it and every
+/// [`PUBLISH_INTERVAL`], skipping the values the broker already has. Everything is sent again
+/// with the discovery configs after each connect.
+async fn run_publisher(
+    context: Arc<AppContext>,
+    client: AsyncClient,
+    topics: Topics,
+    connected: Arc<Notify>,
+) {
+    let mut subscription = context.events().subscribe("mqtt bridge");
+    let mut ticker = tokio::time::interval(PUBLISH_INTERVAL);
+    let mut published: HashMap<&'static str, String> = HashMap::new();
+    // Nothing is sent before the first connect, the client would only queue it.
+    connected.notified().await;
+    announce(&client, &topics).await;
+    loop {
+        let status = match status::current(&context) {
+            Ok(status) => Some(status),
+            Err(err) => {
+                debug!("Skipping MQTT state update, err: {err}");
+                None
+            }
+        };
+        for (name, payload) in status.as_ref().map(payloads).unwrap_or_default() {
+            if published.get(name) == Some(&payload) {
+                continue;
+            }
+            let topic = topics.state(name);
+            match client
+                .publish(&topic, QoS::AtLeastOnce, true, payload.clone())
+                .await
+            {
+                Ok(()) => {
+                    published.insert(name, payload);
+                }
+                Err(err) => error!("Failed to publish {topic}, err: {err}"),
+            }
+        }
+
+        tokio::select! {
+            event = subscription.next() => {
+                if event.is_none() {
+                    return;
+                }
+            }
+            _ = ticker.tick() => {}
+            () = connected.notified() => {
+                published.clear();
+                announce(&client, &topics).await;
+            }
+        }
+    }
+}
+
+/// Publishes the discovery configs and marks the idler online.
+async fn announce(client: &AsyncClient, topics: &Topics) {
+    for (topic, config) in topics.discovery() {
+        if let Err(err) = client
+            .publish(&topic, QoS::AtLeastOnce, true, config.to_string())
+            .await
+        {
+            error!("Failed to publish discovery config {topic}, err: {err}");
+        }
+    }
+    if let Err(err) = client
+        .publish(topics.availability(), QoS::AtLeastOnce, true, ONLINE)
+        .await
+    {
+        error!("Failed to publish availability, err: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(Command::parse("pause").unwrap(), Command::Pause(None));
+        assert_eq!(
+            Command::parse("pause 90s").unwrap(),
+            Command::Pause(Some(Duration::from_secs(90)))
+        );
+        assert_eq!(
+            Command::parse("pause   1h").unwrap(),
+            Command::Pause(Some(Duration::from_secs(3600)))
+        );
+        assert_eq!(Command::parse("resume").unwrap(), Command::Resume);
+        assert_eq!(
+            Command::parse("interval 120").unwrap(),
+            Command::Interval("120".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_commands() {
+        for command in [
+            "",
+            "Pause",
+            "pause soon",
+            "resume now",
+            "interval",
+            "interval 0",
+            "interval ten",
+            "exit",
+        ] {
+            assert!(
+                matches!(Command::parse(command), Err(CommandError::InvalidInput(_))),
+                "{command:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::{str::FromStr, sync::Arc};
+use tracing::{error, warn};
+
+use app_controller::AppContext;
+use event_bus::Event;
+use registry_ops::RegistryEntries;
+
+mod bridge;
+mod topics;
+
+use bridge::Bridge;
+
+/// The optional MQTT bridge, controlled by the `MqttEndpoint`, `MqttBroker` and `MqttTopic`
+/// settings.
+struct MqttEndpoint {
+    context: Arc<AppContext>,
+    bridge: Option<Bridge>,
+}
+
+/// Connects to the broker when the bridge is enabled in the settings, and follows the event
+/// bus to connect, disconnect or reconnect when the settings change.
+///
+/// # Panics
+///
+/// Panics if called outside of a tokio runtime.
+pub fn spawn(context: Arc<AppContext>) {
+    let mut subscription = context.events().subscribe("mqtt settings");
+    let enabled = context
+        .settings()
+        .get(RegistryEntries::MqttEndpoint)
+        .lock()
+        .is_ok_and(|setting| setting.is_enabled());
+    let mut endpoint = MqttEndpoint {
+        context,
+        bridge: None,
+    };
+    endpoint.set_enabled(enabled);
+
+    tokio::spawn(async move {
+        while let Some(event) = subscription.next().await {
+            endpoint.handle(&event);
+        }
+    });
+}
+
+impl MqttEndpoint {
+    fn handle(&mut self, event: &Event) {
+        if let Event::SettingChanged { setting, value } = event {
+            if *setting == RegistryEntries::MqttEndpoint.to_string() {
+                self.set_enabled(value == "Enabled");
+            } else if *setting == RegistryEntries::MqttBroker.to_string()
+                || *setting == RegistryEntries::MqttTopic.to_string()
+            {
+                self.restart();
+            }
+        }
+    }
+
+    /// Reconnects a running bridge, e.g. after the broker changed.
+    fn restart(&mut self) {
+        if self.bridge.is_some() {
+            self.set_enabled(false);
+            self.set_enabled(true);
+        }
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            if let Some(bridge) = self.bridge.take() {
+                bridge.stop();
+            }
+            return;
+        }
+        if self.bridge.is_some() {
+            return;
+        }
+
+        let (Some(broker), Some(topic)) = (
+            load(&self.context, RegistryEntries::MqttBroker),
+            load(&self.context, RegistryEntries::MqttTopic),
+        ) else {
+            return;
+        };
+        self.bridge = Some(Bridge::start(Arc::clone(&self.context), &broker, &topic));
+    }
+}
+
+fn load<T: FromStr<Err: std::fmt::Display>>(
+    context: &AppContext,
+    entry: RegistryEntries,
+) -> Option<T> {
+    let setting = match context.settings().get(entry).lock() {
+        Ok(setting) => setting,
+        Err(err) => {
+            error!("Failed to lock {entry}, err: {err}");
+            return None;
+        }
+    };
+    match setting.last_data.parse() {
+        Ok(value) => Some(value),
+        Err(err) => {
+            warn!("Found invalid {entry} {:?}, err: {err}", setting.last_data);
+            None
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use serde_json::{Value, json};
+
+use registry_ops::{MAX_FORCE_INTERVAL, MIN_FORCE_INTERVAL};
+
+/// Prefix Home Assistant subscribes to for discovery by default.
+pub(crate) const DISCOVERY_PREFIX: &str = "homeassistant";
+pub(crate) const ONLINE: &str = "online";
+pub(crate) const OFFLINE: &str = "offline";
+
+/// Topics under the base from the `MqttTopic` setting.
+#[derive(Debug, Clone)]
+pub(crate) struct Topics {
+    base: String,
+}
+
+impl Topics {
+    pub fn new(base: &str) -> Topics {
+        Topics {
+            base: base.to_string(),
+        }
+    }
+
+    /// `<base>/<name>`.
+    pub fn state(&self, name: &str) -> String {
+        format!("{}/{name}", self.base)
+    }
+
+    /// `online` while connected, `offline` as last will.
+    pub fn availability(&self) -> String {
+        self.state("availability")
+    }
+
+    /// Takes `pause`, `pause <duration>`, `resume` and `interval <seconds>`.
+    pub fn command(&self) -> String {
+        self.state("command")
+    }
+
+    /// Identifies this idler in client ids and discovery topics, so idlers with different
+    /// bases don't replace each other.
+    pub fn node_id(&self) -> String {
+        self.base
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect()
+    }
+
+    /// Retained Home Assistant discovery configs, by topic.
+    pub fn discovery(&self) -> Vec<(String, Value)> {
+        let node_id = self.node_id();
+        let device = json!({
+            "identifiers": [node_id],
+            "name": "Smart Idler",
+            "sw_version": env!("CARGO_PKG_VERSION"),
+        });
+        let entity = |component: &str, object_id: &str, mut config: Value| {
+            config["unique_id"] = json!(format!("{node_id}_{object_id}"));
+            config["availability_topic"] = json!(self.availability());
+            config["device"] = device.clone();
+            (
+                format!("{DISCOVERY_PREFIX}/{component}/{node_id}/{object_id}/config"),
+                config,
+            )
+        };
+        vec![
+            entity(
+                "switch",
+                "active",
+                json!({
+                    "name": "Keep awake",
+                    "icon": "mdi:coffee",
+                    "state_topic": self.state("active"),
+                    "command_topic": self.command(),
+                    "payload_on": "resume",
+                    "payload_off": "pause",
+                    "state_on": "ON",
+                    "state_off": "OFF",
+                }),
+            ),
+            entity(
+                "sensor",
+                "mode",
+                json!({
+                    "name": "Mode",
+                    "state_topic": self.state("mode"),
+                    "device_class": "enum",
+                    "options": ["active", "paused", "timed_pause", "timed_keep_awake"],
+                }),
+            ),
+            entity(
+                "sensor",
+                "idle_seconds",
+                json!({
+                    "name": "Idle time",
+                    "state_topic": self.state("idle_seconds"),
+                    "device_class": "duration",
+                    "unit_of_measurement": "s",
+                    "state_class": "measurement",
+                }),
+            ),
+            entity(
+                "sensor",
+                "last_injection",
+                json!({
+                    "name": "Last injection",
+                    "icon": "mdi:robot",
+                    "state_topic": self.state("last_injection"),
+                }),
+            ),
+            entity(
+                "number",
+                "interval",
+                json!({
+                    "name": "Force interval",
+                    "state_topic": self.state("interval"),
+                    "command_topic": self.command(),
+                    "command_template": "interval {{ value | int }}",
+                    "min": MIN_FORCE_INTERVAL,
+                    "max": MAX_FORCE_INTERVAL,
+                    "step": 1,
+                    "mode": "box",
+                    "unit_of_measurement": "s",
+                }),
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_topics_under_the_base() {
+        let topics = Topics::new("office/desk-1");
+        assert_eq!(topics.state("mode"), "office/desk-1/mode");
+        assert_eq!(topics.availability(), "office/desk-1/availability");
+        assert_eq!(topics.command(), "office/desk-1/command");
+        assert_eq!(topics.node_id(), "office_desk_1");
+    }
+
+    #[test]
+    fn describes_every_entity_for_discovery() {
+        let topics = Topics::new("office/desk");
+        let discovery = topics.discovery();
+        let names: Vec<&str> = discovery.iter().map(|(topic, _)| topic.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "homeassistant/switch/office_desk/active/config",
+                "homeassistant/sensor/office_desk/mode/config",
+                "homeassistant/sensor/office_desk/idle_seconds/config",
+                "homeassistant/sensor/office_desk/last_injection/config",
+                "homeassistant/number/office_desk/interval/config",
+            ]
+        );
+        for (topic, config) in &discovery {
+            let object_id = topic.rsplit('/').nth(1).unwrap();
+            assert_eq!(
+                config["unique_id"],
+                format!("office_desk_{object_id}"),
+                "{topic}"
+            );
+            assert_eq!(config["availability_topic"], "office/desk/availability");
+            assert_eq!(config["device"]["identifiers"], json!(["office_desk"]));
+            assert_eq!(config["state_topic"], format!("office/desk/{object_id}"));
+        }
+
+        let switch = &discovery[0].1;
+        assert_eq!(switch["command_topic"], "office/desk/command");
+        assert_eq!(switch["payload_on"], "resume");
+        assert_eq!(switch["payload_off"], "pause");
+        let interval = &discovery[4].1;
+        assert_eq!(interval["command_template"], "interval {{ value | int }}");
+        assert_eq!(interval["min"], MIN_FORCE_INTERVAL);
+        assert_eq!(interval["max"], MAX_FORCE_INTERVAL);
+    }
+}
@@ -0,0 +1,146 @@
+//! Runs the bridge against a real broker. Start one, e.g. `mosquitto -p 1883`, and run
+//! `cargo test -p mqtt_bridge -- --ignored`, with `MQTT_BROKER=host:port` for another address.
+
+use std::{
+    env, fs, process,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Packet, Publish, QoS};
+
+use app_controller::AppContext;
+use event_bus::EventBus;
+use registry_ops::{MqttBroker, RegistryEntries};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// State topics and discovery components the bridge retains.
+const STATES: [&str; 6] = [
+    "availability",
+    "active",
+    "mode",
+    "last_injection",
+    "interval",
+    "idle_seconds",
+];
+const ENTITIES: [(&str, &str); 5] = [
+    ("switch", "active"),
+    ("sensor", "mode"),
+    ("sensor", "idle_seconds"),
+    ("sensor", "last_injection"),
+    ("number", "interval"),
+];
+
+/// Polls `event_loop` until a message on `topic` matches `accept`.
+async fn expect(event_loop: &mut EventLoop, topic: &str, accept: impl Fn(&Publish) -> bool) {
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let Ok(event) = tokio::time::timeout(remaining, event_loop.poll()).await else {
+            panic!("Nothing expected was published on {topic}");
+        };
+        if let Event::Incoming(Packet::Publish(publish)) = event.expect("broker connection") {
+            if publish.topic == topic && accept(&publish) {
+                return;
+            }
+        }
+    }
+}
+
+async fn expect_payload(event_loop: &mut EventLoop, topic: &str, payload: &str) {
+    expect(event_loop, topic, |publish| {
+        publish.payload == payload.as_bytes()
+    })
+    .await;
+}
+
+/// Clears what the bridge retained under `base` and `node_id`.
+async fn clear_retained(
+    client: &AsyncClient,
+    event_loop: &mut EventLoop,
+    base: &str,
+    node_id: &str,
+) {
+    let states = STATES.iter().map(|name| format!("{base}/{name}"));
+    let configs = ENTITIES.iter().map(|(component, object_id)| {
+        format!("homeassistant/{component}/{node_id}/{object_id}/config")
+    });
+    for topic in states.chain(configs) {
+        client
+            .publish(topic, QoS::AtLeastOnce, true, "")
+            .await
+            .unwrap();
+    }
+    let _ = tokio::time::timeout(Duration::from_secs(1), async {
+        while event_loop.poll().await.is_ok() {}
+    })
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "needs an MQTT broker"]
+async fn publishes_state_and_takes_commands() {
+    let dir = env::temp_dir().join(format!("smart-idler-mqtt-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    env::set_var("SMART_IDLER_CONFIG", dir.join("settings.json"));
+    for variable in ["XDG_CONFIG_HOME", "XDG_DATA_HOME", "XDG_STATE_HOME"] {
+        env::set_var(variable, &dir);
+    }
+    let broker: MqttBroker = env::var("MQTT_BROKER")
+        .as_deref()
+        .unwrap_or("localhost")
+        .parse()
+        .unwrap();
+    let base = format!("smart-idler-test/{}", process::id());
+    let node_id = base.replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+    let command = format!("{base}/command");
+
+    let options = MqttOptions::new(
+        format!("smart-idler-test-{}", process::id()),
+        broker.host.clone(),
+        broker.port,
+    );
+    let (client, mut event_loop) = AsyncClient::new(options, 64);
+    let discovery = format!("homeassistant/switch/{node_id}/active/config");
+    for topic in [format!("{base}/#"), discovery.clone()] {
+        client.subscribe(topic, QoS::AtLeastOnce).await.unwrap();
+    }
+
+    let context = Arc::new(AppContext::new(EventBus::new()));
+    context.start_services();
+    context.store(RegistryEntries::MqttBroker, &broker).unwrap();
+    context.store(RegistryEntries::MqttTopic, &base).unwrap();
+    context
+        .store(RegistryEntries::MqttEndpoint, &"Enabled")
+        .unwrap();
+    mqtt_bridge::spawn(Arc::clone(&context));
+
+    expect_payload(&mut event_loop, &format!("{base}/availability"), "online").await;
+    expect(&mut event_loop, &discovery, |publish| {
+        let config: serde_json::Value = serde_json::from_slice(&publish.payload).unwrap();
+        config["command_topic"] == command.as_str()
+    })
+    .await;
+
+    for (payload, topic, state) in [
+        ("pause", "active", "OFF"),
+        ("resume", "active", "ON"),
+        ("interval 150", "interval", "150"),
+    ] {
+        client
+            .publish(&command, QoS::AtLeastOnce, false, payload)
+            .await
+            .unwrap();
+        expect_payload(&mut event_loop, &format!("{base}/{topic}"), state).await;
+    }
+
+    // Disabling the bridge marks the idler offline.
+    context
+        .store(RegistryEntries::MqttEndpoint, &"Disabled")
+        .unwrap();
+    expect_payload(&mut event_loop, &format!("{base}/availability"), "offline").await;
+
+    clear_retained(&client, &mut event_loop, &base, &node_id).await;
+    context.shutdown();
+    let _ = fs::remove_dir_all(&dir);
+}
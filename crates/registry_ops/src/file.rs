@@ -0,0 +1,174 @@
+use anyhow::{Result, anyhow};
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::{self, File, OpenOptions},
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    process,
+    sync::{LazyLock, Mutex},
+};
+use tracing::{debug, info};
+
+use crate::RegistryEntries;
+
+const APP_DIR: &str = "smart-idler";
+const SETTINGS_FILE: &str = "settings.json";
+
+/// Serializes the read-modify-write cycles of this process' threads, the `flock` of
+/// [`FileLock`] those of different processes.
+static FILE_LOCK: LazyLock<Mutex<()>> = LazyLock::new(|| Mutex::new(()));
+
+/// `$SMART_IDLER_CONFIG`, else `settings.json` in `$XDG_CONFIG_HOME/smart-idler` or
+/// `~/.config/smart-idler`.
+fn settings_path() -> PathBuf {
+    if let Some(path) = env::var_os("SMART_IDLER_CONFIG") {
+        return PathBuf::from(path);
+    }
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(env::temp_dir)
+        .join(APP_DIR)
+        .join(SETTINGS_FILE)
+}
+
+fn load(path: &Path) -> Result<BTreeMap<String, String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Exclusive `flock` on `settings.json.lock` next to the settings file, held for a whole
+/// read-modify-write cycle so the CLI and a running idler don't lose each other's updates.
+/// Closing the file releases it.
+struct FileLock {
+    _file: File,
+}
+
+impl FileLock {
+    fn acquire(path: &Path) -> Result<FileLock> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let lock_path = path.with_extension("json.lock");
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .map_err(|err| anyhow!("Failed to open {lock_path:?}, err: {err}"))?;
+        // SAFETY: the descriptor stays open for the duration of the call.
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            let err = std::io::Error::last_os_error();
+            return Err(anyhow!("Failed to lock {lock_path:?}, err: {err}"));
+        }
+        Ok(FileLock { _file: file })
+    }
+}
+
+fn read_from(path: &Path, entry: RegistryEntries) -> Result<String> {
+    let _guard = FILE_LOCK
+        .lock()
+        .map_err(|err| anyhow!("Failed to lock settings file, err: {err}"))?;
+    let _lock = FileLock::acquire(path)?;
+    let data = load(path)?
+        .remove(&entry.to_string())
+        .ok_or_else(|| anyhow!("No {entry} in {path:?}"))?;
+    debug!("Found data {data:#?} in {entry}");
+    Ok(data)
+}
+
+fn write_to(path: &Path, entry: RegistryEntries, data: &str) -> Result<()> {
+    let _guard = FILE_LOCK
+        .lock()
+        .map_err(|err| anyhow!("Failed to lock settings file, err: {err}"))?;
+    let _lock = FileLock::acquire(path)?;
+    let mut settings = load(path)?;
+    settings.insert(entry.to_string(), data.to_string());
+
+    // Unique per process, so a writer never replaces the half-written file of another.
+    let temporary = path.with_extension(format!("json.{}.tmp", process::id()));
+    fs::write(&temporary, serde_json::to_string_pretty(&settings)?)?;
+    if let Err(err) = fs::rename(&temporary, path) {
+        let _ = fs::remove_file(&temporary);
+        return Err(err.into());
+    }
+    info!("Set data {data:#?} in {entry}");
+    Ok(())
+}
+
+/// Reads `entry` from the settings file.
+pub(crate) fn read(entry: RegistryEntries) -> Result<String> {
+    read_from(&settings_path(), entry)
+}
+
+/// Writes `data` to `entry` in the settings file, replacing the file atomically.
+pub(crate) fn write(entry: RegistryEntries, data: &str) -> Result<()> {
+    write_to(&settings_path(), entry, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_path(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("smart-idler-file-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join(SETTINGS_FILE)
+    }
+
+    #[test]
+    fn keeps_concurrent_writes_of_every_entry() {
+        let path = test_path("concurrent");
+        let entries = [
+            RegistryEntries::ForceInterval,
+            RegistryEntries::ShutdownTime,
+            RegistryEntries::MqttTopic,
+            RegistryEntries::ApiPort,
+        ];
+        std::thread::scope(|scope| {
+            for (index, entry) in entries.into_iter().enumerate() {
+                let path = &path;
+                scope.spawn(move || {
+                    for round in 0..20 {
+                        write_to(path, entry, &format!("{index}-{round}")).unwrap();
+                    }
+                });
+            }
+        });
+        for (index, entry) in entries.into_iter().enumerate() {
+            assert_eq!(read_from(&path, entry).unwrap(), format!("{index}-19"));
+        }
+        let leftovers: Vec<_> = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name().to_string_lossy().ends_with(".tmp"))
+            .collect();
+        assert!(leftovers.is_empty(), "left {leftovers:?}");
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn excludes_other_open_file_descriptions() {
+        let path = test_path("flock");
+        let lock = FileLock::acquire(&path).unwrap();
+        // Another process opens its own description of the lock file, like this one does.
+        let other = File::open(path.with_extension("json.lock")).unwrap();
+        let try_lock = || unsafe { libc::flock(other.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+        assert_ne!(try_lock(), 0);
+        drop(lock);
+        assert_eq!(try_lock(), 0);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn reports_missing_entries() {
+        let path = test_path("missing");
+        write_to(&path, RegistryEntries::ApiPort, "8080").unwrap();
+        assert!(read_from(&path, RegistryEntries::MqttTopic).is_err());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -1,11 +1,38 @@
 use anyhow::Result;
 use chrono::Local;
 use serde::{Deserialize, Serialize};
-use std::fmt;
-use tracing::{debug, error, info, trace};
+use std::{fmt, str::FromStr};
+use tracing::trace;
+
+mod settings;
+mod store;
+mod weekly;
+
+pub use settings::{
+    ApiPort, ForceInterval, MAX_FORCE_INTERVAL, MIN_FORCE_INTERVAL, MQTT_DEFAULT_PORT, MetricsPort,
+    MqttBroker, MqttTopic, Permission, SHUTDOWN_STOPPED, STATISTICS_RETENTION_RANGE,
+    STOP_WARNING_RANGE, ScheduleAction, SettingError, ShutdownTime, StatisticsRetention,
+    StopWarnings, Toggle, validate,
+};
+pub use store::SettingsStore;
+pub use weekly::{Weekdays, WeeklyRule, WeeklySchedule};
+
+// Settings live in `HKLM` on Windows and in a JSON file elsewhere.
+#[cfg(not(windows))]
+mod file;
+#[cfg(not(windows))]
+use file as backend;
+#[cfg(windows)]
+mod registry;
+#[cfg(windows)]
+use registry as backend;
 
-const APP_SUBKEY: &str = "SOFTWARE\\SmartIdler";
 const SLEEP_TIME_SECONDS: u64 = 60;
+const STATISTICS_RETENTION_DAYS: u32 = 30;
+const METRICS_PORT: u16 = 9183;
+const API_PORT: u16 = 9184;
+const MQTT_BROKER: &str = "localhost:1883";
+const MQTT_TOPIC: &str = "smart-idler";
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub enum RegistryState {
@@ -29,12 +56,80 @@ impl From<RegistryState> for String {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug, Copy)]
+#[derive(Clone, Serialize, Deserialize, Debug, Copy, PartialEq, Eq)]
 pub enum RegistryEntries {
     ForceInterval,
     LastRobotInput,
     LogStatistics,
     ShutdownTime,
+    TimedMode,
+    StatisticsRetention,
+    MetricsEndpoint,
+    MetricsPort,
+    ControlOwnerPermission,
+    ControlOthersPermission,
+    ApiEndpoint,
+    ApiPort,
+    MqttEndpoint,
+    MqttBroker,
+    MqttTopic,
+    WeeklySchedule,
+    ScheduleAction,
+    StopWarnings,
+}
+
+impl RegistryEntries {
+    /// Every entry, in declaration order.
+    pub const ALL: [RegistryEntries; 18] = [
+        RegistryEntries::ForceInterval,
+        RegistryEntries::LastRobotInput,
+        RegistryEntries::LogStatistics,
+        RegistryEntries::ShutdownTime,
+        RegistryEntries::TimedMode,
+        RegistryEntries::StatisticsRetention,
+        RegistryEntries::MetricsEndpoint,
+        RegistryEntries::MetricsPort,
+        RegistryEntries::ControlOwnerPermission,
+        RegistryEntries::ControlOthersPermission,
+        RegistryEntries::ApiEndpoint,
+        RegistryEntries::ApiPort,
+        RegistryEntries::MqttEndpoint,
+        RegistryEntries::MqttBroker,
+        RegistryEntries::MqttTopic,
+        RegistryEntries::WeeklySchedule,
+        RegistryEntries::ScheduleAction,
+        RegistryEntries::StopWarnings,
+    ];
+
+    /// Entries set by the user, leaving out the state the app records at runtime and the
+    /// control policy, which only an administrator may change.
+    pub const SETTINGS: [RegistryEntries; 14] = [
+        RegistryEntries::ForceInterval,
+        RegistryEntries::LogStatistics,
+        RegistryEntries::ShutdownTime,
+        RegistryEntries::StatisticsRetention,
+        RegistryEntries::MetricsEndpoint,
+        RegistryEntries::MetricsPort,
+        RegistryEntries::ApiEndpoint,
+        RegistryEntries::ApiPort,
+        RegistryEntries::MqttEndpoint,
+        RegistryEntries::MqttBroker,
+        RegistryEntries::MqttTopic,
+        RegistryEntries::WeeklySchedule,
+        RegistryEntries::ScheduleAction,
+        RegistryEntries::StopWarnings,
+    ];
+}
+
+impl FromStr for RegistryEntries {
+    type Err = SettingError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        RegistryEntries::ALL
+            .into_iter()
+            .find(|entry| entry.to_string() == value)
+            .ok_or_else(|| SettingError::Unknown(value.to_string()))
+    }
 }
 
 impl fmt::Display for RegistryEntries {
@@ -44,29 +139,78 @@ impl fmt::Display for RegistryEntries {
             RegistryEntries::LastRobotInput => write!(f, "LastRobotInput"),
             RegistryEntries::LogStatistics => write!(f, "LogStatistics"),
             RegistryEntries::ShutdownTime => write!(f, "ShutdownTime"),
+            RegistryEntries::TimedMode => write!(f, "TimedMode"),
+            RegistryEntries::StatisticsRetention => write!(f, "StatisticsRetention"),
+            RegistryEntries::MetricsEndpoint => write!(f, "MetricsEndpoint"),
+            RegistryEntries::MetricsPort => write!(f, "MetricsPort"),
+            RegistryEntries::ControlOwnerPermission => write!(f, "ControlOwnerPermission"),
+            RegistryEntries::ControlOthersPermission => write!(f, "ControlOthersPermission"),
+            RegistryEntries::ApiEndpoint => write!(f, "ApiEndpoint"),
+            RegistryEntries::ApiPort => write!(f, "ApiPort"),
+            RegistryEntries::MqttEndpoint => write!(f, "MqttEndpoint"),
+            RegistryEntries::MqttBroker => write!(f, "MqttBroker"),
+            RegistryEntries::MqttTopic => write!(f, "MqttTopic"),
+            RegistryEntries::WeeklySchedule => write!(f, "WeeklySchedule"),
+            RegistryEntries::ScheduleAction => write!(f, "ScheduleAction"),
+            RegistryEntries::StopWarnings => write!(f, "StopWarnings"),
         }
     }
 }
 
+/// Where a setting is kept.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// The registry on Windows, the settings file elsewhere.
+    #[default]
+    System,
+    /// Only in memory, starting from the default, so isolated instances can run side by
+    /// side, e.g. in tests.
+    Memory,
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct RegistrySetting {
     pub registry_entry: RegistryEntries,
     pub last_data: String,
+    #[serde(skip)]
+    backend: Backend,
 }
 
 impl RegistrySetting {
+    /// Reads `entry` from the system backend, writing its default when it is missing.
     #[must_use]
     pub fn new(entry: &RegistryEntries) -> RegistrySetting {
+        RegistrySetting::with_backend(entry, Backend::System)
+    }
+
+    /// Reads `entry` from `backend`, writing its default when it is missing.
+    #[must_use]
+    pub fn with_backend(entry: &RegistryEntries, backend: Backend) -> RegistrySetting {
         let initial_data = match entry {
             RegistryEntries::ForceInterval => SLEEP_TIME_SECONDS.to_string(),
             RegistryEntries::LastRobotInput => get_current_time(),
-            RegistryEntries::LogStatistics => RegistryState::Disabled.to_string(),
+            RegistryEntries::LogStatistics
+            | RegistryEntries::TimedMode
+            | RegistryEntries::MetricsEndpoint
+            | RegistryEntries::ApiEndpoint
+            | RegistryEntries::MqttEndpoint => RegistryState::Disabled.to_string(),
             RegistryEntries::ShutdownTime => "18:00".to_string(),
+            RegistryEntries::StatisticsRetention => STATISTICS_RETENTION_DAYS.to_string(),
+            RegistryEntries::MetricsPort => METRICS_PORT.to_string(),
+            RegistryEntries::ApiPort => API_PORT.to_string(),
+            RegistryEntries::MqttBroker => MQTT_BROKER.to_string(),
+            RegistryEntries::MqttTopic => MQTT_TOPIC.to_string(),
+            RegistryEntries::WeeklySchedule => String::new(),
+            RegistryEntries::ScheduleAction => ScheduleAction::Exit.to_string(),
+            RegistryEntries::StopWarnings => "10,2".to_string(),
+            RegistryEntries::ControlOwnerPermission => Permission::Admin.to_string(),
+            RegistryEntries::ControlOthersPermission => Permission::None.to_string(),
         };
 
         let mut new_settings = RegistrySetting {
             registry_entry: *entry,
             last_data: initial_data.clone(),
+            backend,
         };
 
         let status = new_settings.update_local_from_registry();
@@ -83,33 +227,13 @@ impl RegistrySetting {
     ///
     /// # Errors
     ///
-    /// Returns an error if there is a problem opening the app key or getting the data from the registry.
+    /// Returns an error if the setting can't be read from the backend, e.g. when it was never
+    /// written.
     pub fn update_local_from_registry(&mut self) -> Result<String> {
-        let app_key = match windows_registry::LOCAL_MACHINE.open(APP_SUBKEY) {
-            Ok(e) => e,
-            Err(err) => {
-                error!("Failed to open app key with err {err:?}");
-                create_app_key();
-                return Err(err.into());
-            }
-        };
-        let data: String = match app_key.get_string(self.registry_entry.to_string()) {
-            Ok(data) => {
-                debug!(
-                    "Found data {data:#?} in {}",
-                    self.registry_entry.to_string()
-                );
-                data
-            }
-            Err(err) => {
-                error!(
-                    "Failed to get data from {}, with error {err:?}",
-                    &self.registry_entry
-                );
-                self.set_registry_data(self.last_data.clone())?;
-                return Err(err.into());
-            }
-        };
+        if self.backend == Backend::Memory {
+            return Ok(self.last_data.clone());
+        }
+        let data = backend::read(self.registry_entry)?;
         self.last_data.clone_from(&data);
         Ok(data)
     }
@@ -121,33 +245,13 @@ impl RegistrySetting {
     ///
     /// # Errors
     ///
-    /// Returns an error if there is a problem setting the data in the registry.
+    /// Returns an error if there is a problem setting the data in the backend.
     pub fn set_registry_data<T: Into<String>>(&mut self, new_data: T) -> Result<()> {
         let new_data = new_data.into();
-        let app_key = match windows_registry::LOCAL_MACHINE.create(APP_SUBKEY) {
-            Ok(e) => e,
-            Err(err) => {
-                error!("Failed to open app key: {APP_SUBKEY} with err {err:?}");
-                create_app_key();
-                return Err(err.into());
-            }
-        };
-        match app_key.set_string(&self.registry_entry.to_string(), &new_data) {
-            Ok(()) => {
-                info!(
-                    "Set data {new_data:#?} in {}",
-                    self.registry_entry.to_string()
-                );
-                self.last_data = new_data;
-            }
-            Err(err) => {
-                error!(
-                    "Failed to set data from {}, with error {err:?}",
-                    self.registry_entry.to_string()
-                );
-                return Err(err.into());
-            }
-        };
+        if self.backend == Backend::System {
+            backend::write(self.registry_entry, &new_data)?;
+        }
+        self.last_data = new_data;
         Ok(())
     }
 
@@ -158,13 +262,6 @@ impl RegistrySetting {
     }
 }
 
-fn create_app_key() {
-    match windows_registry::LOCAL_MACHINE.create(APP_SUBKEY) {
-        Ok(val) => info!("Created {APP_SUBKEY}, val: {val:?}"),
-        Err(err) => error!("Failed to create {APP_SUBKEY} with err: {err}"),
-    }
-}
-
 #[must_use]
 pub fn get_current_time() -> String {
     Local::now().format("%H:%M:%S").to_string()
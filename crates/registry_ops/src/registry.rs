@@ -0,0 +1,57 @@
+use anyhow::Result;
+use tracing::{debug, error, info};
+
+use crate::RegistryEntries;
+
+const APP_SUBKEY: &str = "SOFTWARE\\SmartIdler";
+
+/// Reads `entry` from the app key in `HKLM`.
+pub(crate) fn read(entry: RegistryEntries) -> Result<String> {
+    let app_key = match windows_registry::LOCAL_MACHINE.open(APP_SUBKEY) {
+        Ok(e) => e,
+        Err(err) => {
+            error!("Failed to open app key with err {err:?}");
+            create_app_key();
+            return Err(err.into());
+        }
+    };
+    match app_key.get_string(entry.to_string()) {
+        Ok(data) => {
+            debug!("Found data {data:#?} in {entry}");
+            Ok(data)
+        }
+        Err(err) => {
+            error!("Failed to get data from {entry}, with error {err:?}");
+            Err(err.into())
+        }
+    }
+}
+
+/// Writes `data` to `entry` in the app key in `HKLM`.
+pub(crate) fn write(entry: RegistryEntries, data: &str) -> Result<()> {
+    let app_key = match windows_registry::LOCAL_MACHINE.create(APP_SUBKEY) {
+        Ok(e) => e,
+        Err(err) => {
+            error!("Failed to open app key: {APP_SUBKEY} with err {err:?}");
+            create_app_key();
+            return Err(err.into());
+        }
+    };
+    match app_key.set_string(&entry.to_string(), data) {
+        Ok(()) => {
+            info!("Set data {data:#?} in {entry}");
+            Ok(())
+        }
+        Err(err) => {
+            error!("Failed to set data from {entry}, with error {err:?}");
+            Err(err.into())
+        }
+    }
+}
+
+fn create_app_key() {
+    match windows_registry::LOCAL_MACHINE.create(APP_SUBKEY) {
+        Ok(val) => info!("Created {APP_SUBKEY}, val: {val:?}"),
+        Err(err) => error!("Failed to create {APP_SUBKEY} with err: {err}"),
+    }
+}
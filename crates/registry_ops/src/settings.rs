@@ -0,0 +1,749 @@
+use chrono::NaiveTime;
+use std::{fmt, ops::RangeInclusive, str::FromStr};
+
+use crate::{RegistryEntries, RegistryState, WeeklySchedule};
+
+/// Shortest accepted `ForceInterval`, in seconds.
+pub const MIN_FORCE_INTERVAL: u64 = 60;
+/// Longest accepted `ForceInterval`, in seconds (one day).
+pub const MAX_FORCE_INTERVAL: u64 = 24 * 60 * 60;
+/// Accepted `StatisticsRetention`, in days.
+pub const STATISTICS_RETENTION_RANGE: RangeInclusive<u32> = 1..=3650;
+/// Minutes before the stop time a warning may be given.
+pub const STOP_WARNING_RANGE: RangeInclusive<u32> = 1..=720;
+/// Value of `ShutdownTime` while no shutdown is scheduled.
+pub const SHUTDOWN_STOPPED: &str = "STOP";
+const SHUTDOWN_TIME_FORMAT: &str = "%H:%M";
+
+/// A value rejected before being written to the registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SettingError {
+    /// The value can't be parsed for the entry.
+    Invalid {
+        entry: RegistryEntries,
+        value: String,
+        expected: &'static str,
+    },
+    /// The value parses but is outside of the accepted range.
+    OutOfRange {
+        entry: RegistryEntries,
+        value: String,
+        min: u64,
+        max: u64,
+    },
+    /// No setting is known by that key.
+    Unknown(String),
+}
+
+impl fmt::Display for SettingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingError::Invalid {
+                entry,
+                value,
+                expected,
+            } => write!(f, "Invalid {entry} {value:?}, expected {expected}"),
+            SettingError::OutOfRange {
+                entry,
+                value,
+                min,
+                max,
+            } => write!(
+                f,
+                "{entry} {value} is out of range, expected {min} to {max}"
+            ),
+            SettingError::Unknown(key) => write!(f, "Unknown setting {key:?}"),
+        }
+    }
+}
+
+impl std::error::Error for SettingError {}
+
+fn parse_number<T: FromStr>(entry: RegistryEntries, value: &str) -> Result<T, SettingError> {
+    value.trim().parse().map_err(|_| SettingError::Invalid {
+        entry,
+        value: value.to_string(),
+        expected: "a whole number",
+    })
+}
+
+fn check_range<T>(
+    entry: RegistryEntries,
+    value: T,
+    range: &RangeInclusive<T>,
+) -> Result<T, SettingError>
+where
+    T: PartialOrd + Into<u64> + Copy + fmt::Display,
+{
+    if !range.contains(&value) {
+        return Err(SettingError::OutOfRange {
+            entry,
+            value: value.to_string(),
+            min: (*range.start()).into(),
+            max: (*range.end()).into(),
+        });
+    }
+    Ok(value)
+}
+
+/// Seconds of idle time after which input is injected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ForceInterval(u64);
+
+impl ForceInterval {
+    #[must_use]
+    pub fn seconds(self) -> u64 {
+        self.0
+    }
+}
+
+impl FromStr for ForceInterval {
+    type Err = SettingError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let seconds: u64 = parse_number(RegistryEntries::ForceInterval, value)?;
+        ForceInterval::try_from(seconds)
+    }
+}
+
+impl TryFrom<u64> for ForceInterval {
+    type Error = SettingError;
+
+    fn try_from(seconds: u64) -> Result<Self, Self::Error> {
+        check_range(
+            RegistryEntries::ForceInterval,
+            seconds,
+            &(MIN_FORCE_INTERVAL..=MAX_FORCE_INTERVAL),
+        )
+        .map(ForceInterval)
+    }
+}
+
+impl fmt::Display for ForceInterval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Days of statistics kept on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatisticsRetention(u32);
+
+impl StatisticsRetention {
+    #[must_use]
+    pub fn days(self) -> u32 {
+        self.0
+    }
+}
+
+impl FromStr for StatisticsRetention {
+    type Err = SettingError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let days: u32 = parse_number(RegistryEntries::StatisticsRetention, value)?;
+        StatisticsRetention::try_from(days)
+    }
+}
+
+impl TryFrom<u32> for StatisticsRetention {
+    type Error = SettingError;
+
+    fn try_from(days: u32) -> Result<Self, Self::Error> {
+        check_range(
+            RegistryEntries::StatisticsRetention,
+            days,
+            &STATISTICS_RETENTION_RANGE,
+        )
+        .map(StatisticsRetention)
+    }
+}
+
+impl fmt::Display for StatisticsRetention {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Port of the local metrics endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetricsPort(u16);
+
+impl MetricsPort {
+    #[must_use]
+    pub fn port(self) -> u16 {
+        self.0
+    }
+}
+
+impl FromStr for MetricsPort {
+    type Err = SettingError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let port: u16 = parse_number(RegistryEntries::MetricsPort, value)?;
+        MetricsPort::try_from(port)
+    }
+}
+
+impl TryFrom<u16> for MetricsPort {
+    type Error = SettingError;
+
+    fn try_from(port: u16) -> Result<Self, Self::Error> {
+        check_range(RegistryEntries::MetricsPort, port, &(1..=u16::MAX)).map(MetricsPort)
+    }
+}
+
+impl fmt::Display for MetricsPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Port of the local REST API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApiPort(u16);
+
+impl ApiPort {
+    #[must_use]
+    pub fn port(self) -> u16 {
+        self.0
+    }
+}
+
+impl FromStr for ApiPort {
+    type Err = SettingError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let port: u16 = parse_number(RegistryEntries::ApiPort, value)?;
+        ApiPort::try_from(port)
+    }
+}
+
+impl TryFrom<u16> for ApiPort {
+    type Error = SettingError;
+
+    fn try_from(port: u16) -> Result<Self, Self::Error> {
+        check_range(RegistryEntries::ApiPort, port, &(1..=u16::MAX)).map(ApiPort)
+    }
+}
+
+impl fmt::Display for ApiPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Default port of MQTT brokers, used when `MqttBroker` has none.
+pub const MQTT_DEFAULT_PORT: u16 = 1883;
+
+/// Address of the MQTT broker, `host` or `host:port`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MqttBroker {
+    pub host: String,
+    pub port: u16,
+}
+
+impl FromStr for MqttBroker {
+    type Err = SettingError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || SettingError::Invalid {
+            entry: RegistryEntries::MqttBroker,
+            value: value.to_string(),
+            expected: "host or host:port",
+        };
+        let value = value.trim();
+        let (host, port) = match value.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+            None => (value, MQTT_DEFAULT_PORT),
+        };
+        if host.is_empty() || port == 0 || host.contains(char::is_whitespace) {
+            return Err(invalid());
+        }
+        Ok(MqttBroker {
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+impl fmt::Display for MqttBroker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.host, self.port)
+    }
+}
+
+/// Base of the MQTT topics, e.g. `smart-idler` or `office/desk`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MqttTopic(String);
+
+impl MqttTopic {
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for MqttTopic {
+    type Err = SettingError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        if value.is_empty()
+            || value.starts_with('/')
+            || value.ends_with('/')
+            || value.contains(['+', '#'])
+        {
+            return Err(SettingError::Invalid {
+                entry: RegistryEntries::MqttTopic,
+                value: value.to_string(),
+                expected: "a topic without wildcards or leading and trailing slashes",
+            });
+        }
+        Ok(MqttTopic(value.to_string()))
+    }
+}
+
+impl fmt::Display for MqttTopic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// What a local client may do over the control endpoint, each level including the ones
+/// before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Permission {
+    /// Every request is refused.
+    None,
+    /// Status and events.
+    Read,
+    /// Pausing, resuming, timers, the stop time and exiting.
+    Control,
+    /// Changing the settings.
+    Admin,
+}
+
+impl Permission {
+    /// Parses the value stored in one of the control policy `entry`s.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SettingError::Invalid`] if `value` names no permission.
+    pub fn parse(entry: RegistryEntries, value: &str) -> Result<Permission, SettingError> {
+        match value.trim() {
+            "None" => Ok(Permission::None),
+            "Read" => Ok(Permission::Read),
+            "Control" => Ok(Permission::Control),
+            "Admin" => Ok(Permission::Admin),
+            _ => Err(SettingError::Invalid {
+                entry,
+                value: value.to_string(),
+                expected: "None, Read, Control or Admin",
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Permission::None => write!(f, "None"),
+            Permission::Read => write!(f, "Read"),
+            Permission::Control => write!(f, "Control"),
+            Permission::Admin => write!(f, "Admin"),
+        }
+    }
+}
+
+/// Daily shutdown time, or [`SHUTDOWN_STOPPED`] when none is scheduled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownTime {
+    Stopped,
+    At(NaiveTime),
+}
+
+impl ShutdownTime {
+    #[must_use]
+    pub fn time(self) -> Option<NaiveTime> {
+        match self {
+            ShutdownTime::Stopped => None,
+            ShutdownTime::At(time) => Some(time),
+        }
+    }
+}
+
+impl FromStr for ShutdownTime {
+    type Err = SettingError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        if value == SHUTDOWN_STOPPED {
+            return Ok(ShutdownTime::Stopped);
+        }
+        NaiveTime::parse_from_str(value, SHUTDOWN_TIME_FORMAT)
+            .map(ShutdownTime::At)
+            .map_err(|_| SettingError::Invalid {
+                entry: RegistryEntries::ShutdownTime,
+                value: value.to_string(),
+                expected: "HH:MM or STOP",
+            })
+    }
+}
+
+impl fmt::Display for ShutdownTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShutdownTime::Stopped => write!(f, "{SHUTDOWN_STOPPED}"),
+            ShutdownTime::At(time) => write!(f, "{}", time.format(SHUTDOWN_TIME_FORMAT)),
+        }
+    }
+}
+
+/// What happens once the daily stop time is reached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleAction {
+    /// Exit the app, the behaviour before the action could be chosen.
+    Exit,
+    /// Stop keeping the system awake, keeping the app running.
+    Pause,
+    Lock,
+    Sleep,
+    Hibernate,
+    /// Run the command line through the shell.
+    Run(String),
+}
+
+impl FromStr for ScheduleAction {
+    type Err = SettingError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        let (name, command) = value
+            .split_once(char::is_whitespace)
+            .map_or((value, ""), |(name, command)| (name, command.trim()));
+        match (name.to_lowercase().as_str(), command) {
+            ("exit", "") => Ok(ScheduleAction::Exit),
+            ("pause", "") => Ok(ScheduleAction::Pause),
+            ("lock", "") => Ok(ScheduleAction::Lock),
+            ("sleep", "") => Ok(ScheduleAction::Sleep),
+            ("hibernate", "") => Ok(ScheduleAction::Hibernate),
+            ("run", command) if !command.is_empty() => Ok(ScheduleAction::Run(command.to_string())),
+            _ => Err(SettingError::Invalid {
+                entry: RegistryEntries::ScheduleAction,
+                value: value.to_string(),
+                expected: "exit, pause, lock, sleep, hibernate or run COMMAND",
+            }),
+        }
+    }
+}
+
+impl fmt::Display for ScheduleAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScheduleAction::Exit => write!(f, "exit"),
+            ScheduleAction::Pause => write!(f, "pause"),
+            ScheduleAction::Lock => write!(f, "lock"),
+            ScheduleAction::Sleep => write!(f, "sleep"),
+            ScheduleAction::Hibernate => write!(f, "hibernate"),
+            ScheduleAction::Run(command) => write!(f, "run {command}"),
+        }
+    }
+}
+
+/// Minutes before the stop time to warn at, e.g. `10,2`, or `none`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StopWarnings(Vec<u32>);
+
+impl StopWarnings {
+    /// The lead times, the earliest warning first.
+    #[must_use]
+    pub fn minutes(&self) -> &[u32] {
+        &self.0
+    }
+}
+
+impl FromStr for StopWarnings {
+    type Err = SettingError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let entry = RegistryEntries::StopWarnings;
+        if value.trim().is_empty() || value.trim().eq_ignore_ascii_case("none") {
+            return Ok(StopWarnings::default());
+        }
+        let mut minutes = value
+            .split(',')
+            .map(|minutes| check_range(entry, parse_number(entry, minutes)?, &STOP_WARNING_RANGE))
+            .collect::<Result<Vec<u32>, _>>()?;
+        minutes.sort_unstable_by(|a, b| b.cmp(a));
+        minutes.dedup();
+        Ok(StopWarnings(minutes))
+    }
+}
+
+impl fmt::Display for StopWarnings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "none");
+        }
+        let minutes: Vec<String> = self.0.iter().map(ToString::to_string).collect();
+        write!(f, "{}", minutes.join(","))
+    }
+}
+
+/// Settings that are switched on and off from the UI, by their UI key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Toggle {
+    Logging,
+    Metrics,
+    Api,
+    Mqtt,
+}
+
+impl Toggle {
+    #[must_use]
+    pub fn registry_entry(self) -> RegistryEntries {
+        match self {
+            Toggle::Logging => RegistryEntries::LogStatistics,
+            Toggle::Metrics => RegistryEntries::MetricsEndpoint,
+            Toggle::Api => RegistryEntries::ApiEndpoint,
+            Toggle::Mqtt => RegistryEntries::MqttEndpoint,
+        }
+    }
+}
+
+impl FromStr for Toggle {
+    type Err = SettingError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "logging" => Ok(Toggle::Logging),
+            "metrics" => Ok(Toggle::Metrics),
+            "api" => Ok(Toggle::Api),
+            "mqtt" => Ok(Toggle::Mqtt),
+            _ => Err(SettingError::Unknown(value.to_string())),
+        }
+    }
+}
+
+impl From<bool> for RegistryState {
+    fn from(enabled: bool) -> Self {
+        if enabled {
+            RegistryState::Enabled
+        } else {
+            RegistryState::Disabled
+        }
+    }
+}
+
+/// Checks that `value` can be stored in `entry`. The runtime state entries accept anything.
+///
+/// # Errors
+///
+/// Returns the [`SettingError`] the matching typed setting would return.
+pub fn validate(entry: RegistryEntries, value: &str) -> Result<(), SettingError> {
+    match entry {
+        RegistryEntries::ForceInterval => value.parse::<ForceInterval>().map(drop),
+        RegistryEntries::StatisticsRetention => value.parse::<StatisticsRetention>().map(drop),
+        RegistryEntries::MetricsPort => value.parse::<MetricsPort>().map(drop),
+        RegistryEntries::ApiPort => value.parse::<ApiPort>().map(drop),
+        RegistryEntries::MqttBroker => value.parse::<MqttBroker>().map(drop),
+        RegistryEntries::MqttTopic => value.parse::<MqttTopic>().map(drop),
+        RegistryEntries::ShutdownTime => value.parse::<ShutdownTime>().map(drop),
+        RegistryEntries::WeeklySchedule => value.parse::<WeeklySchedule>().map(drop),
+        RegistryEntries::ScheduleAction => value.parse::<ScheduleAction>().map(drop),
+        RegistryEntries::StopWarnings => value.parse::<StopWarnings>().map(drop),
+        RegistryEntries::ControlOwnerPermission | RegistryEntries::ControlOthersPermission => {
+            Permission::parse(entry, value).map(drop)
+        }
+        RegistryEntries::LogStatistics
+        | RegistryEntries::MetricsEndpoint
+        | RegistryEntries::ApiEndpoint
+        | RegistryEntries::MqttEndpoint => {
+            if value == RegistryState::Enabled.to_string()
+                || value == RegistryState::Disabled.to_string()
+            {
+                Ok(())
+            } else {
+                Err(SettingError::Invalid {
+                    entry,
+                    value: value.to_string(),
+                    expected: "Enabled or Disabled",
+                })
+            }
+        }
+        RegistryEntries::LastRobotInput | RegistryEntries::TimedMode => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_force_interval_in_range() {
+        assert_eq!("60".parse::<ForceInterval>().unwrap().seconds(), 60);
+        assert_eq!(
+            " 86400 ".parse::<ForceInterval>().unwrap().seconds(),
+            86_400
+        );
+        assert_eq!(
+            "59".parse::<ForceInterval>(),
+            Err(SettingError::OutOfRange {
+                entry: RegistryEntries::ForceInterval,
+                value: "59".to_string(),
+                min: MIN_FORCE_INTERVAL,
+                max: MAX_FORCE_INTERVAL,
+            })
+        );
+        assert!(matches!(
+            "1m".parse::<ForceInterval>(),
+            Err(SettingError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn parses_retention_and_ports() {
+        assert_eq!("30".parse::<StatisticsRetention>().unwrap().days(), 30);
+        assert!("0".parse::<StatisticsRetention>().is_err());
+        assert!("3651".parse::<StatisticsRetention>().is_err());
+        assert_eq!("9090".parse::<MetricsPort>().unwrap().port(), 9090);
+        assert!("0".parse::<MetricsPort>().is_err());
+        assert!("65536".parse::<MetricsPort>().is_err());
+    }
+
+    #[test]
+    fn parses_shutdown_time() {
+        let time = NaiveTime::from_hms_opt(18, 5, 0).unwrap();
+        assert_eq!("18:05".parse(), Ok(ShutdownTime::At(time)));
+        assert_eq!(" STOP ".parse(), Ok(ShutdownTime::Stopped));
+        assert_eq!(ShutdownTime::At(time).to_string(), "18:05");
+        assert_eq!(ShutdownTime::Stopped.to_string(), SHUTDOWN_STOPPED);
+        assert_eq!(ShutdownTime::Stopped.time(), None);
+        for value in ["", "stop", "24:00", "18", "18:60", "6pm"] {
+            assert!(
+                value.parse::<ShutdownTime>().is_err(),
+                "{value:?} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn parses_toggles() {
+        assert_eq!(
+            "api".parse::<Toggle>().unwrap().registry_entry(),
+            RegistryEntries::ApiEndpoint
+        );
+        assert_eq!(
+            "Logging".parse::<Toggle>(),
+            Err(SettingError::Unknown("Logging".to_string()))
+        );
+    }
+
+    #[test]
+    fn validates_by_entry() {
+        assert!(validate(RegistryEntries::ForceInterval, "120").is_ok());
+        assert!(validate(RegistryEntries::ForceInterval, "10").is_err());
+        assert!(validate(RegistryEntries::LogStatistics, "Enabled").is_ok());
+        assert!(validate(RegistryEntries::LogStatistics, "on").is_err());
+        assert!(validate(RegistryEntries::LastRobotInput, "anything").is_ok());
+    }
+
+    #[test]
+    fn orders_permissions_by_what_they_include() {
+        assert!(Permission::None < Permission::Read);
+        assert!(Permission::Read < Permission::Control);
+        assert!(Permission::Control < Permission::Admin);
+        let entry = RegistryEntries::ControlOthersPermission;
+        for permission in [
+            Permission::None,
+            Permission::Read,
+            Permission::Control,
+            Permission::Admin,
+        ] {
+            assert_eq!(
+                Permission::parse(entry, &permission.to_string()),
+                Ok(permission)
+            );
+        }
+        assert!(Permission::parse(entry, "admin").is_err());
+        assert!(validate(entry, "Root").is_err());
+    }
+
+    #[test]
+    fn parses_mqtt_brokers() {
+        let broker: MqttBroker = "broker.lan".parse().unwrap();
+        assert_eq!(
+            (broker.host.as_str(), broker.port),
+            ("broker.lan", MQTT_DEFAULT_PORT)
+        );
+        let broker: MqttBroker = " 10.0.0.2:8883 ".parse().unwrap();
+        assert_eq!((broker.host.as_str(), broker.port), ("10.0.0.2", 8883));
+        assert_eq!(broker.to_string(), "10.0.0.2:8883");
+        for value in [
+            "",
+            ":1883",
+            "broker:0",
+            "broker:65536",
+            "broker:port",
+            "my broker",
+        ] {
+            assert!(value.parse::<MqttBroker>().is_err(), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn parses_mqtt_topics() {
+        let topic: MqttTopic = " office/desk ".parse().unwrap();
+        assert_eq!(topic.as_str(), "office/desk");
+        assert_eq!(topic.to_string(), "office/desk");
+        for value in ["", "/office", "office/", "office/+", "office/#"] {
+            assert!(value.parse::<MqttTopic>().is_err(), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn parses_schedule_actions() {
+        assert_eq!(" Sleep ".parse(), Ok(ScheduleAction::Sleep));
+        assert_eq!("exit".parse(), Ok(ScheduleAction::Exit));
+        let run: ScheduleAction = "run  backup.sh --quiet ".parse().unwrap();
+        assert_eq!(run, ScheduleAction::Run("backup.sh --quiet".to_string()));
+        assert_eq!(run.to_string(), "run backup.sh --quiet");
+        for action in [
+            ScheduleAction::Exit,
+            ScheduleAction::Pause,
+            ScheduleAction::Lock,
+            ScheduleAction::Sleep,
+            ScheduleAction::Hibernate,
+            run,
+        ] {
+            assert_eq!(action.to_string().parse(), Ok(action));
+        }
+        for value in ["", "run", "run  ", "lock now", "reboot"] {
+            assert!(value.parse::<ScheduleAction>().is_err(), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn parses_stop_warnings_earliest_first() {
+        let warnings: StopWarnings = " 2, 10,2 ,30".parse().unwrap();
+        assert_eq!(warnings.minutes(), [30, 10, 2]);
+        assert_eq!(warnings.to_string(), "30,10,2");
+        assert_eq!(warnings.to_string().parse(), Ok(warnings));
+        for none in ["", " ", "none", "NONE"] {
+            assert_eq!(none.parse(), Ok(StopWarnings::default()), "{none:?}");
+        }
+        assert_eq!(StopWarnings::default().to_string(), "none");
+        assert_eq!(
+            "10,0".parse::<StopWarnings>(),
+            Err(SettingError::OutOfRange {
+                entry: RegistryEntries::StopWarnings,
+                value: "0".to_string(),
+                min: u64::from(*STOP_WARNING_RANGE.start()),
+                max: u64::from(*STOP_WARNING_RANGE.end()),
+            })
+        );
+        for value in ["721", "10,", "ten", "-5"] {
+            assert!(value.parse::<StopWarnings>().is_err(), "{value:?}");
+        }
+    }
+}
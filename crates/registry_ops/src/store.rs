@@ -0,0 +1,111 @@
+use std::sync::Mutex;
+
+use crate::{Backend, RegistryEntries, RegistrySetting};
+
+/// Every registry setting of the app, read from the registry once when the store is loaded.
+#[derive(Debug)]
+pub struct SettingsStore {
+    force_interval: Mutex<RegistrySetting>,
+    last_robot_input: Mutex<RegistrySetting>,
+    log_statistics: Mutex<RegistrySetting>,
+    shutdown_time: Mutex<RegistrySetting>,
+    timed_mode: Mutex<RegistrySetting>,
+    statistics_retention: Mutex<RegistrySetting>,
+    metrics_endpoint: Mutex<RegistrySetting>,
+    metrics_port: Mutex<RegistrySetting>,
+    control_owner_permission: Mutex<RegistrySetting>,
+    control_others_permission: Mutex<RegistrySetting>,
+    api_endpoint: Mutex<RegistrySetting>,
+    api_port: Mutex<RegistrySetting>,
+    mqtt_endpoint: Mutex<RegistrySetting>,
+    mqtt_broker: Mutex<RegistrySetting>,
+    mqtt_topic: Mutex<RegistrySetting>,
+    weekly_schedule: Mutex<RegistrySetting>,
+    schedule_action: Mutex<RegistrySetting>,
+    stop_warnings: Mutex<RegistrySetting>,
+}
+
+impl SettingsStore {
+    /// Reads every setting, writing the default of the missing ones to the registry.
+    #[must_use]
+    pub fn load() -> SettingsStore {
+        SettingsStore::with_backend(Backend::System)
+    }
+
+    /// Every setting at its default, kept in memory only. Stores made this way don't see each
+    /// other's changes, nor those of the registry.
+    #[must_use]
+    pub fn in_memory() -> SettingsStore {
+        SettingsStore::with_backend(Backend::Memory)
+    }
+
+    fn with_backend(backend: Backend) -> SettingsStore {
+        let load = |entry| Mutex::new(RegistrySetting::with_backend(&entry, backend));
+        SettingsStore {
+            force_interval: load(RegistryEntries::ForceInterval),
+            last_robot_input: load(RegistryEntries::LastRobotInput),
+            log_statistics: load(RegistryEntries::LogStatistics),
+            shutdown_time: load(RegistryEntries::ShutdownTime),
+            timed_mode: load(RegistryEntries::TimedMode),
+            statistics_retention: load(RegistryEntries::StatisticsRetention),
+            metrics_endpoint: load(RegistryEntries::MetricsEndpoint),
+            metrics_port: load(RegistryEntries::MetricsPort),
+            control_owner_permission: load(RegistryEntries::ControlOwnerPermission),
+            control_others_permission: load(RegistryEntries::ControlOthersPermission),
+            api_endpoint: load(RegistryEntries::ApiEndpoint),
+            api_port: load(RegistryEntries::ApiPort),
+            mqtt_endpoint: load(RegistryEntries::MqttEndpoint),
+            mqtt_broker: load(RegistryEntries::MqttBroker),
+            mqtt_topic: load(RegistryEntries::MqttTopic),
+            weekly_schedule: load(RegistryEntries::WeeklySchedule),
+            schedule_action: load(RegistryEntries::ScheduleAction),
+            stop_warnings: load(RegistryEntries::StopWarnings),
+        }
+    }
+
+    #[must_use]
+    pub fn get(&self, entry: RegistryEntries) -> &Mutex<RegistrySetting> {
+        match entry {
+            RegistryEntries::ForceInterval => &self.force_interval,
+            RegistryEntries::LastRobotInput => &self.last_robot_input,
+            RegistryEntries::LogStatistics => &self.log_statistics,
+            RegistryEntries::ShutdownTime => &self.shutdown_time,
+            RegistryEntries::TimedMode => &self.timed_mode,
+            RegistryEntries::StatisticsRetention => &self.statistics_retention,
+            RegistryEntries::MetricsEndpoint => &self.metrics_endpoint,
+            RegistryEntries::MetricsPort => &self.metrics_port,
+            RegistryEntries::ControlOwnerPermission => &self.control_owner_permission,
+            RegistryEntries::ControlOthersPermission => &self.control_others_permission,
+            RegistryEntries::ApiEndpoint => &self.api_endpoint,
+            RegistryEntries::ApiPort => &self.api_port,
+            RegistryEntries::MqttEndpoint => &self.mqtt_endpoint,
+            RegistryEntries::MqttBroker => &self.mqtt_broker,
+            RegistryEntries::MqttTopic => &self.mqtt_topic,
+            RegistryEntries::WeeklySchedule => &self.weekly_schedule,
+            RegistryEntries::ScheduleAction => &self.schedule_action,
+            RegistryEntries::StopWarnings => &self.stop_warnings,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_in_memory_stores_apart() {
+        let first = SettingsStore::in_memory();
+        let second = SettingsStore::in_memory();
+        let entry = RegistryEntries::ForceInterval;
+        first
+            .get(entry)
+            .lock()
+            .unwrap()
+            .set_registry_data("120")
+            .unwrap();
+        assert_eq!(first.get(entry).lock().unwrap().last_data, "120");
+        assert_eq!(second.get(entry).lock().unwrap().last_data, "60");
+        let mut reloaded = first.get(entry).lock().unwrap().clone();
+        assert_eq!(reloaded.update_local_from_registry().unwrap(), "120");
+    }
+}
@@ -0,0 +1,301 @@
+use std::{fmt, str::FromStr};
+
+use chrono::{NaiveTime, Weekday};
+
+use crate::{RegistryEntries, SettingError};
+
+const TIME_FORMAT: &str = "%H:%M";
+const RULE_SEPARATOR: char = ';';
+const EXPECTED_RULE: &str = "rules like `mon-thu 08:30-17:30` separated by `;`";
+
+fn invalid(value: &str, expected: &'static str) -> SettingError {
+    SettingError::Invalid {
+        entry: RegistryEntries::WeeklySchedule,
+        value: value.to_string(),
+        expected,
+    }
+}
+
+/// Days a rule applies to, e.g. `mon-thu,sat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Weekdays(u8);
+
+impl Weekdays {
+    fn bit(day: Weekday) -> u8 {
+        1 << day.num_days_from_monday()
+    }
+
+    #[must_use]
+    pub fn contains(self, day: Weekday) -> bool {
+        self.0 & Weekdays::bit(day) != 0
+    }
+}
+
+impl FromStr for Weekdays {
+    type Err = SettingError;
+
+    /// Parses days and ranges of days separated by `,`. A range may wrap around the end of the
+    /// week, e.g. `sat-mon`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let day = |name: &str| {
+            name.trim()
+                .parse::<Weekday>()
+                .map_err(|_| invalid(value, "days like `mon-thu,sat`"))
+        };
+        let mut days = 0;
+        for part in value.split(',') {
+            let (first, last) = match part.split_once('-') {
+                Some((first, last)) => (day(first)?, day(last)?),
+                None => (day(part)?, day(part)?),
+            };
+            let mut current = first;
+            days |= Weekdays::bit(current);
+            while current != last {
+                current = current.succ();
+                days |= Weekdays::bit(current);
+            }
+        }
+        Ok(Weekdays(days))
+    }
+}
+
+impl fmt::Display for Weekdays {
+    /// Writes runs of consecutive days as ranges, Monday first.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = |day: Weekday| day.to_string().to_lowercase();
+        let mut runs: Vec<(Weekday, Weekday)> = Vec::new();
+        let mut day = Weekday::Mon;
+        for _ in 0..7 {
+            if self.contains(day) {
+                match runs.last_mut() {
+                    Some((_, last)) if last.succ() == day => *last = day,
+                    _ => runs.push((day, day)),
+                }
+            }
+            day = day.succ();
+        }
+        let runs: Vec<String> = runs
+            .into_iter()
+            .map(|(first, last)| {
+                if first == last {
+                    name(first)
+                } else {
+                    format!("{}-{}", name(first), name(last))
+                }
+            })
+            .collect();
+        write!(f, "{}", runs.join(","))
+    }
+}
+
+/// A window the system is kept awake in on some days, e.g. `mon-thu 08:30-17:30`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeeklyRule {
+    pub days: Weekdays,
+    pub open: NaiveTime,
+    /// Always after `open`, windows don't span midnight.
+    pub close: NaiveTime,
+}
+
+impl FromStr for WeeklyRule {
+    type Err = SettingError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        let Some((days, window)) = value.split_once(char::is_whitespace) else {
+            return Err(invalid(value, EXPECTED_RULE));
+        };
+        let time = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), TIME_FORMAT)
+                .map_err(|_| invalid(value, EXPECTED_RULE))
+        };
+        let Some((open, close)) = window.split_once('-') else {
+            return Err(invalid(value, EXPECTED_RULE));
+        };
+        let (open, close) = (time(open)?, time(close)?);
+        if close <= open {
+            return Err(invalid(value, "a window closing after it opens"));
+        }
+        Ok(WeeklyRule {
+            days: days.parse()?,
+            open,
+            close,
+        })
+    }
+}
+
+impl fmt::Display for WeeklyRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {}-{}",
+            self.days,
+            self.open.format(TIME_FORMAT),
+            self.close.format(TIME_FORMAT)
+        )
+    }
+}
+
+/// Rules starting and stopping the keep-awake through the week, empty when the week isn't
+/// scheduled. Stored as the rules separated by `;`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WeeklySchedule(Vec<WeeklyRule>);
+
+impl WeeklySchedule {
+    #[must_use]
+    pub fn rules(&self) -> &[WeeklyRule] {
+        &self.0
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn add(&mut self, rule: WeeklyRule) {
+        self.0.push(rule);
+    }
+
+    /// Replaces the rule numbered `number`, counting from 1.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no such rule.
+    pub fn replace(&mut self, number: usize, rule: WeeklyRule) -> Result<(), SettingError> {
+        *self.get_mut(number)? = rule;
+        Ok(())
+    }
+
+    /// Removes the rule numbered `number`, counting from 1.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no such rule.
+    pub fn remove(&mut self, number: usize) -> Result<WeeklyRule, SettingError> {
+        self.get_mut(number)?;
+        Ok(self.0.remove(number - 1))
+    }
+
+    fn get_mut(&mut self, number: usize) -> Result<&mut WeeklyRule, SettingError> {
+        let count = self.0.len();
+        number
+            .checked_sub(1)
+            .and_then(|index| self.0.get_mut(index))
+            .ok_or_else(|| SettingError::OutOfRange {
+                entry: RegistryEntries::WeeklySchedule,
+                value: number.to_string(),
+                min: 1,
+                max: count as u64,
+            })
+    }
+}
+
+impl FromStr for WeeklySchedule {
+    type Err = SettingError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value
+            .split(RULE_SEPARATOR)
+            .filter(|rule| !rule.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map(WeeklySchedule)
+    }
+}
+
+impl fmt::Display for WeeklySchedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rules: Vec<String> = self.0.iter().map(ToString::to_string).collect();
+        write!(f, "{}", rules.join(&format!("{RULE_SEPARATOR} ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn days(value: &str) -> Vec<Weekday> {
+        let days: Weekdays = value.parse().unwrap();
+        [
+            Weekday::Mon,
+            Weekday::Tue,
+            Weekday::Wed,
+            Weekday::Thu,
+            Weekday::Fri,
+            Weekday::Sat,
+            Weekday::Sun,
+        ]
+        .into_iter()
+        .filter(|day| days.contains(*day))
+        .collect()
+    }
+
+    #[test]
+    fn parses_days_and_ranges() {
+        assert_eq!(days("mon"), [Weekday::Mon]);
+        assert_eq!(
+            days("mon-wed, fri"),
+            [Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Fri]
+        );
+        assert_eq!(days("sat-mon"), [Weekday::Mon, Weekday::Sat, Weekday::Sun]);
+        assert!("mon-funday".parse::<Weekdays>().is_err());
+        assert!("".parse::<Weekdays>().is_err());
+    }
+
+    #[test]
+    fn writes_days_as_runs() {
+        let display = |value: &str| value.parse::<Weekdays>().unwrap().to_string();
+        assert_eq!(display("fri,mon,tue,wed"), "mon-wed,fri");
+        assert_eq!(display("sat-mon"), "mon,sat-sun");
+        assert_eq!(display("sunday"), "sun");
+    }
+
+    #[test]
+    fn parses_rules_closing_after_they_open() {
+        let rule: WeeklyRule = " mon-thu  08:30-17:30 ".parse().unwrap();
+        assert_eq!(rule.open, NaiveTime::from_hms_opt(8, 30, 0).unwrap());
+        assert_eq!(rule.close, NaiveTime::from_hms_opt(17, 30, 0).unwrap());
+        assert_eq!(rule.to_string(), "mon-thu 08:30-17:30");
+        for value in [
+            "mon",
+            "mon 08:30",
+            "mon 8h-17h",
+            "mon 17:30-08:30",
+            "mon 09:00-09:00",
+        ] {
+            assert!(value.parse::<WeeklyRule>().is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn round_trips_schedules() {
+        let schedule: WeeklySchedule = "mon-fri 09:00-12:00;; sat 10:00-11:00;".parse().unwrap();
+        assert_eq!(schedule.rules().len(), 2);
+        assert_eq!(schedule.to_string(), "mon-fri 09:00-12:00; sat 10:00-11:00");
+        assert_eq!(schedule.to_string().parse::<WeeklySchedule>(), Ok(schedule));
+        assert!("".parse::<WeeklySchedule>().unwrap().is_empty());
+        assert!("mon 09:00-12:00; nope".parse::<WeeklySchedule>().is_err());
+    }
+
+    #[test]
+    fn edits_rules_by_number() {
+        let rule = |value: &str| value.parse::<WeeklyRule>().unwrap();
+        let mut schedule = WeeklySchedule::default();
+        schedule.add(rule("mon 09:00-12:00"));
+        schedule.add(rule("tue 09:00-12:00"));
+        schedule.replace(2, rule("wed 13:00-14:00")).unwrap();
+        assert_eq!(schedule.to_string(), "mon 09:00-12:00; wed 13:00-14:00");
+        assert_eq!(schedule.remove(1), Ok(rule("mon 09:00-12:00")));
+        assert_eq!(
+            schedule.remove(2),
+            Err(SettingError::OutOfRange {
+                entry: RegistryEntries::WeeklySchedule,
+                value: "2".to_string(),
+                min: 1,
+                max: 1,
+            })
+        );
+        assert!(schedule.replace(0, rule("mon 09:00-12:00")).is_err());
+        assert_eq!(schedule.rules(), [rule("wed 13:00-14:00")]);
+    }
+}
@@ -0,0 +1,120 @@
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+use app_controller::AppContext;
+use event_bus::Event;
+use local_http::HttpServer;
+use registry_ops::{ApiPort, RegistryEntries};
+
+mod openapi;
+mod routes;
+mod token;
+
+pub use token::{default_path as token_path, load_or_create as load_token};
+
+/// The optional REST API on `127.0.0.1`, controlled by the `ApiEndpoint` and `ApiPort`
+/// settings.
+struct ApiEndpoint {
+    context: Arc<AppContext>,
+    server: Option<HttpServer>,
+}
+
+/// Starts the API when it is enabled in the settings, and follows the event bus to start,
+/// stop or rebind it when the settings change.
+///
+/// # Panics
+///
+/// Panics if called outside of a tokio runtime.
+pub fn spawn(context: Arc<AppContext>) {
+    let mut subscription = context.events().subscribe("rest api");
+    let enabled = context
+        .settings()
+        .get(RegistryEntries::ApiEndpoint)
+        .lock()
+        .is_ok_and(|setting| setting.is_enabled());
+    let mut endpoint = ApiEndpoint {
+        context,
+        server: None,
+    };
+    endpoint.set_enabled(enabled);
+
+    tokio::spawn(async move {
+        while let Some(event) = subscription.next().await {
+            endpoint.handle(&event);
+        }
+    });
+}
+
+impl ApiEndpoint {
+    fn handle(&mut self, event: &Event) {
+        if let Event::SettingChanged { setting, value } = event {
+            if *setting == RegistryEntries::ApiEndpoint.to_string() {
+                self.set_enabled(value == "Enabled");
+            } else if *setting == RegistryEntries::ApiPort.to_string() {
+                self.restart();
+            }
+        }
+    }
+
+    /// Rebinds a running listener, e.g. after the port changed.
+    fn restart(&mut self) {
+        if self.server.is_some() {
+            self.set_enabled(false);
+            self.set_enabled(true);
+        }
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            if let Some(server) = self.server.take() {
+                info!("Stopping REST API");
+                server.stop();
+            }
+            return;
+        }
+        if self.server.is_some() {
+            return;
+        }
+
+        let Some(port) = load_port(&self.context) else {
+            return;
+        };
+        let path = token_path();
+        let token = match load_token(&path) {
+            Ok(token) => token,
+            Err(err) => {
+                error!("Failed to load the API token {path:?}, err: {err}");
+                return;
+            }
+        };
+        let context = Arc::clone(&self.context);
+        let port = port.port();
+        let handler = Arc::new(move |request: &local_http::Request| {
+            routes::handle(&context, &token, port, request)
+        });
+        match HttpServer::start(port, handler) {
+            Ok(server) => {
+                info!("REST API token is in {path:?}");
+                self.server = Some(server);
+            }
+            Err(err) => error!("Failed to start REST API on port {port}, err: {err}"),
+        }
+    }
+}
+
+fn load_port(context: &AppContext) -> Option<ApiPort> {
+    let setting = match context.settings().get(RegistryEntries::ApiPort).lock() {
+        Ok(setting) => setting,
+        Err(err) => {
+            error!("Failed to lock API port, err: {err}");
+            return None;
+        }
+    };
+    match setting.last_data.parse() {
+        Ok(port) => Some(port),
+        Err(err) => {
+            warn!("Found invalid API port {:?}, err: {err}", setting.last_data);
+            None
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use serde_json::{Map, Value, json};
+
+use crate::routes::{OPENAPI_PATH, ROUTES, Route};
+
+fn reference(schema: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{schema}") })
+}
+
+fn content(schema: &str) -> Value {
+    json!({ "application/json": { "schema": reference(schema) } })
+}
+
+/// Schemas of the request and response bodies, by name.
+fn schemas() -> Value {
+    let nullable_integer = json!({ "type": "integer", "nullable": true });
+    json!({
+        "Timer": {
+            "type": "object",
+            "properties": {
+                "mode": { "type": "string", "nullable": true },
+                "remaining_seconds": { "type": "integer" },
+                "deadline": { "type": "string", "description": "HH:MM, empty without a timer" },
+            },
+        },
+        "Schedule": {
+            "type": "object",
+            "properties": {
+                "active": { "type": "boolean" },
+                "time": { "type": "string", "description": "HH:MM" },
+                "remaining_seconds": nullable_integer,
+                "warned": {
+                    "type": "boolean",
+                    "description": "Whether the warning before the stop time was given",
+                },
+                "action": {
+                    "type": "string",
+                    "description": "Run at the stop time: exit, pause, lock, sleep, hibernate or run COMMAND",
+                },
+                "weekly": {
+                    "type": "object",
+                    "nullable": true,
+                    "description": "Window of the weekly schedule, null without rules",
+                    "properties": {
+                        "open": { "type": "boolean" },
+                        "until": {
+                            "type": "string",
+                            "nullable": true,
+                            "description": "Next opening or closing as Mon 08:30",
+                        },
+                    },
+                },
+            },
+        },
+        "Status": {
+            "type": "object",
+            "properties": {
+                "idle_seconds": nullable_integer,
+                "force_interval": { "type": "integer" },
+                "next_injection_seconds": nullable_integer,
+                "last_injection": { "type": "string" },
+                "mode": {
+                    "type": "string",
+                    "enum": ["active", "paused", "timed_pause", "timed_keep_awake"],
+                },
+                "timer": reference("Timer"),
+                "schedule": reference("Schedule"),
+                "logging": { "type": "boolean" },
+                "metrics_enabled": { "type": "boolean" },
+                "metrics_port": { "type": "integer" },
+                "api_enabled": { "type": "boolean" },
+                "api_port": { "type": "integer" },
+                "mqtt_enabled": { "type": "boolean" },
+                "mqtt_broker": { "type": "string", "description": "host:port" },
+                "mqtt_topic": { "type": "string" },
+            },
+        },
+        "Interval": {
+            "type": "object",
+            "required": ["seconds"],
+            "properties": { "seconds": { "type": "integer", "minimum": 60, "maximum": 86400 } },
+        },
+        "Shutdown": {
+            "type": "object",
+            "required": ["time"],
+            "properties": { "time": { "type": "string", "description": "HH:MM or STOP" } },
+        },
+        "Logging": {
+            "type": "object",
+            "required": ["enabled"],
+            "properties": { "enabled": { "type": "boolean" } },
+        },
+        "TimerRequest": {
+            "type": "object",
+            "required": ["mode", "duration"],
+            "properties": {
+                "mode": { "type": "string", "enum": ["pause", "keep_awake"] },
+                "duration": { "type": "string", "description": "e.g. 90m or 1h30m" },
+            },
+        },
+        "Error": {
+            "type": "object",
+            "properties": {
+                "kind": {
+                    "type": "string",
+                    "enum": ["lock", "invalid_setting", "invalid_input", "registry", "not_ready", "denied"],
+                },
+                "message": { "type": "string" },
+            },
+        },
+    })
+}
+
+fn operation(route: &Route) -> Value {
+    let mut responses = Map::new();
+    match route.response {
+        Some(schema) => responses.insert(
+            "200".to_string(),
+            json!({ "description": "OK", "content": content(schema) }),
+        ),
+        None => responses.insert("204".to_string(), json!({ "description": "Done" })),
+    };
+    responses.insert(
+        "400".to_string(),
+        json!({ "description": "Invalid request", "content": content("Error") }),
+    );
+    responses.insert(
+        "401".to_string(),
+        json!({ "description": "Missing or wrong token" }),
+    );
+    responses.insert(
+        "503".to_string(),
+        json!({ "description": "The idle service isn't running", "content": content("Error") }),
+    );
+
+    let mut operation = json!({
+        "operationId": route.operation_id,
+        "summary": route.summary,
+        "responses": responses,
+    });
+    if let Some(schema) = route.request {
+        operation["requestBody"] = json!({ "required": true, "content": content(schema) });
+    }
+    operation
+}
+
+/// The `OpenAPI` 3.0 description of every route, served on `port`.
+pub(crate) fn document(port: u16) -> Value {
+    let mut paths = Map::new();
+    for route in ROUTES {
+        let path = paths
+            .entry(route.path)
+            .or_insert_with(|| Value::Object(Map::new()));
+        path[route.method.to_lowercase()] = operation(route);
+    }
+    paths.insert(
+        OPENAPI_PATH.to_string(),
+        json!({
+            "get": {
+                "operationId": "getOpenApi",
+                "summary": "This document",
+                "security": [],
+                "responses": { "200": { "description": "OK" } },
+            },
+        }),
+    );
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Smart Idler",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Local API of the idler, only bound to 127.0.0.1. The bearer token is \
+                in the api-token file next to the settings.",
+        },
+        "servers": [{ "url": format!("http://127.0.0.1:{port}") }],
+        "components": {
+            "schemas": schemas(),
+            "securitySchemes": { "token": { "type": "http", "scheme": "bearer" } },
+        },
+        "security": [{ "token": [] }],
+        "paths": paths,
+    })
+}
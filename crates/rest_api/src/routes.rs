@@ -0,0 +1,290 @@
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tracing::{debug, error};
+
+use app_controller::{AppContext, CommandError, status};
+use local_http::{Request, Response};
+use registry_ops::{ForceInterval, RegistryEntries, RegistryState, ShutdownTime};
+
+use crate::{openapi, token};
+
+pub(crate) const OPENAPI_PATH: &str = "/api/v1/openapi.json";
+const JSON: &str = "application/json";
+
+type Handler = fn(&AppContext, &str) -> Result<Response, CommandError>;
+
+/// One operation of the API, served by [`handle`] and described by [`openapi::document`].
+pub(crate) struct Route {
+    pub method: &'static str,
+    pub path: &'static str,
+    pub operation_id: &'static str,
+    pub summary: &'static str,
+    /// Schema of the JSON body, `None` when the operation takes none.
+    pub request: Option<&'static str>,
+    /// Schema of the `200` response, `None` when the operation answers `204`.
+    pub response: Option<&'static str>,
+    handler: Handler,
+}
+
+pub(crate) const ROUTES: &[Route] = &[
+    Route {
+        method: "GET",
+        path: "/api/v1/status",
+        operation_id: "getStatus",
+        summary: "Status of the idle service, the settings and the schedule",
+        request: None,
+        response: Some("Status"),
+        handler: get_status,
+    },
+    Route {
+        method: "PUT",
+        path: "/api/v1/interval",
+        operation_id: "setInterval",
+        summary: "Sets the idle seconds after which input is injected",
+        request: Some("Interval"),
+        response: None,
+        handler: set_interval,
+    },
+    Route {
+        method: "GET",
+        path: "/api/v1/shutdown",
+        operation_id: "getShutdown",
+        summary: "State of the daily stop time",
+        request: None,
+        response: Some("Schedule"),
+        handler: get_shutdown,
+    },
+    Route {
+        method: "PUT",
+        path: "/api/v1/shutdown",
+        operation_id: "setShutdown",
+        summary: "Schedules the daily stop at HH:MM, or cancels it with STOP",
+        request: Some("Shutdown"),
+        response: None,
+        handler: set_shutdown,
+    },
+    Route {
+        method: "GET",
+        path: "/api/v1/logging",
+        operation_id: "getLogging",
+        summary: "Whether statistics are logged",
+        request: None,
+        response: Some("Logging"),
+        handler: get_logging,
+    },
+    Route {
+        method: "PUT",
+        path: "/api/v1/logging",
+        operation_id: "setLogging",
+        summary: "Switches statistics logging on or off",
+        request: Some("Logging"),
+        response: None,
+        handler: set_logging,
+    },
+    Route {
+        method: "GET",
+        path: "/api/v1/timer",
+        operation_id: "getTimer",
+        summary: "The running timer",
+        request: None,
+        response: Some("Timer"),
+        handler: get_timer,
+    },
+    Route {
+        method: "PUT",
+        path: "/api/v1/timer",
+        operation_id: "setTimer",
+        summary: "Pauses or keeps the system awake for a while",
+        request: Some("TimerRequest"),
+        response: None,
+        handler: set_timer,
+    },
+    Route {
+        method: "DELETE",
+        path: "/api/v1/timer",
+        operation_id: "cancelTimer",
+        summary: "Cancels the running timer",
+        request: None,
+        response: None,
+        handler: cancel_timer,
+    },
+];
+
+#[derive(Debug, Deserialize)]
+struct Interval {
+    seconds: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Shutdown {
+    time: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Logging {
+    enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TimerMode {
+    Pause,
+    KeepAwake,
+}
+
+#[derive(Debug, Deserialize)]
+struct TimerRequest {
+    mode: TimerMode,
+    /// e.g. `90m` or `1h30m`.
+    duration: String,
+}
+
+fn parse<T: DeserializeOwned>(body: &str) -> Result<T, CommandError> {
+    serde_json::from_str(body)
+        .map_err(|err| CommandError::InvalidInput(format!("Invalid request body, err: {err}")))
+}
+
+fn json(value: &impl Serialize) -> Response {
+    match serde_json::to_string(value) {
+        Ok(body) => Response::new(200, JSON, body),
+        Err(err) => {
+            error!("Failed to serialize response, err: {err}");
+            Response::text(500, "Internal Server Error")
+        }
+    }
+}
+
+fn no_content() -> Response {
+    Response::new(204, JSON, String::new())
+}
+
+fn get_status(context: &AppContext, _body: &str) -> Result<Response, CommandError> {
+    Ok(json(&status::current(context)?))
+}
+
+fn set_interval(context: &AppContext, body: &str) -> Result<Response, CommandError> {
+    let interval = ForceInterval::try_from(parse::<Interval>(body)?.seconds)?;
+    context.store(RegistryEntries::ForceInterval, &interval)?;
+    Ok(no_content())
+}
+
+fn get_shutdown(context: &AppContext, _body: &str) -> Result<Response, CommandError> {
+    Ok(json(&status::schedule(context)?))
+}
+
+fn set_shutdown(context: &AppContext, body: &str) -> Result<Response, CommandError> {
+    let time: ShutdownTime = parse::<Shutdown>(body)?.time.parse()?;
+    context.schedule_stop(time)?;
+    Ok(no_content())
+}
+
+fn get_logging(context: &AppContext, _body: &str) -> Result<Response, CommandError> {
+    let entry = RegistryEntries::LogStatistics;
+    let enabled = match context.settings().get(entry).lock() {
+        Ok(setting) => setting.is_enabled(),
+        Err(err) => return Err(CommandError::lock(entry, &err)),
+    };
+    Ok(json(&Logging { enabled }))
+}
+
+fn set_logging(context: &AppContext, body: &str) -> Result<Response, CommandError> {
+    let state = RegistryState::from(parse::<Logging>(body)?.enabled);
+    context.store(RegistryEntries::LogStatistics, &state)?;
+    Ok(no_content())
+}
+
+fn get_timer(context: &AppContext, _body: &str) -> Result<Response, CommandError> {
+    let timer: status::TimerStatus = context.running_idle_service()?.timer().into();
+    Ok(json(&timer))
+}
+
+fn set_timer(context: &AppContext, body: &str) -> Result<Response, CommandError> {
+    let request: TimerRequest = parse(body)?;
+    let service = context.running_idle_service()?;
+    let duration = idler_utils::parse_duration(&request.duration)
+        .map_err(|err| CommandError::InvalidInput(err.to_string()))?;
+    match request.mode {
+        TimerMode::Pause => service.pause_for(duration),
+        TimerMode::KeepAwake => service.keep_awake_for(duration),
+    }
+    .map_err(|err| CommandError::InvalidInput(err.to_string()))?;
+    Ok(no_content())
+}
+
+fn cancel_timer(context: &AppContext, _body: &str) -> Result<Response, CommandError> {
+    context.running_idle_service()?.cancel_timer();
+    Ok(no_content())
+}
+
+fn error_response(err: &CommandError) -> Response {
+    let status = match err {
+        CommandError::InvalidInput(_) => 400,
+        CommandError::Denied(_) => 403,
+        CommandError::NotReady(_) => 503,
+        CommandError::Lock(_) | CommandError::InvalidSetting(_) | CommandError::Registry(_) => 500,
+    };
+    Response {
+        status,
+        ..json(err)
+    }
+}
+
+fn authorized(request: &Request, token: &str) -> bool {
+    request
+        .header("Authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| token::matches(token, given.trim()))
+}
+
+/// Routes `request`, answering everything but the `OpenAPI` document with `401` unless it
+/// carries `Authorization: Bearer <token>`.
+pub(crate) fn handle(context: &AppContext, token: &str, port: u16, request: &Request) -> Response {
+    if request.path == OPENAPI_PATH {
+        return if request.method == "GET" {
+            json(&openapi::document(port))
+        } else {
+            Response::method_not_allowed()
+        };
+    }
+    let mut routes = ROUTES
+        .iter()
+        .filter(|route| route.path == request.path)
+        .peekable();
+    if routes.peek().is_none() {
+        return Response::not_found();
+    }
+    if !authorized(request, token) {
+        debug!("Rejecting unauthorized {} {}", request.method, request.path);
+        return Response::text(401, "Unauthorized");
+    }
+    let Some(route) = routes.find(|route| route.method == request.method) else {
+        return Response::method_not_allowed();
+    };
+    (route.handler)(context, &request.body).unwrap_or_else(|err| error_response(&err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(authorization: Option<&str>) -> Request {
+        Request {
+            method: "GET".to_string(),
+            path: "/status".to_string(),
+            headers: authorization
+                .map(|value| ("authorization".to_string(), value.to_string()))
+                .into_iter()
+                .collect(),
+            body: String::new(),
+        }
+    }
+
+    #[test]
+    fn requires_the_bearer_token() {
+        assert!(authorized(&request(Some("Bearer secret")), "secret"));
+        assert!(authorized(&request(Some("Bearer secret ")), "secret"));
+        assert!(!authorized(&request(Some("Bearer secreT")), "secret"));
+        assert!(!authorized(&request(Some("Basic secret")), "secret"));
+        assert!(!authorized(&request(Some("secret")), "secret"));
+        assert!(!authorized(&request(None), "secret"));
+    }
+}
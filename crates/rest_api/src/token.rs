@@ -0,0 +1,132 @@
+use std::{
+    env,
+    fmt::Write as _,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+use tracing::info;
+
+use anyhow::{Result, anyhow};
+
+const TOKEN_FILE: &str = "api-token";
+const TOKEN_BYTES: usize = 32;
+
+/// Location of the API token, under `%APPDATA%`.
+#[cfg(windows)]
+#[must_use]
+pub fn default_path() -> PathBuf {
+    env::var_os("APPDATA")
+        .map_or_else(env::temp_dir, PathBuf::from)
+        .join("SmartIdler")
+        .join(TOKEN_FILE)
+}
+
+/// Location of the API token, under `$XDG_CONFIG_HOME` or `~/.config`.
+#[cfg(not(windows))]
+#[must_use]
+pub fn default_path() -> PathBuf {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(env::temp_dir)
+        .join("smart-idler")
+        .join(TOKEN_FILE)
+}
+
+#[cfg(unix)]
+fn create_private(path: &Path) -> io::Result<fs::File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+}
+
+/// `%APPDATA%` is only readable by its user.
+#[cfg(not(unix))]
+fn create_private(path: &Path) -> io::Result<fs::File> {
+    OpenOptions::new().write(true).create_new(true).open(path)
+}
+
+/// Reads the token at `path`, writing a random one only readable by the current user the
+/// first time.
+///
+/// # Errors
+///
+/// Returns an error if the token can't be read or written, or the file is empty.
+pub fn load_or_create(path: &Path) -> Result<String> {
+    match fs::read_to_string(path) {
+        Ok(token) => {
+            let token = token.trim().to_string();
+            if token.is_empty() {
+                return Err(anyhow!("The API token {path:?} is empty"));
+            }
+            return Ok(token);
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err.into()),
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let token = rand::random::<[u8; TOKEN_BYTES]>().iter().fold(
+        String::with_capacity(TOKEN_BYTES * 2),
+        |mut token, byte| {
+            let _ = write!(token, "{byte:02x}");
+            token
+        },
+    );
+    writeln!(create_private(path)?, "{token}")?;
+    info!("Created the API token {path:?}");
+    Ok(token)
+}
+
+/// Compares in constant time, so the response time doesn't leak how much of the token
+/// matched.
+pub(crate) fn matches(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+
+    #[test]
+    fn matches_only_the_exact_token() {
+        assert!(matches("0123abcd", "0123abcd"));
+        assert!(!matches("0123abcd", "0123abce"));
+        assert!(!matches("0123abcd", "1123abcd"));
+        assert!(!matches("0123abcd", "0123abc"));
+        assert!(!matches("0123abcd", "0123abcd0"));
+        assert!(!matches("0123abcd", ""));
+    }
+
+    #[test]
+    fn creates_the_token_once() {
+        let dir = env::temp_dir().join(format!("smart-idler-token-{}", process::id()));
+        let path = dir.join(TOKEN_FILE);
+        let token = load_or_create(&path).unwrap();
+        assert_eq!(token.len(), TOKEN_BYTES * 2);
+        assert!(token.bytes().all(|byte| byte.is_ascii_hexdigit()));
+        assert_eq!(load_or_create(&path).unwrap(), token);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        fs::write(&path, "\n").unwrap();
+        assert!(load_or_create(&path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,79 @@
+use std::{
+    io::{BufRead, BufReader},
+    path::Path,
+    process::{Child, Command, Stdio},
+};
+
+use tempfile::TempDir;
+use zbus::{Connection, connection};
+
+/// A private `dbus-daemon` listening in its own temporary directory, killed and removed on
+/// drop.
+pub struct DbusDaemon {
+    daemon: Child,
+    address: String,
+    dir: TempDir,
+}
+
+impl DbusDaemon {
+    /// # Panics
+    ///
+    /// Panics if `dbus-daemon` isn't installed or doesn't print its address.
+    #[must_use]
+    pub fn start() -> DbusDaemon {
+        let dir = tempfile::Builder::new()
+            .prefix("smart-idler-dbus-")
+            .tempdir()
+            .expect("create a temporary directory");
+        let mut daemon = Command::new("dbus-daemon")
+            .arg("--session")
+            .arg("--nofork")
+            .arg("--print-address=1")
+            .arg(format!("--address=unix:dir={}", dir.path().display()))
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("start dbus-daemon, it comes with the dbus package");
+        let mut address = String::new();
+        let stdout = daemon.stdout.take().expect("dbus-daemon output");
+        BufReader::new(stdout)
+            .read_line(&mut address)
+            .expect("read the address of dbus-daemon");
+        DbusDaemon {
+            daemon,
+            address: address.trim().to_string(),
+            dir,
+        }
+    }
+
+    /// The temporary directory the daemon listens in, removed with it.
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// A connection builder for the bus, e.g. to request names before connecting.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the daemon printed an invalid address.
+    pub fn builder(&self) -> connection::Builder<'_> {
+        connection::Builder::address(self.address.as_str()).expect("valid bus address")
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the bus can't be reached.
+    pub async fn connect(&self) -> Connection {
+        self.builder()
+            .build()
+            .await
+            .expect("connect to the private bus")
+    }
+}
+
+impl Drop for DbusDaemon {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
@@ -0,0 +1,7 @@
+//! Fixtures shared by the integration tests of the workspace.
+
+#[cfg(not(windows))]
+mod dbus;
+
+#[cfg(not(windows))]
+pub use dbus::DbusDaemon;
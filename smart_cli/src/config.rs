@@ -0,0 +1,208 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow};
+
+use registry_ops::{RegistryEntries, SettingsStore};
+
+const PROFILES_DIR: &str = "profiles";
+
+/// User settings by entry name, the format of `config export` and of the profiles.
+pub(crate) type Settings = BTreeMap<String, String>;
+
+/// Folder of the saved profiles, under the roaming app data folder.
+#[cfg(windows)]
+fn profiles_dir() -> PathBuf {
+    env::var_os("APPDATA")
+        .map_or_else(env::temp_dir, PathBuf::from)
+        .join("SmartIdler")
+        .join(PROFILES_DIR)
+}
+
+/// Folder of the saved profiles, under `$XDG_CONFIG_HOME` or `~/.config`.
+#[cfg(not(windows))]
+fn profiles_dir() -> PathBuf {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(env::temp_dir)
+        .join("smart-idler")
+        .join(PROFILES_DIR)
+}
+
+/// # Errors
+///
+/// Returns an error if `name` is empty or has characters other than letters, digits, `-`
+/// and `_`.
+pub(crate) fn profile_path(name: &str) -> Result<PathBuf> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || "-_".contains(character));
+    if !valid {
+        return Err(anyhow!(
+            "Invalid profile name {name:?}, use letters, digits, - and _"
+        ));
+    }
+    Ok(profiles_dir().join(format!("{name}.json")))
+}
+
+/// Names of the saved profiles, sorted.
+///
+/// # Errors
+///
+/// Returns an error if the profiles folder can't be read.
+pub(crate) fn list_profiles() -> Result<Vec<String>> {
+    let entries = match fs::read_dir(profiles_dir()) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut names = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            if let Some(name) = path.file_stem() {
+                names.push(name.to_string_lossy().into_owned());
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Reads the user settings from `settings`.
+///
+/// # Errors
+///
+/// Returns an error if a setting can't be locked.
+pub(crate) fn export(settings: &SettingsStore) -> Result<Settings> {
+    RegistryEntries::SETTINGS
+        .into_iter()
+        .map(|entry| match settings.get(entry).lock() {
+            Ok(setting) => Ok((entry.to_string(), setting.last_data.clone())),
+            Err(err) => Err(anyhow!("Failed to lock {entry}, err: {err}")),
+        })
+        .collect()
+}
+
+/// Checks every value of `values`, returning them by entry.
+///
+/// # Errors
+///
+/// Returns an error if a key is not a user setting or a value is rejected.
+pub(crate) fn validate(values: &Settings) -> Result<Vec<(RegistryEntries, String)>> {
+    let mut checked = Vec::with_capacity(values.len());
+    for (key, value) in values {
+        let entry: RegistryEntries = key.parse()?;
+        if !RegistryEntries::SETTINGS.contains(&entry) {
+            return Err(anyhow!("{entry} is not a user setting"));
+        }
+        registry_ops::validate(entry, value)?;
+        checked.push((entry, value.clone()));
+    }
+    Ok(checked)
+}
+
+/// # Errors
+///
+/// Returns an error if the file can't be read or is not a JSON map of strings.
+pub(crate) fn read_file(path: &Path) -> Result<Settings> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("Invalid settings in {}", path.display()))
+}
+
+/// # Errors
+///
+/// Returns an error if the file or its folder can't be written.
+pub(crate) fn write_file(path: &Path, settings: &Settings) -> Result<()> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(settings)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(values: &[(&str, &str)]) -> Settings {
+        values
+            .iter()
+            .map(|(key, value)| ((*key).to_string(), (*value).to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn validates_an_export() {
+        let exported = export(&SettingsStore::in_memory()).unwrap();
+        assert_eq!(exported.len(), RegistryEntries::SETTINGS.len());
+        assert_eq!(exported["ForceInterval"], "60");
+
+        let checked = validate(&exported).unwrap();
+        assert_eq!(checked.len(), exported.len());
+        assert!(checked.contains(&(RegistryEntries::ForceInterval, "60".to_string())));
+    }
+
+    #[test]
+    fn rejects_invalid_imports() {
+        for (key, value) in [
+            ("ForceInterval", "0"),
+            ("ForceInterval", "often"),
+            ("ShutdownTime", "25:00"),
+            ("NoSuchSetting", "1"),
+            // Only an administrator may change the control policy.
+            ("ControlOwnerPermission", "Admin"),
+            ("ControlOthersPermission", "Admin"),
+            // The app records its own state.
+            ("LastRobotInput", "12:00:00"),
+        ] {
+            let values = settings(&[("ForceInterval", "120"), (key, value)]);
+            assert!(validate(&values).is_err(), "{key} = {value}");
+        }
+    }
+
+    #[test]
+    fn checks_profile_names() {
+        for name in ["work", "late-shift", "night_2"] {
+            let path = profile_path(name).unwrap();
+            assert_eq!(path.file_name().unwrap(), format!("{name}.json").as_str());
+            assert!(path.parent().unwrap().ends_with(PROFILES_DIR));
+        }
+        for name in ["", "../settings", "a b", "work.json", "nuit/jour"] {
+            assert!(profile_path(name).is_err(), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn round_trips_profiles() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join(PROFILES_DIR).join("work.json");
+        let saved = settings(&[
+            ("ForceInterval", "120"),
+            ("ShutdownTime", "17:30"),
+            ("WeeklySchedule", "mon-fri 08:30-17:30"),
+        ]);
+
+        write_file(&path, &saved).unwrap();
+        let read = read_file(&path).unwrap();
+        assert_eq!(read, saved);
+        assert_eq!(validate(&read).unwrap().len(), saved.len());
+
+        fs::write(&path, "[1, 2]").unwrap();
+        assert!(read_file(&path).is_err());
+        assert!(read_file(&temp.path().join("missing.json")).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+use anyhow::{Result, anyhow};
+
+use app_controller::AppContext;
+use control::{InstanceLock, Listener};
+use event_bus::{Event, EventBus};
+
+use crate::output::Report;
+
+#[cfg(unix)]
+async fn terminated() {
+    use tokio::signal::unix::{SignalKind, signal};
+    match signal(SignalKind::terminate()) {
+        Ok(mut terminate) => {
+            terminate.recv().await;
+        }
+        Err(err) => {
+            error!("Failed to listen for SIGTERM, err: {err}");
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn terminated() {
+    std::future::pending::<()>().await;
+}
+
+/// Serves the idler on the D-Bus session bus, which headless hosts often don't have.
+#[cfg(not(windows))]
+async fn serve_dbus(context: Arc<AppContext>) {
+    if let Err(err) = dbus_service::serve(context).await {
+        warn!("Not serving on D-Bus, err: {err}");
+    }
+}
+
+#[cfg(windows)]
+async fn serve_dbus(_context: Arc<AppContext>) {}
+
+/// Runs the idle service, the statistics, the stored daily stop time, the control endpoint,
+/// the D-Bus service, and the metrics endpoint, REST API and MQTT bridge when enabled until
+/// Ctrl-C, `quit` or the stop time with the `exit` stop action.
+///
+/// # Errors
+///
+/// Returns an error if another idler is running or the control socket can't be bound.
+pub(crate) async fn run() -> Result<Report> {
+    // Locking and binding first makes a second idler fail before touching anything.
+    let Some(_instance) = InstanceLock::acquire()? else {
+        return Err(anyhow!(
+            "Another idler is running, control it with `smart-idler status`"
+        ));
+    };
+    let listener = Listener::bind().await?;
+    let context = Arc::new(AppContext::new(EventBus::new()));
+    if let Err(err) = context.schedule_stored_stop() {
+        error!("Not scheduling the stored stop time, err: {err}");
+    }
+
+    let mut subscription = context.events().subscribe("daemon");
+    metrics_endpoint::spawn(Arc::clone(&context));
+    rest_api::spawn(Arc::clone(&context));
+    mqtt_bridge::spawn(Arc::clone(&context));
+    context.start_services();
+    app_controller::weekly::spawn(Arc::clone(&context));
+    app_controller::action::spawn(Arc::clone(&context));
+    let server = tokio::spawn(control::serve(listener, Arc::clone(&context)));
+    let dbus = tokio::spawn(serve_dbus(Arc::clone(&context)));
+    info!("Idler running, press Ctrl-C to stop");
+
+    let exit_requested = async {
+        while let Some(event) = subscription.next().await {
+            if event == Event::ExitRequested {
+                return "Exit requested";
+            }
+        }
+        "Event bus closed"
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => info!("Interrupted"),
+        () = terminated() => info!("Terminated"),
+        reason = exit_requested => info!("{reason}"),
+    }
+
+    // Dropping the listener removes the socket file.
+    server.abort();
+    let _ = server.await;
+    dbus.abort();
+    // Shutting down the idle service blocks until its task exits.
+    tokio::task::block_in_place(|| context.shutdown());
+    Ok(Report::Nothing)
+}
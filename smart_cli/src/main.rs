@@ -0,0 +1,499 @@
+use std::{io, path::PathBuf, time::Duration};
+use tracing::Level;
+
+use anyhow::{Result, anyhow};
+use chrono::NaiveTime;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+
+use app_controller::POSTPONE;
+use autostart::Mode;
+use control::{Client, Reply, Request};
+use idler_utils::{TimedMode, Timer};
+use registry_ops::{
+    ApiPort, ForceInterval, MetricsPort, MqttBroker, MqttTopic, RegistryEntries, RegistryState,
+    ScheduleAction, SettingsStore, ShutdownTime, StatisticsRetention, StopWarnings, WeeklyRule,
+    WeeklySchedule,
+};
+
+use crate::{output::Report, target::Target};
+
+mod config;
+mod daemon;
+mod output;
+mod status;
+mod target;
+
+const BIN_NAME: &str = "smart-idler";
+
+#[derive(Debug, Parser)]
+#[command(name = BIN_NAME, version, about)]
+struct Args {
+    /// Print the result as JSON
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Show the status of the running idler, or the stored settings when none is running
+    Status,
+    /// Run the idler without the tray, controlled through the other commands
+    Daemon,
+    /// Keep the system awake again
+    Start {
+        /// Keep the system awake for a while and pause afterwards, e.g. `3h`
+        #[arg(long = "for", value_name = "DURATION", value_parser = idler_utils::parse_duration)]
+        duration: Option<Duration>,
+    },
+    /// Stop keeping the system awake
+    Stop,
+    /// Stop keeping the system awake for a while, e.g. `pause --for 30m`
+    Pause {
+        #[arg(long = "for", value_name = "DURATION", value_parser = idler_utils::parse_duration)]
+        duration: Duration,
+    },
+    /// Print the events of the running idler until it exits
+    Events,
+    /// Make the running idler exit
+    Quit,
+    /// Change a setting
+    Set {
+        #[command(subcommand)]
+        setting: SetCommand,
+    },
+    /// Set or cancel the daily stop time
+    Schedule {
+        #[command(subcommand)]
+        action: ScheduleCommand,
+    },
+    /// Keep the system awake in weekly windows, e.g. `weekly add mon-thu 08:30-17:30`
+    Weekly {
+        #[command(subcommand)]
+        action: WeeklyCommand,
+    },
+    /// Save and apply named sets of settings
+    Profile {
+        #[command(subcommand)]
+        action: ProfileCommand,
+    },
+    /// Export or import the settings as JSON
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+    /// Start the daemon at login
+    Autostart {
+        #[command(subcommand)]
+        action: AutostartCommand,
+    },
+    /// Print the completion script for a shell
+    Completions { shell: Shell },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum SetCommand {
+    /// Idle seconds after which input is injected
+    Interval { seconds: ForceInterval },
+    /// Days of statistics kept on disk
+    Retention { days: StatisticsRetention },
+    /// Port of the local metrics endpoint
+    MetricsPort { port: MetricsPort },
+    /// Statistics logging
+    Logging { state: Switch },
+    /// Local metrics endpoint
+    Metrics { state: Switch },
+    /// Port of the local REST API
+    ApiPort { port: ApiPort },
+    /// Local REST API
+    Api { state: Switch },
+    /// MQTT broker as `host` or `host:port`
+    MqttBroker { broker: MqttBroker },
+    /// Base of the MQTT topics
+    MqttTopic { topic: MqttTopic },
+    /// MQTT bridge
+    Mqtt { state: Switch },
+    /// What to do at the stop time: exit, pause, lock, sleep, hibernate or "run COMMAND"
+    StopAction { action: ScheduleAction },
+    /// Minutes before the stop time to warn at, e.g. `10,2`, or `none`
+    StopWarnings { minutes: StopWarnings },
+}
+
+#[derive(Debug, Subcommand)]
+enum ScheduleCommand {
+    /// Stop keeping the system awake at `HH:MM` every day
+    StopAt {
+        #[arg(value_parser = parse_stop_time)]
+        time: NaiveTime,
+    },
+    /// Move the pending stop later, once, by e.g. `15m` (30 minutes by default)
+    Postpone {
+        #[arg(value_parser = idler_utils::parse_duration)]
+        duration: Option<Duration>,
+    },
+    /// Cancel the daily stop time
+    Cancel,
+}
+
+#[derive(Debug, Subcommand)]
+enum WeeklyCommand {
+    /// List the rules with their numbers
+    List,
+    /// Keep awake on `DAYS`, e.g. `mon-thu` or `mon,wed,fri`, from `HH:MM-HH:MM`
+    Add { days: String, hours: String },
+    /// Replace the rule numbered `NUMBER`
+    Update {
+        number: usize,
+        days: String,
+        hours: String,
+    },
+    /// Remove the rule numbered `NUMBER`
+    Remove { number: usize },
+    /// Remove every rule
+    Clear,
+}
+
+#[derive(Debug, Subcommand)]
+enum ProfileCommand {
+    /// List the saved profiles
+    List,
+    /// Save the current settings as a profile
+    Save { name: String },
+    /// Apply the settings of a profile
+    Use { name: String },
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommand {
+    /// Print the settings, or write them to a file
+    Export {
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Apply the settings of a file written by `config export`
+    Import { file: PathBuf },
+}
+
+#[derive(Debug, Subcommand)]
+enum AutostartCommand {
+    /// Run `smart-idler daemon` of this executable at login
+    Enable,
+    /// Stop running the daemon at login
+    Disable,
+    /// Show whether the daemon runs at login
+    Status,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Switch {
+    On,
+    Off,
+}
+
+impl From<Switch> for RegistryState {
+    fn from(switch: Switch) -> Self {
+        RegistryState::from(matches!(switch, Switch::On))
+    }
+}
+
+fn parse_stop_time(text: &str) -> Result<NaiveTime> {
+    match text.parse::<ShutdownTime>()?.time() {
+        Some(time) => Ok(time),
+        None => Err(anyhow!("Expected HH:MM, use `schedule cancel` to cancel")),
+    }
+}
+
+/// Writes `value` to `entry` of the running idler, or of the stored settings.
+async fn store(
+    target: &mut Target,
+    entry: RegistryEntries,
+    value: &impl ToString,
+) -> Result<Report> {
+    let value = value.to_string();
+    target.set(entry, value.clone()).await?;
+    Ok(Report::Changed {
+        setting: entry.to_string(),
+        value,
+    })
+}
+
+async fn request(request: Request, message: &str) -> Result<Report> {
+    Client::connect().await?.request(request).await?;
+    Ok(Report::Done {
+        message: message.to_string(),
+    })
+}
+
+async fn pause(duration: Duration) -> Result<Report> {
+    match Target::connect().await {
+        Target::Running(mut client) => {
+            let seconds = duration.as_secs();
+            client.request(Request::PauseFor { seconds }).await?;
+        }
+        Target::Stored(settings) => {
+            Timer::store(&settings, Some(&Timer::new(TimedMode::Pause, duration)?));
+        }
+    }
+    Ok(Report::Changed {
+        setting: RegistryEntries::TimedMode.to_string(),
+        value: format!(
+            "{} for {}",
+            TimedMode::Pause,
+            idler_utils::format_countdown(duration)
+        ),
+    })
+}
+
+async fn run_schedule(action: ScheduleCommand) -> Result<Report> {
+    let time = match action {
+        ScheduleCommand::StopAt { time } => ShutdownTime::At(time),
+        ScheduleCommand::Cancel => ShutdownTime::Stopped,
+        ScheduleCommand::Postpone { duration } => {
+            let duration = duration.unwrap_or(POSTPONE);
+            let message = format!(
+                "Postponed the stop by {}",
+                idler_utils::format_countdown(duration)
+            );
+            let seconds = duration.as_secs();
+            return request(Request::Postpone { seconds }, &message).await;
+        }
+    };
+    let mut target = Target::connect().await;
+    store(&mut target, RegistryEntries::ShutdownTime, &time).await
+}
+
+async fn run_set(setting: SetCommand) -> Result<Report> {
+    let mut target = Target::connect().await;
+    match setting {
+        SetCommand::Interval { seconds } => {
+            store(&mut target, RegistryEntries::ForceInterval, &seconds).await
+        }
+        SetCommand::Retention { days } => {
+            store(&mut target, RegistryEntries::StatisticsRetention, &days).await
+        }
+        SetCommand::MetricsPort { port } => {
+            store(&mut target, RegistryEntries::MetricsPort, &port).await
+        }
+        SetCommand::Logging { state } => {
+            let state = RegistryState::from(state);
+            store(&mut target, RegistryEntries::LogStatistics, &state).await
+        }
+        SetCommand::Metrics { state } => {
+            let state = RegistryState::from(state);
+            store(&mut target, RegistryEntries::MetricsEndpoint, &state).await
+        }
+        SetCommand::ApiPort { port } => store(&mut target, RegistryEntries::ApiPort, &port).await,
+        SetCommand::Api { state } => {
+            let state = RegistryState::from(state);
+            store(&mut target, RegistryEntries::ApiEndpoint, &state).await
+        }
+        SetCommand::MqttBroker { broker } => {
+            store(&mut target, RegistryEntries::MqttBroker, &broker).await
+        }
+        SetCommand::MqttTopic { topic } => {
+            store(&mut target, RegistryEntries::MqttTopic, &topic).await
+        }
+        SetCommand::Mqtt { state } => {
+            let state = RegistryState::from(state);
+            store(&mut target, RegistryEntries::MqttEndpoint, &state).await
+        }
+        SetCommand::StopAction { action } => {
+            store(&mut target, RegistryEntries::ScheduleAction, &action).await
+        }
+        SetCommand::StopWarnings { minutes } => {
+            store(&mut target, RegistryEntries::StopWarnings, &minutes).await
+        }
+    }
+}
+
+/// Applies the validated settings of `path` to the running idler, or the stored settings.
+async fn import(path: PathBuf) -> Result<Report> {
+    let values = config::validate(&config::read_file(&path)?)?;
+    let mut target = Target::connect().await;
+    for (entry, value) in &values {
+        target.set(*entry, value.clone()).await?;
+    }
+    Ok(Report::Imported {
+        count: values.len(),
+        path,
+    })
+}
+
+fn parse_rule(days: &str, hours: &str) -> Result<WeeklyRule> {
+    Ok(format!("{days} {hours}").parse()?)
+}
+
+fn rule_texts(schedule: &WeeklySchedule) -> Report {
+    Report::Rules {
+        rules: schedule.rules().iter().map(ToString::to_string).collect(),
+    }
+}
+
+/// Changes the rules of the running idler, so it can't race with other front-ends.
+async fn change_rules(client: &mut Client, action: WeeklyCommand) -> Result<Report> {
+    let request = match action {
+        WeeklyCommand::List => Request::Rules,
+        WeeklyCommand::Add { days, hours } => Request::AddRule {
+            rule: parse_rule(&days, &hours)?.to_string(),
+        },
+        WeeklyCommand::Update {
+            number,
+            days,
+            hours,
+        } => Request::ReplaceRule {
+            number,
+            rule: parse_rule(&days, &hours)?.to_string(),
+        },
+        WeeklyCommand::Remove { number } => Request::RemoveRule { number },
+        WeeklyCommand::Clear => {
+            let request = Request::Set {
+                setting: RegistryEntries::WeeklySchedule,
+                value: WeeklySchedule::default().to_string(),
+            };
+            client.request(request).await?;
+            return Ok(rule_texts(&WeeklySchedule::default()));
+        }
+    };
+    match client.request(request).await? {
+        Reply::Rules { rules } => Ok(Report::Rules { rules }),
+        reply => Err(anyhow!("Unexpected reply {reply:?}")),
+    }
+}
+
+async fn run_weekly(action: WeeklyCommand) -> Result<Report> {
+    let settings = match Target::connect().await {
+        Target::Running(mut client) => return change_rules(&mut client, action).await,
+        Target::Stored(settings) => settings,
+    };
+    let entry = RegistryEntries::WeeklySchedule;
+    let mut schedule: WeeklySchedule = status::parse(&settings, entry)?;
+    match action {
+        WeeklyCommand::List => return Ok(rule_texts(&schedule)),
+        WeeklyCommand::Add { days, hours } => schedule.add(parse_rule(&days, &hours)?),
+        WeeklyCommand::Update {
+            number,
+            days,
+            hours,
+        } => schedule.replace(number, parse_rule(&days, &hours)?)?,
+        WeeklyCommand::Remove { number } => drop(schedule.remove(number)?),
+        WeeklyCommand::Clear => schedule = WeeklySchedule::default(),
+    }
+    store(&mut Target::Stored(settings), entry, &schedule).await
+}
+
+async fn run_profile(action: ProfileCommand) -> Result<Report> {
+    match action {
+        ProfileCommand::List => Ok(Report::Profiles {
+            names: config::list_profiles()?,
+        }),
+        ProfileCommand::Save { name } => {
+            let path = config::profile_path(&name)?;
+            config::write_file(&path, &config::export(&SettingsStore::load())?)?;
+            Ok(Report::Saved { path })
+        }
+        ProfileCommand::Use { name } => import(config::profile_path(&name)?).await,
+    }
+}
+
+async fn run_config(action: ConfigCommand) -> Result<Report> {
+    match action {
+        ConfigCommand::Export { output: None } => {
+            Ok(Report::Settings(config::export(&SettingsStore::load())?))
+        }
+        ConfigCommand::Export { output: Some(path) } => {
+            config::write_file(&path, &config::export(&SettingsStore::load())?)?;
+            Ok(Report::Saved { path })
+        }
+        ConfigCommand::Import { file } => import(file).await,
+    }
+}
+
+fn run_autostart(action: &AutostartCommand) -> Result<Report> {
+    let autostart = match action {
+        AutostartCommand::Enable => autostart::enable(Mode::Daemon)?,
+        AutostartCommand::Disable => autostart::disable(Mode::Daemon)?,
+        AutostartCommand::Status => autostart::status(Mode::Daemon)?,
+    };
+    Ok(Report::Autostart {
+        autostart,
+        location: autostart::location(Mode::Daemon),
+    })
+}
+
+async fn print_events(json: bool) -> Result<Report> {
+    let mut client = Client::connect().await?;
+    client.subscribe().await?;
+    while let Some(event) = client.next_event().await? {
+        if json {
+            println!("{}", serde_json::to_string(&event)?);
+        } else {
+            println!("{event}");
+        }
+    }
+    Ok(Report::Nothing)
+}
+
+async fn run(command: Command, json: bool) -> Result<Report> {
+    match command {
+        Command::Status => match Target::connect().await {
+            Target::Running(mut client) => match client.request(Request::Status).await? {
+                Reply::Status(status) => Ok(Report::Running(status)),
+                reply => Err(anyhow!("Unexpected reply {reply:?}")),
+            },
+            Target::Stored(settings) => Ok(Report::Status(status::stored(&settings)?)),
+        },
+        Command::Daemon => daemon::run().await,
+        Command::Start { duration: None } => request(Request::Resume, "Keeping awake").await,
+        Command::Start {
+            duration: Some(duration),
+        } => {
+            let seconds = duration.as_secs();
+            let message = format!(
+                "Keeping awake for {}",
+                idler_utils::format_countdown(duration)
+            );
+            request(Request::KeepAwakeFor { seconds }, &message).await
+        }
+        Command::Stop => request(Request::Pause, "Paused").await,
+        Command::Pause { duration } => pause(duration).await,
+        Command::Events => print_events(json).await,
+        Command::Quit => request(Request::Exit, "Asked the idler to exit").await,
+        Command::Set { setting } => run_set(setting).await,
+        Command::Schedule { action } => run_schedule(action).await,
+        Command::Weekly { action } => run_weekly(action).await,
+        Command::Profile { action } => run_profile(action).await,
+        Command::Config { action } => run_config(action).await,
+        Command::Autostart { action } => run_autostart(&action),
+        Command::Completions { shell } => {
+            clap_complete::generate(shell, &mut Args::command(), BIN_NAME, &mut io::stdout());
+            Ok(Report::Nothing)
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    // Only the daemon logs its activity, the other commands print their result.
+    let level = if matches!(args.command, Command::Daemon) {
+        Level::INFO
+    } else {
+        Level::WARN
+    };
+    let _ = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(io::stderr)
+        .try_init();
+
+    let status = match run(args.command, args.json).await {
+        Ok(report) => report.print(args.json),
+        Err(err) => Err(err),
+    };
+    if let Err(err) = status {
+        output::print_error(args.json, &err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,116 @@
+use std::{fmt, path::PathBuf};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use app_controller::status::Status as RunningStatus;
+use autostart::Autostart;
+
+use crate::{
+    config::Settings,
+    status::{Running, Status},
+};
+
+/// Result of a command, printed as text or, with `--json`, as a JSON object tagged by
+/// `result`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub(crate) enum Report {
+    /// Status of the running idler.
+    Running(Box<RunningStatus>),
+    /// Stored settings, when no idler is running.
+    Status(Status),
+    Done {
+        message: String,
+    },
+    Changed {
+        setting: String,
+        value: String,
+    },
+    Saved {
+        path: PathBuf,
+    },
+    Imported {
+        count: usize,
+        path: PathBuf,
+    },
+    Profiles {
+        names: Vec<String>,
+    },
+    /// Rules of the weekly schedule, numbered from 1 when printed.
+    Rules {
+        rules: Vec<String>,
+    },
+    Autostart {
+        autostart: Autostart,
+        location: String,
+    },
+    /// Exported settings, printed as the bare JSON map so they can be imported again.
+    #[serde(skip)]
+    Settings(Settings),
+    /// The command printed its own output.
+    #[serde(skip)]
+    Nothing,
+}
+
+impl Report {
+    /// # Errors
+    ///
+    /// Returns an error if the report can't be serialized.
+    pub(crate) fn print(&self, json: bool) -> Result<()> {
+        match self {
+            Report::Nothing => {}
+            Report::Settings(settings) => println!("{}", serde_json::to_string_pretty(settings)?),
+            _ if json => println!("{}", serde_json::to_string(self)?),
+            _ => println!("{self}"),
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Report::Running(status) => write!(f, "{}", Running(status)),
+            Report::Status(status) => write!(f, "{status}"),
+            Report::Done { message } => write!(f, "{message}"),
+            Report::Changed { setting, value } => write!(f, "{setting} set to {value}"),
+            Report::Saved { path } => write!(f, "Saved the settings to {}", path.display()),
+            Report::Imported { count, path } => {
+                write!(f, "Applied {count} settings from {}", path.display())
+            }
+            Report::Profiles { names } if names.is_empty() => write!(f, "No saved profiles"),
+            Report::Profiles { names } => write!(f, "{}", names.join("\n")),
+            Report::Rules { rules } if rules.is_empty() => write!(f, "No weekly rules"),
+            Report::Rules { rules } => {
+                let lines: Vec<String> = rules
+                    .iter()
+                    .enumerate()
+                    .map(|(index, rule)| format!("{}. {rule}", index + 1))
+                    .collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+            Report::Autostart {
+                autostart: autostart @ Autostart::Stale { .. },
+                location,
+            } => write!(
+                f,
+                "Autostart: {autostart} in {location}, run `autostart enable` to fix it"
+            ),
+            Report::Autostart {
+                autostart,
+                location,
+            } => write!(f, "Autostart: {autostart} in {location}"),
+            Report::Settings(_) | Report::Nothing => Ok(()),
+        }
+    }
+}
+
+/// Prints `err` on stderr, or as `{ "error": ... }` on stdout with `--json`.
+pub(crate) fn print_error(json: bool, err: &anyhow::Error) {
+    if json {
+        println!("{}", serde_json::json!({ "error": err.to_string() }));
+    } else {
+        eprintln!("Error: {err}");
+    }
+}
@@ -0,0 +1,227 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+use anyhow::{Result, anyhow};
+use chrono::Local;
+use serde::Serialize;
+
+use app_controller::{
+    status::{Status as RunningStatus, WindowStatus},
+    weekly,
+};
+use event_bus::IdlerMode;
+use idler_utils::Timer;
+use registry_ops::{RegistryEntries, RegistryState, SettingsStore, ShutdownTime, WeeklySchedule};
+
+#[derive(Debug, Serialize)]
+pub(crate) struct TimerStatus {
+    pub mode: String,
+    /// Deadline as `%H:%M`.
+    pub deadline: String,
+    pub remaining_seconds: u64,
+}
+
+/// The stored settings, timer and stop time, shown when no idler is running.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Serialize)]
+pub(crate) struct Status {
+    pub force_interval: u64,
+    pub last_injection: String,
+    pub timer: Option<TimerStatus>,
+    /// Daily stop time as `%H:%M`, `None` when cancelled.
+    pub stop_time: Option<String>,
+    /// What happens at the stop time, e.g. `sleep`.
+    pub stop_action: String,
+    /// The weekly schedule window, `None` without rules.
+    pub weekly: Option<WindowStatus>,
+    pub logging: bool,
+    pub metrics_enabled: bool,
+    pub metrics_port: u16,
+    pub api_enabled: bool,
+    pub api_port: u16,
+    pub mqtt_enabled: bool,
+    pub mqtt_broker: String,
+    pub mqtt_topic: String,
+}
+
+fn read(settings: &SettingsStore, entry: RegistryEntries) -> Result<String> {
+    match settings.get(entry).lock() {
+        Ok(setting) => Ok(setting.last_data.clone()),
+        Err(err) => Err(anyhow!("Failed to lock {entry}, err: {err}")),
+    }
+}
+
+pub(crate) fn parse<T: FromStr>(settings: &SettingsStore, entry: RegistryEntries) -> Result<T> {
+    let data = read(settings, entry)?;
+    data.parse()
+        .map_err(|_| anyhow!("Found invalid {entry} value {data:?}"))
+}
+
+fn is_enabled(settings: &SettingsStore, entry: RegistryEntries) -> Result<bool> {
+    Ok(read(settings, entry)? == RegistryState::Enabled.to_string())
+}
+
+/// Reads the status from `settings`.
+///
+/// # Errors
+///
+/// Returns an error if a setting can't be read or holds an invalid value.
+pub(crate) fn stored(settings: &SettingsStore) -> Result<Status> {
+    let timer = Timer::load(settings)
+        .filter(|timer| !timer.is_expired())
+        .map(|timer| TimerStatus {
+            mode: timer.mode.to_string(),
+            deadline: timer.deadline.format("%H:%M").to_string(),
+            remaining_seconds: timer.remaining().as_secs(),
+        });
+    let stop_time = parse::<ShutdownTime>(settings, RegistryEntries::ShutdownTime)?
+        .time()
+        .map(|time| time.format("%H:%M").to_string());
+    let weekly: WeeklySchedule = parse(settings, RegistryEntries::WeeklySchedule)?;
+    Ok(Status {
+        force_interval: parse(settings, RegistryEntries::ForceInterval)?,
+        last_injection: read(settings, RegistryEntries::LastRobotInput)?,
+        timer,
+        stop_time,
+        stop_action: read(settings, RegistryEntries::ScheduleAction)?,
+        weekly: weekly::window(&weekly, Local::now()).map(WindowStatus::from),
+        logging: is_enabled(settings, RegistryEntries::LogStatistics)?,
+        metrics_enabled: is_enabled(settings, RegistryEntries::MetricsEndpoint)?,
+        metrics_port: parse(settings, RegistryEntries::MetricsPort)?,
+        api_enabled: is_enabled(settings, RegistryEntries::ApiEndpoint)?,
+        api_port: parse(settings, RegistryEntries::ApiPort)?,
+        mqtt_enabled: is_enabled(settings, RegistryEntries::MqttEndpoint)?,
+        mqtt_broker: read(settings, RegistryEntries::MqttBroker)?,
+        mqtt_topic: read(settings, RegistryEntries::MqttTopic)?,
+    })
+}
+
+fn countdown(seconds: u64) -> String {
+    idler_utils::format_countdown(Duration::from_secs(seconds))
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled { "on" } else { "off" }
+}
+
+fn window(window: Option<&WindowStatus>) -> String {
+    let Some(window) = window else {
+        return "none".to_string();
+    };
+    let state = if window.open { "open" } else { "closed" };
+    match &window.until {
+        Some(until) => format!("{state} until {until}"),
+        None => state.to_string(),
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Force interval: {} s", self.force_interval)?;
+        writeln!(f, "Last injection: {}", self.last_injection)?;
+        match &self.timer {
+            Some(timer) => writeln!(
+                f,
+                "Timer:          {} until {} ({} left)",
+                timer.mode,
+                timer.deadline,
+                countdown(timer.remaining_seconds)
+            )?,
+            None => writeln!(f, "Timer:          none")?,
+        }
+        match &self.stop_time {
+            Some(time) => writeln!(f, "Stop time:      {time}, then {}", self.stop_action)?,
+            None => writeln!(f, "Stop time:      none")?,
+        }
+        writeln!(f, "Weekly:         {}", window(self.weekly.as_ref()))?;
+        writeln!(f, "Logging:        {}", on_off(self.logging))?;
+        writeln!(
+            f,
+            "Metrics:        {} (port {})",
+            on_off(self.metrics_enabled),
+            self.metrics_port
+        )?;
+        writeln!(
+            f,
+            "REST API:       {} (port {})",
+            on_off(self.api_enabled),
+            self.api_port
+        )?;
+        write!(
+            f,
+            "MQTT:           {} ({} under {})",
+            on_off(self.mqtt_enabled),
+            self.mqtt_broker,
+            self.mqtt_topic
+        )
+    }
+}
+
+/// Text form of the status of the running idler.
+pub(crate) struct Running<'a>(pub &'a RunningStatus);
+
+impl fmt::Display for Running<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = self.0;
+        let mode = match status.mode {
+            IdlerMode::Active => "keeping awake",
+            IdlerMode::Paused => "paused",
+            IdlerMode::TimedPause => "paused for a while",
+            IdlerMode::TimedKeepAwake => "keeping awake for a while",
+        };
+        writeln!(f, "Mode:           {mode}")?;
+        match status.idle_seconds {
+            Some(seconds) => writeln!(f, "Idle:           {seconds} s")?,
+            None => writeln!(f, "Idle:           unknown")?,
+        }
+        match status.next_injection_seconds {
+            Some(seconds) => writeln!(f, "Next injection: in {seconds} s")?,
+            None => writeln!(f, "Next injection: none")?,
+        }
+        writeln!(f, "Force interval: {} s", status.force_interval)?;
+        writeln!(f, "Last injection: {}", status.last_injection)?;
+        match &status.timer.mode {
+            Some(timer_mode) => writeln!(
+                f,
+                "Timer:          {timer_mode} until {} ({} left)",
+                status.timer.deadline,
+                countdown(status.timer.remaining_seconds)
+            )?,
+            None => writeln!(f, "Timer:          none")?,
+        }
+        match status.schedule.remaining_seconds {
+            Some(seconds) if status.schedule.active => writeln!(
+                f,
+                "Stop time:      {} ({} left), then {}",
+                status.schedule.time,
+                countdown(seconds),
+                status.schedule.action
+            )?,
+            _ => writeln!(f, "Stop time:      none")?,
+        }
+        writeln!(
+            f,
+            "Weekly:         {}",
+            window(status.schedule.weekly.as_ref())
+        )?;
+        writeln!(f, "Logging:        {}", on_off(status.logging))?;
+        writeln!(
+            f,
+            "Metrics:        {} (port {})",
+            on_off(status.metrics_enabled),
+            status.metrics_port
+        )?;
+        writeln!(
+            f,
+            "REST API:       {} (port {})",
+            on_off(status.api_enabled),
+            status.api_port
+        )?;
+        write!(
+            f,
+            "MQTT:           {} ({} under {})",
+            on_off(status.mqtt_enabled),
+            status.mqtt_broker,
+            status.mqtt_topic
+        )
+    }
+}
@@ -0,0 +1,66 @@
+use tracing::debug;
+
+use anyhow::{Result, anyhow};
+
+use control::{Client, Request};
+use registry_ops::{RegistryEntries, SettingsStore};
+
+/// Where changes go: the running idler when one is listening, else the stored settings it
+/// reads when it starts.
+pub(crate) enum Target {
+    Running(Client),
+    Stored(Box<SettingsStore>),
+}
+
+impl Target {
+    pub(crate) async fn connect() -> Target {
+        match Client::connect().await {
+            Ok(client) => Target::Running(client),
+            Err(err) => {
+                debug!("Using the stored settings, err: {err}");
+                Target::Stored(Box::new(SettingsStore::load()))
+            }
+        }
+    }
+
+    /// Writes the already validated `value` to `entry`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the idler rejects the value or the setting can't be written.
+    pub(crate) async fn set(&mut self, entry: RegistryEntries, value: String) -> Result<()> {
+        match self {
+            Target::Running(client) => {
+                let request = Request::Set {
+                    setting: entry,
+                    value,
+                };
+                client.request(request).await.map(drop)
+            }
+            Target::Stored(settings) => settings
+                .get(entry)
+                .lock()
+                .map_err(|err| anyhow!("Failed to lock {entry}, err: {err}"))?
+                .set_registry_data(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn writes_the_stored_settings() {
+        let mut target = Target::Stored(Box::new(SettingsStore::in_memory()));
+        target
+            .set(RegistryEntries::ForceInterval, "120".to_string())
+            .await
+            .unwrap();
+        let Target::Stored(settings) = target else {
+            unreachable!();
+        };
+        let interval = settings.get(RegistryEntries::ForceInterval);
+        assert_eq!(interval.lock().unwrap().last_data, "120");
+    }
+}
@@ -0,0 +1,239 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::{error, info};
+
+use tauri::{AppHandle, Manager, api::notification::Notification};
+
+use app_controller::{AppContext, POSTPONE, action};
+use event_bus::{Event, EventBus};
+
+use crate::tray;
+
+/// Tauri event carrying every bus event to the windows.
+const WINDOW_EVENT: &str = "idler-event";
+const RESET_FAILED_COOLDOWN: Duration = Duration::from_secs(60 * 60);
+/// Identifier of the postpone action of the stop warning.
+#[cfg(all(unix, not(target_os = "macos")))]
+const POSTPONE_ACTION: &str = "postpone";
+
+/// Spawns the subscribers logging events, showing notifications, forwarding events to the
+/// windows, showing the window when asked and closing the app once the stop action or a
+/// control client asks.
+pub(crate) fn spawn_subscribers(app: &AppHandle, events: &EventBus) {
+    let mut subscription = events.subscribe("logger");
+    tauri::async_runtime::spawn(async move {
+        while let Some(event) = subscription.next().await {
+            info!("{event}");
+        }
+    });
+
+    let mut subscription = events.subscribe("notifications");
+    let notifier = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut last_reset_failed: Option<Instant> = None;
+        let warning = StopWarning::default();
+        while let Some(event) = subscription.next().await {
+            match event {
+                Event::ScheduleFired { time } => {
+                    warning.close().await;
+                    let action = stop_action(&notifier);
+                    notify(
+                        &notifier,
+                        &format!("Reached the stop time {time}, {action}"),
+                    );
+                }
+                Event::StopWarning { time, minutes } => {
+                    let action = stop_action(&notifier);
+                    warning
+                        .show(
+                            &notifier,
+                            &format!("Stop time {time} in {minutes} min, then {action}"),
+                        )
+                        .await;
+                }
+                Event::IdleResetFailed { .. } => {
+                    if last_reset_failed.is_some_and(|at| at.elapsed() < RESET_FAILED_COOLDOWN) {
+                        continue;
+                    }
+                    last_reset_failed = Some(Instant::now());
+                    notify(
+                        &notifier,
+                        "Failed to reset the idle time, the system may sleep",
+                    );
+                }
+                _ => {}
+            }
+        }
+    });
+
+    let mut subscription = events.subscribe("exit requests");
+    let exiting = app.clone();
+    tauri::async_runtime::spawn(async move {
+        while let Some(event) = subscription.next().await {
+            if event == Event::ExitRequested {
+                // Shutting down the idle service blocks until its task exits.
+                tokio::task::block_in_place(|| exiting.state::<Arc<AppContext>>().shutdown());
+                info!("Exiting app with app handle");
+                exiting.exit(0);
+                return;
+            }
+        }
+    });
+
+    let mut subscription = events.subscribe("show requests");
+    let shower = app.clone();
+    tauri::async_runtime::spawn(async move {
+        while let Some(event) = subscription.next().await {
+            if event == Event::ShowRequested {
+                tray::show_window(&shower);
+            }
+        }
+    });
+
+    let mut subscription = events.subscribe("windows");
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        while let Some(event) = subscription.next().await {
+            if let Err(err) = app.emit_all(WINDOW_EVENT, &event) {
+                error!("Failed to forward event to windows, err: {err}");
+            }
+        }
+    });
+}
+
+/// The configured stop action as shown to the user, `exit` when it can't be read.
+fn stop_action(app: &AppHandle) -> String {
+    action::configured(&app.state::<Arc<AppContext>>())
+        .map_or_else(|_| "exit".to_string(), |action| action.to_string())
+}
+
+/// The stop warning on screen, with an action postponing the stop by [`POSTPONE`].
+#[cfg(all(unix, not(target_os = "macos")))]
+#[derive(Default)]
+struct StopWarning {
+    /// Notification id of the warning shown last, while it is open.
+    shown: Arc<std::sync::Mutex<Option<u32>>>,
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl StopWarning {
+    /// Replaces the warning on screen with `body`. A blocking thread waits for the action
+    /// until the notification is closed, by the user or by [`StopWarning::close`].
+    async fn show(&self, app: &AppHandle, body: &str) {
+        self.close().await;
+        let app = app.clone();
+        let body = body.to_string();
+        let shown = Arc::clone(&self.shown);
+        tauri::async_runtime::spawn_blocking(move || {
+            let name = &app.package_info().name;
+            let handle = notify_rust::Notification::new()
+                .appname(name)
+                .summary(name)
+                .body(&body)
+                .action(
+                    POSTPONE_ACTION,
+                    &format!("Postpone {} min", POSTPONE.as_secs() / 60),
+                )
+                .show();
+            let handle = match handle {
+                Ok(handle) => handle,
+                Err(err) => {
+                    error!("Failed to show notification with err: {err}");
+                    return;
+                }
+            };
+            let id = handle.id();
+            set_shown(&shown, |shown| *shown = Some(id));
+            handle.wait_for_action(|action| {
+                if action != POSTPONE_ACTION {
+                    return;
+                }
+                let context = Arc::clone(app.state::<Arc<AppContext>>().inner());
+                tauri::async_runtime::spawn(async move {
+                    if let Err(err) = context.postpone_stop(POSTPONE).await {
+                        error!("Failed to postpone the stop, err: {err}");
+                    }
+                    // Some servers keep a notification open after its action.
+                    close_notification(id).await;
+                });
+            });
+            set_shown(&shown, |shown| {
+                if *shown == Some(id) {
+                    *shown = None;
+                }
+            });
+        });
+    }
+
+    /// Closes the warning on screen, if any, which ends the thread waiting for its action.
+    async fn close(&self) {
+        let mut id = None;
+        set_shown(&self.shown, |shown| id = shown.take());
+        if let Some(id) = id {
+            close_notification(id).await;
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn set_shown(shown: &std::sync::Mutex<Option<u32>>, update: impl FnOnce(&mut Option<u32>)) {
+    match shown.lock() {
+        Ok(mut shown) => update(&mut shown),
+        Err(err) => error!("Failed to lock the shown stop warning, err: {err}"),
+    }
+}
+
+/// Asks the notification server on the session bus to close notification `id`.
+#[cfg(all(unix, not(target_os = "macos")))]
+async fn close_notification(id: u32) {
+    let closed = match zbus::Connection::session().await {
+        Ok(connection) => connection
+            .call_method(
+                Some("org.freedesktop.Notifications"),
+                "/org/freedesktop/Notifications",
+                Some("org.freedesktop.Notifications"),
+                "CloseNotification",
+                &id,
+            )
+            .await
+            .map(|_| ()),
+        Err(err) => Err(err),
+    };
+    if let Err(err) = closed {
+        error!("Failed to close notification {id}, err: {err}");
+    }
+}
+
+/// The stop warning, without a postpone action: Tauri 1 notifications have none on Windows
+/// and macOS, so it points to the postpone entry of the tray menu instead. Shown
+/// notifications can't be closed either, they time out on their own.
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+#[derive(Default)]
+struct StopWarning;
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+impl StopWarning {
+    #[allow(clippy::unused_async)]
+    async fn show(&self, app: &AppHandle, body: &str) {
+        let minutes = POSTPONE.as_secs() / 60;
+        notify(
+            app,
+            &format!("{body}. Postpone it by {minutes} min from the tray menu"),
+        );
+    }
+
+    #[allow(clippy::unused_async)]
+    async fn close(&self) {}
+}
+
+fn notify(app: &AppHandle, body: &str) {
+    let status = Notification::new(&app.config().tauri.bundle.identifier)
+        .title(&app.package_info().name)
+        .body(body)
+        .show();
+    if let Err(err) = status {
+        error!("Failed to show notification with err: {err}");
+    }
+}
@@ -0,0 +1,81 @@
+use std::time::Duration;
+use tracing::{debug, info};
+
+use anyhow::{Result, anyhow};
+use clap::Subcommand;
+
+use control::{Client, Request};
+
+/// How long a second launch waits for a starting idler to open its control endpoint.
+const CONNECT_RETRIES: u32 = 20;
+const CONNECT_DELAY: Duration = Duration::from_millis(250);
+
+/// Commands of the tray command line, applied by the first instance and handed to it by the
+/// next ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Subcommand)]
+pub(crate) enum Action {
+    /// Show the window
+    #[command(alias = "show-window")]
+    Show,
+    /// Stop keeping the system awake, for a while when given e.g. `1h`
+    Pause {
+        #[arg(value_parser = idler_utils::parse_duration)]
+        duration: Option<Duration>,
+    },
+    /// Keep the system awake for a while and stop afterwards, e.g. `3h`
+    KeepAwake {
+        #[arg(value_parser = idler_utils::parse_duration)]
+        duration: Duration,
+    },
+    /// Keep the system awake again, cancelling any timer
+    Resume,
+}
+
+impl Action {
+    fn request(self) -> Request {
+        match self {
+            Action::Show => Request::Show,
+            Action::Pause { duration: None } => Request::Pause,
+            Action::Pause {
+                duration: Some(duration),
+            } => Request::PauseFor {
+                seconds: duration.as_secs(),
+            },
+            Action::KeepAwake { duration } => Request::KeepAwakeFor {
+                seconds: duration.as_secs(),
+            },
+            Action::Resume => Request::Resume,
+        }
+    }
+}
+
+/// Connects to the running idler, retrying while it is still starting up.
+async fn connect() -> Result<Client> {
+    let mut attempt = 1;
+    loop {
+        match Client::connect().await {
+            Ok(client) => return Ok(client),
+            Err(err) if attempt < CONNECT_RETRIES => {
+                debug!("Waiting for the running idler, err: {err}");
+                attempt += 1;
+                tokio::time::sleep(CONNECT_DELAY).await;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Hands `action` to the running idler, showing its window when the command line had none.
+///
+/// # Errors
+///
+/// Returns an error if the running idler can't be reached or rejects the request.
+pub(crate) async fn forward(action: Option<Action>) -> Result<()> {
+    let action = action.unwrap_or(Action::Show);
+    let mut client = connect()
+        .await
+        .map_err(|err| anyhow!("Another idler is running but can't be reached, err: {err}"))?;
+    client.request(action.request()).await?;
+    info!("Handed {action:?} to the running idler");
+    Ok(())
+}
@@ -1,33 +1,136 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 extern crate msvc_spectre_libs;
 
+use std::{sync::Arc, time::Duration};
 use tracing::{error, info};
 
 use anyhow::Result;
+use clap::Parser;
 use tauri::{Builder, RunEvent, generate_context};
 
+use app_controller::AppContext;
+use event_bus::EventBus;
+use idler_utils::{TimedMode, Timer};
+use instance::Action;
+use registry_ops::SettingsStore;
+
+mod events;
+mod instance;
 mod registry_plugin;
+mod status;
 mod tray;
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    #[cfg(debug_assertions)]
-    tauri::async_runtime::set(tokio::runtime::Handle::current());
+#[derive(Debug, Parser)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+struct Args {
+    /// Handed to the running idler when there is one
+    #[command(subcommand)]
+    action: Option<Action>,
+
+    /// Stop keeping the system awake for a while, e.g. `30m` or `1h30m`
+    #[arg(long, value_name = "DURATION", value_parser = idler_utils::parse_duration)]
+    pause_for: Option<Duration>,
+
+    /// Keep the system awake for a while and stop afterwards, e.g. `3h`
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = idler_utils::parse_duration,
+        conflicts_with = "pause_for"
+    )]
+    keep_awake_for: Option<Duration>,
+}
+
+impl Args {
+    /// The subcommand, or the timer flags kept for older shortcuts.
+    fn action(&self) -> Option<Action> {
+        self.action
+            .or(self.pause_for.map(|duration| Action::Pause {
+                duration: Some(duration),
+            }))
+            .or(self
+                .keep_awake_for
+                .map(|duration| Action::KeepAwake { duration }))
+    }
+}
+
+fn store_timer(settings: &SettingsStore, mode: TimedMode, duration: Duration) {
+    match Timer::new(mode, duration) {
+        Ok(timer) => Timer::store(settings, Some(&timer)),
+        Err(err) => error!("Failed to create {mode} timer with err: {err}"),
+    }
+}
+
+/// Serves the control endpoint, so the CLI can drive the tray app like the daemon.
+fn spawn_control(context: Arc<AppContext>) {
+    tauri::async_runtime::spawn(async move {
+        match control::Listener::bind().await {
+            Ok(listener) => control::serve(listener, context).await,
+            Err(err) => error!("Failed to open the control endpoint with err: {err}"),
+        }
+    });
+}
+
+/// Serves the idler on the D-Bus session bus for desktop widgets and scripts.
+#[cfg(not(windows))]
+fn spawn_dbus(context: Arc<AppContext>) {
+    tauri::async_runtime::spawn(async move {
+        if let Err(err) = dbus_service::serve(context).await {
+            error!("Failed to serve on D-Bus with err: {err}");
+        }
+    });
+}
+
+fn main() -> Result<()> {
     let _ = tracing_subscriber::fmt::try_init();
-    idler_utils::ExecState::start();
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .on_thread_start(mitigations::hide_current_thread_from_debuggers)
+        .build()?;
+    // Tauri, the idle service and the scheduler all share this runtime.
+    tauri::async_runtime::set(runtime.handle().clone());
+    runtime.block_on(run())
+}
+
+async fn run() -> Result<()> {
+    let args = Args::parse();
+    let action = args.action();
+    // Held until the app exits, so later launches hand their command line over instead of
+    // starting a second idle loop.
+    let _instance = match control::InstanceLock::acquire() {
+        Ok(Some(lock)) => Some(lock),
+        Ok(None) => {
+            info!("Another idler is running, handing over the command line");
+            return instance::forward(action).await;
+        }
+        Err(err) => {
+            error!("Failed to check for another idler with err: {err}");
+            None
+        }
+    };
     mitigations::apply_mitigations().await;
 
+    let context = Arc::new(AppContext::new(EventBus::new()));
+
+    // The idle service restores the stored timer once it starts.
+    match action {
+        Some(Action::Pause {
+            duration: Some(duration),
+        }) => store_timer(context.settings(), TimedMode::Pause, duration),
+        Some(Action::KeepAwake { duration }) => {
+            store_timer(context.settings(), TimedMode::KeepAwake, duration);
+        }
+        Some(Action::Resume) => Timer::store(context.settings(), None),
+        _ => {}
+    }
+
     let tauri_app = Builder::default()
+        .manage(Arc::clone(&context))
         .plugin(registry_plugin::init())
         .system_tray(tray::get_tray_menu())
         .on_system_tray_event(move |app, event| {
             tray::handle_system_tray_event(app, event);
         })
-        .setup(|app| {
-            let app_handle = app.handle();
-            let _ = cell_data::TAURI_APP_HANDLE.set(app_handle);
-            Ok(())
-        })
         .build(generate_context!("tauri.conf.json"));
 
     match tauri_app {
@@ -37,22 +140,39 @@ async fn main() -> Result<()> {
             return Err(err.into());
         }
     }
-    .run(move |_, event| match event {
+    .run(move |app_handle, event| match event {
         RunEvent::Ready => {
             info!("App is ready");
-            idler_utils::spawn_idle_threads();
+            events::spawn_subscribers(app_handle, context.events());
+            metrics_endpoint::spawn(Arc::clone(&context));
+            rest_api::spawn(Arc::clone(&context));
+            mqtt_bridge::spawn(Arc::clone(&context));
+            status::spawn_publisher(app_handle.clone(), Arc::clone(&context));
+            if let Err(err) = context.schedule_stored_stop() {
+                error!("Not scheduling the stored stop time, err: {err}");
+            }
+            context.start_services();
+            app_controller::weekly::spawn(Arc::clone(&context));
+            app_controller::action::spawn(Arc::clone(&context));
+            match action {
+                Some(Action::Show) => tray::show_window(app_handle),
+                Some(Action::Pause { duration: None }) => {
+                    if let Some(service) = context.idle_service() {
+                        service.pause();
+                    }
+                }
+                _ => {}
+            }
+            spawn_control(Arc::clone(&context));
+            #[cfg(not(windows))]
+            spawn_dbus(Arc::clone(&context));
+            tray::spawn_timer_countdown(app_handle.clone());
         }
         RunEvent::ExitRequested { api, .. } => {
             api.prevent_exit();
         }
         RunEvent::Exit => {
-            idler_utils::ExecState::stop();
-            let app_handle = cell_data::TAURI_APP_HANDLE.get().unwrap_or_else(|| {
-                error!("Failed to get app handle");
-                std::process::exit(0);
-            });
-            info!("Exiting app with app handle");
-            app_handle.exit(0);
+            context.shutdown();
         }
         _ => {}
     });
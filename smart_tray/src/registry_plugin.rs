@@ -1,158 +1,261 @@
 #![allow(clippy::needless_pass_by_value)]
 use tauri::{
-    Manager, Runtime, State, command,
+    Runtime, State, command,
     plugin::{Builder, TauriPlugin},
 };
-use tracing::{debug, error, trace, warn};
+use tracing::{debug, trace};
 
-use anyhow::Result;
-use std::sync::{
-    Mutex,
-    atomic::{AtomicBool, Ordering},
-    mpsc,
+use std::sync::Arc;
+
+use app_controller::{
+    AppContext, CommandError, POSTPONE, action,
+    status::{self, Status, TimerStatus},
+    weekly,
+};
+use autostart::{Autostart, Mode};
+use registry_ops::{
+    ForceInterval, MetricsPort, RegistryEntries, RegistryState, ScheduleAction, SettingError,
+    ShutdownTime, StatisticsRetention, StopWarnings, Toggle, WeeklySchedule,
 };
 
-use registry_ops::RegistryState;
+#[command(rename_all = "snake_case")]
+pub fn get_status(context: State<Arc<AppContext>>) -> Result<Status, CommandError> {
+    status::current(&context)
+}
 
+/// Use `get_status` instead.
 #[command(rename_all = "snake_case")]
-pub fn get_shutdown_state(channel: State<app_controller::ControllerChannel>) -> bool {
-    channel.active.load(Ordering::SeqCst)
+pub fn get_shutdown_state(context: State<Arc<AppContext>>) -> Result<bool, CommandError> {
+    Ok(status::schedule(&context)?.active)
 }
 
+/// Use `get_status` instead.
 #[command(rename_all = "snake_case")]
-pub fn get_shutdown_clock() -> String {
-    let setting = match cell_data::REGISTRY_SHUTDOWN_TIME.lock() {
-        Ok(h) => h,
-        Err(err) => {
-            error!("Failed to lock shutdown with err: {err}");
-            return String::new();
-        }
-    };
-    trace!("Got shutdown status: {:?}", setting);
-    setting.last_data.to_string()
+pub fn get_shutdown_clock(context: State<Arc<AppContext>>) -> Result<String, CommandError> {
+    Ok(status::schedule(&context)?.time)
 }
 
 #[command(rename_all = "snake_case")]
-pub fn set_shutdown(channel_state: State<app_controller::ControllerChannel>, hour: &str) {
-    let tx = match channel_state.tx.lock() {
-        Ok(val) => val,
-        Err(err) => {
-            error!("Failed to lock tx, err: {err}");
-            return;
-        }
-    };
-    debug!("Sent shutdown date:, {hour}");
-    let _ = tx.send(hour.to_string());
+pub fn set_shutdown(context: State<Arc<AppContext>>, hour: &str) -> Result<(), CommandError> {
+    let time: ShutdownTime = hour.parse()?;
+    context.schedule_stop(time)?;
+    debug!("Scheduled shutdown: {time}");
+    Ok(())
+}
+
+/// Use `get_status` instead.
+#[command(rename_all = "snake_case")]
+pub fn get_data(context: State<Arc<AppContext>>, data: &str) -> Result<String, CommandError> {
+    let status = status::current(&context)?;
+    trace!("Got data for {data:?} from status: {status:?}");
+    match data {
+        "force_interval" => Ok(status.force_interval.to_string()),
+        "robot_input" => Ok(status.last_injection),
+        "metrics_port" => Ok(status.metrics_port.to_string()),
+        _ => Err(SettingError::Unknown(data.to_string()).into()),
+    }
+}
 
-    channel_state.active.store(hour != "STOP", Ordering::SeqCst);
+/// Use `get_status` instead.
+#[command(rename_all = "snake_case")]
+pub fn get_state(context: State<Arc<AppContext>>, data: &str) -> Result<bool, CommandError> {
+    let toggle: Toggle = data.parse()?;
+    let status = status::current(&context)?;
+    Ok(match toggle {
+        Toggle::Logging => status.logging,
+        Toggle::Metrics => status.metrics_enabled,
+        Toggle::Api => status.api_enabled,
+        Toggle::Mqtt => status.mqtt_enabled,
+    })
+}
 
-    let mut setting = match cell_data::REGISTRY_SHUTDOWN_TIME.lock() {
-        Ok(set) => set,
-        Err(err) => {
-            error!("Failed to lock app_state.shutdown, err: {err}");
-            return;
-        }
-    };
-    let status = setting.set_registry_data(hour);
-    trace!("Set shutdown status to: {status:?}");
+#[command(rename_all = "snake_case")]
+pub fn set_registry_state(
+    context: State<Arc<AppContext>>,
+    data: &str,
+    wanted_status: bool,
+) -> Result<(), CommandError> {
+    let toggle: Toggle = data.parse()?;
+    context.store(toggle.registry_entry(), &RegistryState::from(wanted_status))
 }
 
 #[command(rename_all = "snake_case")]
-pub fn get_data(data: &str) -> String {
-    let setting = match data {
-        "force_interval" => &cell_data::REGISTRY_FORCE_INTERVAL,
-        "robot_input" => &cell_data::REGISTRY_ROBOT_INPUT,
+pub fn get_statistics() -> idle_stats::Statistics {
+    idle_stats::snapshot()
+}
+
+#[command(rename_all = "snake_case")]
+pub fn set_statistics_retention(
+    context: State<Arc<AppContext>>,
+    days: u32,
+) -> Result<(), CommandError> {
+    let days = StatisticsRetention::try_from(days)?;
+    context.store(RegistryEntries::StatisticsRetention, &days)
+}
+
+#[command(rename_all = "snake_case")]
+pub fn set_force_interval(
+    context: State<Arc<AppContext>>,
+    interval: &str,
+) -> Result<(), CommandError> {
+    let interval: ForceInterval = interval.parse()?;
+    context.store(RegistryEntries::ForceInterval, &interval)
+}
+
+#[command(rename_all = "snake_case")]
+pub fn set_metrics_port(context: State<Arc<AppContext>>, port: u16) -> Result<(), CommandError> {
+    let port = MetricsPort::try_from(port)?;
+    context.store(RegistryEntries::MetricsPort, &port)
+}
+
+#[command(rename_all = "snake_case")]
+pub fn get_timer(context: State<Arc<AppContext>>) -> Result<TimerStatus, CommandError> {
+    Ok(context.running_idle_service()?.timer().into())
+}
+
+#[command(rename_all = "snake_case")]
+pub fn set_timer(
+    context: State<Arc<AppContext>>,
+    mode: &str,
+    duration: &str,
+) -> Result<(), CommandError> {
+    let service = context.running_idle_service()?;
+    let duration = idler_utils::parse_duration(duration)
+        .map_err(|err| CommandError::InvalidInput(err.to_string()))?;
+    let status = match mode {
+        "pause" => service.pause_for(duration),
+        "keep_awake" => service.keep_awake_for(duration),
         _ => {
-            warn!("Found invalid data in request: {data}");
-            return String::new();
-        }
-    };
-    let setting = match setting.lock() {
-        Ok(set) => set,
-        Err(err) => {
-            error!("Failed to get state: {data:?} with err: {err}");
-            return String::new();
+            return Err(CommandError::InvalidInput(format!(
+                "Unknown timer mode {mode:?}, expected pause or keep_awake"
+            )));
         }
     };
-    trace!("Got data: {:?}", setting);
-    setting.last_data.to_string()
+    trace!("Set timer: {status:?}, mode: {mode:?}");
+    status.map_err(|err| CommandError::InvalidInput(err.to_string()))
 }
 
 #[command(rename_all = "snake_case")]
-pub fn get_state(data: &str) -> bool {
-    let setting = if data == "logging" {
-        &cell_data::REGISTRY_LOG_STATISTICS
-    } else {
-        warn!("Found invalid data in request: {data:?}");
-        return false;
-    };
-    let setting = match setting.lock() {
-        Ok(set) => set,
-        Err(err) => {
-            error!("Failed to get state: {data:?} with err: {err}");
-            return false;
-        }
-    };
-    trace!("Got state: {:?}", setting);
-    if setting.is_enabled() {
-        return true;
-    }
-    false
+pub fn cancel_timer(context: State<Arc<AppContext>>) -> Result<(), CommandError> {
+    context.running_idle_service()?.cancel_timer();
+    Ok(())
+}
+
+fn rule_texts(schedule: &WeeklySchedule) -> Vec<String> {
+    schedule.rules().iter().map(ToString::to_string).collect()
 }
 
+/// Rules of the weekly schedule, e.g. `mon-thu 08:30-17:30`. Commands number them from 1.
 #[command(rename_all = "snake_case")]
-pub fn set_registry_state(data: &str, wanted_status: bool) {
-    let setting = if data == "logging" {
-        &cell_data::REGISTRY_LOG_STATISTICS
-    } else {
-        warn!("Found incorrect data in request: {data:?}");
-        return;
-    };
-    let mut setting = match setting.lock() {
-        Ok(set) => set,
-        Err(err) => {
-            error!("Failed to get state: {data:?} with err: {err}");
-            return;
-        }
-    };
-    let status: Result<()> = if wanted_status {
-        setting.set_registry_data(RegistryState::Enabled.to_string())
-    } else {
-        setting.set_registry_data(RegistryState::Disabled.to_string())
-    };
-    trace!("Set registry: {status:?}");
+pub fn get_weekly_rules(context: State<Arc<AppContext>>) -> Result<Vec<String>, CommandError> {
+    Ok(rule_texts(&weekly::rules(&context)?))
+}
+
+#[command(rename_all = "snake_case")]
+pub fn add_weekly_rule(
+    context: State<Arc<AppContext>>,
+    rule: &str,
+) -> Result<Vec<String>, CommandError> {
+    Ok(rule_texts(&weekly::add_rule(&context, rule)?))
+}
+
+#[command(rename_all = "snake_case")]
+pub fn update_weekly_rule(
+    context: State<Arc<AppContext>>,
+    number: usize,
+    rule: &str,
+) -> Result<Vec<String>, CommandError> {
+    Ok(rule_texts(&weekly::replace_rule(&context, number, rule)?))
+}
+
+#[command(rename_all = "snake_case")]
+pub fn remove_weekly_rule(
+    context: State<Arc<AppContext>>,
+    number: usize,
+) -> Result<Vec<String>, CommandError> {
+    Ok(rule_texts(&weekly::remove_rule(&context, number)?))
+}
+
+/// What happens at the shutdown time, e.g. `sleep` or `run COMMAND`.
+#[command(rename_all = "snake_case")]
+pub fn get_schedule_action(context: State<Arc<AppContext>>) -> Result<String, CommandError> {
+    Ok(action::configured(&context)?.to_string())
+}
+
+#[command(rename_all = "snake_case")]
+pub fn set_schedule_action(
+    context: State<Arc<AppContext>>,
+    action: &str,
+) -> Result<(), CommandError> {
+    let action: ScheduleAction = action.parse()?;
+    context.store(RegistryEntries::ScheduleAction, &action)
 }
 
+/// Moves the pending shutdown later by [`POSTPONE`], once.
 #[command(rename_all = "snake_case")]
-pub fn set_force_interval(interval: &str) {
-    let status = cell_data::REGISTRY_FORCE_INTERVAL
-        .lock()
-        .unwrap()
-        .set_registry_data(interval);
-    trace!("Set force interval: {status:?}, data: {interval:?}");
+pub async fn postpone_stop(context: State<'_, Arc<AppContext>>) -> Result<(), CommandError> {
+    context.postpone_stop(POSTPONE).await.map(drop)
+}
+
+/// Minutes before the shutdown time to warn at, e.g. `10,2`.
+#[command(rename_all = "snake_case")]
+pub fn get_stop_warnings(context: State<Arc<AppContext>>) -> Result<String, CommandError> {
+    Ok(action::warnings(&context)?.to_string())
+}
+
+#[command(rename_all = "snake_case")]
+pub fn set_stop_warnings(
+    context: State<Arc<AppContext>>,
+    minutes: &str,
+) -> Result<(), CommandError> {
+    let warnings: StopWarnings = minutes.parse()?;
+    context.store(RegistryEntries::StopWarnings, &warnings)
+}
+
+#[command(rename_all = "snake_case")]
+pub fn get_autostart() -> Result<Autostart, CommandError> {
+    autostart::status(Mode::Tray).map_err(|err| CommandError::Registry(err.to_string()))
+}
+
+/// Starts the tray at login, or stops doing so.
+#[command(rename_all = "snake_case")]
+pub fn set_autostart(enabled: bool) -> Result<Autostart, CommandError> {
+    let autostart = if enabled {
+        autostart::enable(Mode::Tray)
+    } else {
+        autostart::disable(Mode::Tray)
+    };
+    autostart.map_err(|err| CommandError::Registry(err.to_string()))
 }
 
 pub fn init<R: Runtime>() -> TauriPlugin<R> {
     Builder::new("general")
-        .setup(|app_handle| {
-            let (tx, rx) = mpsc::channel();
-            let tx = Mutex::new(tx);
-            app_controller::close_app_remote(rx);
-
-            app_handle.manage(app_controller::ControllerChannel {
-                tx,
-                active: AtomicBool::new(false),
-            });
-            Ok(())
-        })
         .invoke_handler(tauri::generate_handler![
+            get_status,
             get_data,
             get_state,
             set_registry_state,
             set_force_interval,
             get_shutdown_clock,
             get_shutdown_state,
-            set_shutdown
+            set_shutdown,
+            get_timer,
+            set_timer,
+            cancel_timer,
+            get_statistics,
+            set_statistics_retention,
+            set_metrics_port,
+            get_autostart,
+            set_autostart,
+            get_weekly_rules,
+            add_weekly_rule,
+            update_weekly_rule,
+            remove_weekly_rule,
+            get_schedule_action,
+            set_schedule_action,
+            get_stop_warnings,
+            set_stop_warnings,
+            postpone_stop
         ])
         .build()
 }
@@ -0,0 +1,52 @@
+use std::{sync::Arc, time::Duration};
+use tracing::error;
+
+use tauri::{AppHandle, Manager};
+use tokio::time::MissedTickBehavior;
+
+use app_controller::{AppContext, status};
+
+/// Tauri event carrying the [`status::Status`] to the controller window.
+const STATUS_EVENT: &str = "idler-status";
+const WINDOW_LABEL: &str = "controller";
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Pushes the [`status::Status`] to the controller window after every event on the bus, and
+/// every second while the window is visible.
+pub(crate) fn spawn_publisher(app: AppHandle, context: Arc<AppContext>) {
+    let mut subscription = context.events().subscribe("status");
+    tauri::async_runtime::spawn(async move {
+        let mut tick = tokio::time::interval(TICK_INTERVAL);
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            tokio::select! {
+                event = subscription.next() => {
+                    if event.is_none() {
+                        return;
+                    }
+                    publish(&app, &context, false);
+                }
+                _ = tick.tick() => publish(&app, &context, true),
+            }
+        }
+    });
+}
+
+fn publish(app: &AppHandle, context: &AppContext, only_if_visible: bool) {
+    let Some(window) = app.get_window(WINDOW_LABEL) else {
+        return;
+    };
+    if only_if_visible && !window.is_visible().unwrap_or(false) {
+        return;
+    }
+    let status = match status::current(context) {
+        Ok(status) => status,
+        Err(err) => {
+            error!("Failed to get status, err: {err}");
+            return;
+        }
+    };
+    if let Err(err) = window.emit(STATUS_EVENT, status) {
+        error!("Failed to push status to window, err: {err}");
+    }
+}
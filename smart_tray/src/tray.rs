@@ -1,24 +1,49 @@
-use std::fmt;
+use std::{fmt, sync::Arc, time::Duration};
 use tracing::{error, info, trace, warn};
 
 use anyhow::Result;
 use tauri::{
     AppHandle, CustomMenuItem, Manager, SystemTray, SystemTrayEvent, SystemTrayMenu,
-    SystemTrayMenuItem, UserAttentionType,
+    SystemTrayMenuItem, SystemTraySubmenu, UserAttentionType,
 };
 
+use app_controller::{AppContext, POSTPONE, status};
+use idler_utils::{TimedMode, Timer};
+
+const PAUSE_PREFIX: &str = "pause:";
+const KEEP_AWAKE_PREFIX: &str = "awake:";
+
+const PAUSE_PRESETS: [(&str, &str); 4] = [
+    ("15m", "15 minutes"),
+    ("30m", "30 minutes"),
+    ("1h", "1 hour"),
+    ("2h", "2 hours"),
+];
+
+const KEEP_AWAKE_PRESETS: [(&str, &str); 4] = [
+    ("1h", "1 hour"),
+    ("2h", "2 hours"),
+    ("3h", "3 hours"),
+    ("4h", "4 hours"),
+];
+
 #[derive(PartialEq)]
 pub enum IdlerMenuItems {
     Show,
+    Timer,
+    CancelTimer,
+    Postpone,
     Quit,
 }
 
 impl fmt::Display for IdlerMenuItems {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if self == &IdlerMenuItems::Show {
-            write!(f, "Show")
-        } else {
-            write!(f, "Quit")
+        match self {
+            IdlerMenuItems::Show => write!(f, "Show"),
+            IdlerMenuItems::Timer => write!(f, "Timer"),
+            IdlerMenuItems::CancelTimer => write!(f, "Cancel timer"),
+            IdlerMenuItems::Postpone => write!(f, "Postpone stop by 30 minutes"),
+            IdlerMenuItems::Quit => write!(f, "Quit"),
         }
     }
 }
@@ -29,6 +54,14 @@ impl From<IdlerMenuItems> for String {
     }
 }
 
+fn get_preset_menu(prefix: &str, presets: &[(&str, &str)]) -> SystemTrayMenu {
+    presets
+        .iter()
+        .fold(SystemTrayMenu::new(), |menu, (duration, title)| {
+            menu.add_item(CustomMenuItem::new(format!("{prefix}{duration}"), *title))
+        })
+}
+
 pub fn get_tray_menu() -> SystemTray {
     SystemTray::new().with_menu(
         SystemTrayMenu::new()
@@ -37,6 +70,23 @@ pub fn get_tray_menu() -> SystemTray {
                 IdlerMenuItems::Show,
             ))
             .add_native_item(SystemTrayMenuItem::Separator)
+            .add_item(CustomMenuItem::new(IdlerMenuItems::Timer, get_timer_title(None)).disabled())
+            .add_submenu(SystemTraySubmenu::new(
+                "Pause for",
+                get_preset_menu(PAUSE_PREFIX, &PAUSE_PRESETS),
+            ))
+            .add_submenu(SystemTraySubmenu::new(
+                "Keep awake for",
+                get_preset_menu(KEEP_AWAKE_PREFIX, &KEEP_AWAKE_PRESETS),
+            ))
+            .add_item(CustomMenuItem::new(
+                IdlerMenuItems::CancelTimer,
+                IdlerMenuItems::CancelTimer,
+            ))
+            .add_item(
+                CustomMenuItem::new(IdlerMenuItems::Postpone, IdlerMenuItems::Postpone).disabled(),
+            )
+            .add_native_item(SystemTrayMenuItem::Separator)
             .add_item(CustomMenuItem::new(
                 IdlerMenuItems::Quit,
                 IdlerMenuItems::Quit,
@@ -44,6 +94,96 @@ pub fn get_tray_menu() -> SystemTray {
     )
 }
 
+fn get_timer_title(timer: Option<Timer>) -> String {
+    match timer {
+        Some(timer) if timer.mode == TimedMode::Pause => format!(
+            "Paused, resuming in {}",
+            idler_utils::format_countdown(timer.remaining())
+        ),
+        Some(timer) => format!(
+            "Awake, stopping in {}",
+            idler_utils::format_countdown(timer.remaining())
+        ),
+        None => "No timer running".to_string(),
+    }
+}
+
+/// The tooltip counting down to the stop once it was warned about.
+fn get_tooltip(schedule: Option<&status::ScheduleStatus>) -> String {
+    match schedule {
+        Some(schedule) if schedule.active && schedule.warned => format!(
+            "Smart Idler: {} at {} (in {})",
+            schedule.action,
+            schedule.time,
+            idler_utils::format_countdown(Duration::from_secs(
+                schedule.remaining_seconds.unwrap_or_default()
+            ))
+        ),
+        _ => "Smart Idler".to_string(),
+    }
+}
+
+/// Keeps the countdown item, the postpone item and the tooltip of the tray up to date.
+pub(crate) fn spawn_timer_countdown(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        let mut last_title = String::new();
+        let mut last_tooltip = String::new();
+        let mut last_pending = None;
+        loop {
+            interval.tick().await;
+            let context = app.state::<Arc<AppContext>>();
+            let schedule = status::schedule(&context).ok();
+            let pending = schedule.as_ref().is_some_and(|schedule| schedule.active);
+            if last_pending != Some(pending) {
+                let status = app
+                    .tray_handle()
+                    .get_item(&IdlerMenuItems::Postpone.to_string())
+                    .set_enabled(pending);
+                if let Err(err) = status {
+                    error!("Failed to update postpone item with err: {err}");
+                }
+                last_pending = Some(pending);
+            }
+            let tooltip = get_tooltip(schedule.as_ref());
+            if tooltip != last_tooltip {
+                if let Err(err) = app.tray_handle().set_tooltip(&tooltip) {
+                    trace!("Failed to update tooltip with err: {err}");
+                }
+                last_tooltip = tooltip;
+            }
+            let Some(service) = context.idle_service() else {
+                continue;
+            };
+            let title = get_timer_title(service.timer());
+            if title == last_title {
+                continue;
+            }
+            let status = app
+                .tray_handle()
+                .get_item(&IdlerMenuItems::Timer.to_string())
+                .set_title(&title);
+            if let Err(err) = status {
+                error!("Failed to update timer title with err: {err}");
+            }
+            last_title = title;
+        }
+    });
+}
+
+fn start_timer(app: &AppHandle, mode: TimedMode, duration: &str) {
+    let context = app.state::<Arc<AppContext>>();
+    let Some(service) = context.idle_service() else {
+        warn!("Idle service was not started");
+        return;
+    };
+    let status = idler_utils::parse_duration(duration).and_then(|duration| match mode {
+        TimedMode::Pause => service.pause_for(duration),
+        TimedMode::KeepAwake => service.keep_awake_for(duration),
+    });
+    trace!("Start {mode} timer status: {status:?}");
+}
+
 fn focus_window(app: &AppHandle) -> Result<()> {
     let Some(window) = app.get_window("controller") else {
         error!("Failed to get window");
@@ -79,37 +219,47 @@ fn create_window(app: &AppHandle) {
     }}
 }
 
+/// Focuses the window, creating it if it was closed.
+pub(crate) fn show_window(app: &AppHandle) {
+    if let Err(err) = focus_window(app) {
+        error!("Failed to focus window with err: {err}");
+        create_window(app);
+    }
+}
+
 pub(crate) fn handle_system_tray_event(app: &AppHandle, event: SystemTrayEvent) {
     match event {
         SystemTrayEvent::MenuItemClick { id, .. } => match id.as_str() {
-            "Show" => match focus_window(app) {
-                Ok(()) => {}
-                Err(err) => {
-                    error!("Failed to focus window with err: {err}");
-                    create_window(app);
-                }
+            "Show" => show_window(app),
+            "Cancel timer" => match app.state::<Arc<AppContext>>().idle_service() {
+                Some(service) => service.cancel_timer(),
+                None => warn!("Idle service was not started"),
             },
-            "Quit" => {
-                idler_utils::ExecState::stop();
-                let app_handle = cell_data::TAURI_APP_HANDLE.get().unwrap_or_else(|| {
-                    error!("Failed to get app handle");
-                    std::process::exit(0);
+            "Postpone stop by 30 minutes" => {
+                let context = Arc::clone(app.state::<Arc<AppContext>>().inner());
+                tauri::async_runtime::spawn(async move {
+                    if let Err(err) = context.postpone_stop(POSTPONE).await {
+                        warn!("Failed to postpone the stop, err: {err}");
+                    }
                 });
-                info!("Exiting app with app handle");
-                app_handle.exit(0);
             }
-            _ => {
-                warn!("Unknown menu item: {}", id);
+            "Quit" => {
+                app.state::<Arc<AppContext>>().shutdown();
+                info!("Exiting app");
+                app.exit(0);
+            }
+            id => {
+                if let Some(duration) = id.strip_prefix(PAUSE_PREFIX) {
+                    start_timer(app, TimedMode::Pause, duration);
+                } else if let Some(duration) = id.strip_prefix(KEEP_AWAKE_PREFIX) {
+                    start_timer(app, TimedMode::KeepAwake, duration);
+                } else {
+                    warn!("Unknown menu item: {}", id);
+                }
             }
         },
         SystemTrayEvent::LeftClick { .. } | SystemTrayEvent::DoubleClick { .. } => {
-            match focus_window(app) {
-                Ok(()) => {}
-                Err(err) => {
-                    error!("Failed to focus window with err: {err}");
-                    create_window(app);
-                }
-            }
+            show_window(app);
         }
         _ => {}
     }
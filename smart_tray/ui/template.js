@@ -1,69 +1,184 @@
 const { invoke } = window.__TAURI__.tauri;
+const { listen } = window.__TAURI__.event;
 
 //---Utils
 // eslint-disable-next-line i18n-text/no-en
 const INVALID_DATA_MESSAGE = "Invalid data";
-const LOADING_TEXT = "Loading...";
 const DEFAULT_SHUTDOWN_TIME = "19:00";
 const STOP_TIME = "STOP";
 const SUCCESSFUL_MESSAGE = "SUCCESSFUL";
-const SHUTDOWN_CLOCK_ID = "plugin:general|get_shutdown_clock";
-const SHUTDOWN_STATE_ID = "plugin:general|get_shutdown_state";
+const GET_STATUS_ID = "plugin:general|get_status";
 const SET_SHUTDOWN_ID = "plugin:general|set_shutdown";
 const SET_FORCE_INTERVAL_ID = "plugin:general|set_force_interval";
 const SET_REGISTRY_STATE_ID = "plugin:general|set_registry_state";
 const GET_STATE_ID = "plugin:general|get_state";
-
-//---Default values
-const DEFAULT_MINIMUM_INTERVAL = 60;
+const SET_TIMER_ID = "plugin:general|set_timer";
+const CANCEL_TIMER_ID = "plugin:general|cancel_timer";
+const GET_AUTOSTART_ID = "plugin:general|get_autostart";
+const SET_AUTOSTART_ID = "plugin:general|set_autostart";
+const GET_SCHEDULE_ACTION_ID = "plugin:general|get_schedule_action";
+const SET_SCHEDULE_ACTION_ID = "plugin:general|set_schedule_action";
+const GET_STOP_WARNINGS_ID = "plugin:general|get_stop_warnings";
+const SET_STOP_WARNINGS_ID = "plugin:general|set_stop_warnings";
+const POSTPONE_STOP_ID = "plugin:general|postpone_stop";
+const NO_TIMER_TEXT = "No timer running";
+const PAUSED_TEXT = "Paused";
+const UNKNOWN_TEXT = "-";
+const STATUS_EVENT = "idler-status";
 
 const DOM_ELEMENTS = {
   clockValue: document.getElementById("timed-input"),
   clockStatus: document.getElementById("timed-stop"),
   intervalData: document.getElementById("interval-data"),
   submitIntervalBtn: document.getElementById("submit-interval-btn"),
+  currentInterval: document.getElementById("current-interval"),
+  lastInput: document.getElementById("last-input"),
+  idleTime: document.getElementById("idle-time"),
+  nextInjection: document.getElementById("next-injection"),
+  timerStatus: document.getElementById("timer-status"),
+  timerDuration: document.getElementById("timer-duration"),
+  pauseTimerBtn: document.getElementById("pause-timer-btn"),
+  awakeTimerBtn: document.getElementById("awake-timer-btn"),
+  cancelTimerBtn: document.getElementById("cancel-timer-btn"),
+  autostart: document.getElementById("autostart"),
+  autostartStatus: document.getElementById("autostart-status"),
+  stopAction: document.getElementById("stop-action"),
+  stopActionBtn: document.getElementById("stop-action-btn"),
+  stopWarnings: document.getElementById("stop-warnings"),
+  stopWarningsBtn: document.getElementById("stop-warnings-btn"),
+  postponeBtn: document.getElementById("postpone-btn"),
 };
 
-//---Table refresh
+//---Errors
 
-const refreshTableList = [
-  ["current-interval", "plugin:general|get_data", "force_interval"],
-  ["last-input", "plugin:general|get_data", "robot_input"],
-];
+// Commands reject with `{ kind, message }`.
+function showError(textbox) {
+  return (error) => {
+    textbox.value = "";
+    textbox.placeholder = error.message ?? INVALID_DATA_MESSAGE;
+  };
+}
 
-function refreshStatsTable() {
-  Promise.all(refreshTableList.map(refreshTableElement));
+//---Timer
+
+function formatCountdown(totalSeconds) {
+  const hours = Math.floor(totalSeconds / 3600);
+  const minutes = String(Math.floor(totalSeconds / 60) % 60).padStart(2, "0");
+  const seconds = String(totalSeconds % 60).padStart(2, "0");
+  return `${hours}:${minutes}:${seconds}`;
 }
 
-function refreshTableElement(tableElement) {
-  document.getElementById(tableElement[0]).innerText = LOADING_TEXT;
-  let inputPromise;
-  if (tableElement[2] !== undefined) {
-    inputPromise = invoke(tableElement[1], { data: tableElement[2] });
+function renderTimer(timer) {
+  if (timer.mode === null) {
+    DOM_ELEMENTS.timerStatus.innerText = NO_TIMER_TEXT;
+  } else if (timer.mode === "Pause") {
+    DOM_ELEMENTS.timerStatus.innerText = `Paused until ${timer.deadline} (${formatCountdown(timer.remaining_seconds)})`;
   } else {
-    inputPromise = invoke(tableElement[1]);
+    DOM_ELEMENTS.timerStatus.innerText = `Awake until ${timer.deadline} (${formatCountdown(timer.remaining_seconds)})`;
+  }
+}
+
+//---Status
+
+function renderStatus(status) {
+  DOM_ELEMENTS.currentInterval.innerText = status.force_interval;
+  DOM_ELEMENTS.lastInput.innerText = status.last_injection;
+  DOM_ELEMENTS.idleTime.innerText =
+    status.idle_seconds === null
+      ? UNKNOWN_TEXT
+      : formatCountdown(status.idle_seconds);
+  DOM_ELEMENTS.nextInjection.innerText =
+    status.next_injection_seconds === null
+      ? PAUSED_TEXT
+      : formatCountdown(status.next_injection_seconds);
+  renderTimer(status.timer);
+  DOM_ELEMENTS.clockStatus.checked = status.schedule.active;
+}
+
+function startTimer(mode) {
+  const textbox = DOM_ELEMENTS.timerDuration;
+  if (textbox.value === "") {
+    textbox.placeholder = INVALID_DATA_MESSAGE;
+    return;
   }
   // eslint-disable-next-line github/no-then
-  inputPromise.then((input) => {
-    document.getElementById(tableElement[0]).innerText = input;
-  });
+  invoke(SET_TIMER_ID, { mode: mode, duration: textbox.value }).then(() => {
+    textbox.value = "";
+    textbox.placeholder = "";
+  }, showError(textbox));
 }
 
-//---Event Listeners
+//---Autostart
 
-//-On Load
-window.addEventListener("DOMContentLoaded", () => {
-  refreshStatsTable();
+// A stale entry launches an executable that moved, switching it on again repairs it.
+function renderAutostart(autostart) {
+  DOM_ELEMENTS.autostart.checked = autostart.state === "enabled";
+  DOM_ELEMENTS.autostartStatus.innerText =
+    autostart.state === "stale"
+      ? `Points to missing ${autostart.executable}, switch on to fix`
+      : "";
+}
 
-  //-Shutdown load
+function showAutostartError(error) {
+  DOM_ELEMENTS.autostartStatus.innerText =
+    error.message ?? INVALID_DATA_MESSAGE;
+}
+
+//---Stop action
+
+// The stored action is shown as placeholder, so typing replaces it.
+function renderStopAction(action) {
+  DOM_ELEMENTS.stopAction.value = "";
+  DOM_ELEMENTS.stopAction.placeholder = `At stop time: ${action}`;
+}
+
+function setStopAction() {
+  const textbox = DOM_ELEMENTS.stopAction;
+  const action = textbox.value;
   // eslint-disable-next-line github/no-then
-  invoke(SHUTDOWN_CLOCK_ID, {}).then(
-    (value) => (DOM_ELEMENTS.clockValue.value = value),
+  invoke(SET_SCHEDULE_ACTION_ID, { action: action }).then(
+    () => renderStopAction(action.trim()),
+    showError(textbox),
   );
+}
+
+//---Stop warnings
+
+function renderStopWarnings(minutes) {
+  DOM_ELEMENTS.stopWarnings.value = "";
+  DOM_ELEMENTS.stopWarnings.placeholder = `Warn at: ${minutes} min`;
+}
+
+function setStopWarnings() {
+  const textbox = DOM_ELEMENTS.stopWarnings;
   // eslint-disable-next-line github/no-then
-  invoke(SHUTDOWN_STATE_ID, {}).then(
-    (checked) => (DOM_ELEMENTS.clockStatus.checked = checked),
+  invoke(SET_STOP_WARNINGS_ID, { minutes: textbox.value }).then(
+    // eslint-disable-next-line github/no-then
+    () => invoke(GET_STOP_WARNINGS_ID, {}).then(renderStopWarnings),
+    showError(textbox),
   );
+}
+
+//---Event Listeners
+
+//-Status pushed by the app on every change, and every second while visible
+listen(STATUS_EVENT, (event) => renderStatus(event.payload));
+
+//-On Load
+window.addEventListener("DOMContentLoaded", () => {
+  // eslint-disable-next-line github/no-then
+  invoke(GET_STATUS_ID, {}).then((status) => {
+    renderStatus(status);
+    DOM_ELEMENTS.clockValue.value = status.schedule.active
+      ? status.schedule.time
+      : STOP_TIME;
+  });
+  // eslint-disable-next-line github/no-then
+  invoke(GET_AUTOSTART_ID, {}).then(renderAutostart, showAutostartError);
+  // eslint-disable-next-line github/no-then
+  invoke(GET_SCHEDULE_ACTION_ID, {}).then(renderStopAction);
+  // eslint-disable-next-line github/no-then
+  invoke(GET_STOP_WARNINGS_ID, {}).then(renderStopWarnings);
 });
 
 //-Buttons
@@ -71,38 +186,35 @@ window.addEventListener("DOMContentLoaded", () => {
 //-Auto shutdown
 DOM_ELEMENTS.clockValue.addEventListener("change", () => {
   const timeValue = DOM_ELEMENTS.clockValue.value;
-  invoke(SET_SHUTDOWN_ID, { hour: timeValue });
-  DOM_ELEMENTS.clockStatus.checked = true;
+  // eslint-disable-next-line github/no-then
+  invoke(SET_SHUTDOWN_ID, { hour: timeValue }).catch(
+    showError(DOM_ELEMENTS.clockValue),
+  );
 });
 
 //-Enable shutdown
 DOM_ELEMENTS.clockStatus.addEventListener("click", () => {
+  let timeValue = STOP_TIME;
   if (DOM_ELEMENTS.clockStatus.checked) {
-    let timeValue = DOM_ELEMENTS.clockValue.value;
+    timeValue = DOM_ELEMENTS.clockValue.value;
     if (timeValue === STOP_TIME || timeValue === "") {
       timeValue = DEFAULT_SHUTDOWN_TIME;
     }
-    invoke(SET_SHUTDOWN_ID, { hour: timeValue });
-    DOM_ELEMENTS.clockValue.value = timeValue;
-  } else {
-    invoke(SET_SHUTDOWN_ID, { hour: STOP_TIME });
-    DOM_ELEMENTS.clockValue.value = STOP_TIME;
   }
+  // eslint-disable-next-line github/no-then
+  invoke(SET_SHUTDOWN_ID, { hour: timeValue }).then(
+    () => (DOM_ELEMENTS.clockValue.value = timeValue),
+    showError(DOM_ELEMENTS.clockValue),
+  );
 });
 
 async function update_interval() {
   const textbox = DOM_ELEMENTS.intervalData;
-  if (isNaN(textbox.value) || textbox.value < DEFAULT_MINIMUM_INTERVAL) {
-    textbox.placeholder = INVALID_DATA_MESSAGE;
+  // eslint-disable-next-line github/no-then
+  invoke(SET_FORCE_INTERVAL_ID, { interval: textbox.value }).then(() => {
     textbox.value = "";
-  } else {
-    invoke(SET_FORCE_INTERVAL_ID, {
-      interval: textbox.value,
-    });
     textbox.placeholder = SUCCESSFUL_MESSAGE;
-    textbox.value = "";
-    refreshStatsTable();
-  }
+  }, showError(textbox));
 }
 
 //-Submit button
@@ -115,3 +227,49 @@ DOM_ELEMENTS.intervalData.addEventListener("keypress", async (event) => {
     DOM_ELEMENTS.submitIntervalBtn.click();
   }
 });
+
+//-Timer buttons
+DOM_ELEMENTS.pauseTimerBtn.addEventListener("click", () => startTimer("pause"));
+DOM_ELEMENTS.awakeTimerBtn.addEventListener("click", () =>
+  startTimer("keep_awake"),
+);
+DOM_ELEMENTS.cancelTimerBtn.addEventListener("click", () =>
+  invoke(CANCEL_TIMER_ID, {}),
+);
+
+//-Start at login
+DOM_ELEMENTS.autostart.addEventListener("click", () => {
+  const enabled = DOM_ELEMENTS.autostart.checked;
+  // eslint-disable-next-line github/no-then
+  invoke(SET_AUTOSTART_ID, { enabled: enabled }).then(
+    renderAutostart,
+    (error) => {
+      DOM_ELEMENTS.autostart.checked = !enabled;
+      showAutostartError(error);
+    },
+  );
+});
+
+//-Stop action
+DOM_ELEMENTS.stopActionBtn.addEventListener("click", setStopAction);
+DOM_ELEMENTS.stopAction.addEventListener("keypress", (event) => {
+  if (event.key === "Enter") {
+    event.preventDefault();
+    setStopAction();
+  }
+});
+
+//-Stop warnings
+DOM_ELEMENTS.stopWarningsBtn.addEventListener("click", setStopWarnings);
+DOM_ELEMENTS.stopWarnings.addEventListener("keypress", (event) => {
+  if (event.key === "Enter") {
+    event.preventDefault();
+    setStopWarnings();
+  }
+});
+
+//-Postpone the stop
+DOM_ELEMENTS.postponeBtn.addEventListener("click", () => {
+  // eslint-disable-next-line github/no-then
+  invoke(POSTPONE_STOP_ID, {}).catch(showError(DOM_ELEMENTS.stopWarnings));
+});